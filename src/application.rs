@@ -1,22 +1,16 @@
 use log::info;
-use std::cell::RefCell;
 use std::rc::Rc;
 use thermite_core::{
-    input::{keyboard::KeyboardEvent, mouse::MouseEvent},
+    input::{
+        context::{ConsumePolicy, InputContext},
+        mouse::MouseEvent,
+    },
     messaging::{
-        bus::{BusRequest, EventBus},
+        bus::BusRequest,
         event::{ThermiteEvent, ThermiteEventType},
-        publish::Publisher,
         subscribe::Subscriber,
     },
-    thermite_logging,
-};
-use thermite_gfx::{
-    window::Window,
-    winit::{
-        event::{ElementState, Event as WinitEvent, WindowEvent},
-        event_loop::ControlFlow,
-    },
+    platform::app::{AppContext, System},
 };
 
 // ============================== TEST STRUCTS ============================== //
@@ -24,176 +18,46 @@ pub struct TestSubscriber {}
 impl Subscriber<ThermiteEventType, ThermiteEvent> for TestSubscriber {
     // ! Although we get a ThermiteEvent enum, it is guaranteed to be only of the category that we are subscribed to
     fn on_event(&self, event: &ThermiteEvent) -> BusRequest {
-        info!("Test subscriber received event: {:?}", event);
+        match event {
+            // ! Cursor motion is really noisy, so we don't log it
+            ThermiteEvent::Mouse(MouseEvent::Motion(_))
+            | ThermiteEvent::Mouse(MouseEvent::RawMotion(_)) => (),
+            _ => info!("Test subscriber received event: {:?}", event),
+        }
         BusRequest::NoActionNeeded
     }
 }
-
-pub struct TestPublisher {}
-impl Publisher<ThermiteEventType, ThermiteEvent> for TestPublisher {}
 // ============================== END TEST STRUCTS ============================== //
 
-type ThermiteEventBus = EventBus<ThermiteEventType, ThermiteEvent>;
-// TODO: Make this a Singleton
-pub struct Application {
-    event_bus: Rc<RefCell<ThermiteEventBus>>, // Single-threaded, for now
-    window: Window<ThermiteEvent>,
-    publ: Rc<TestPublisher>,
+/// The test application's only system, which hooks a `TestSubscriber` up to the bus
+pub struct TestSystem {
     sub: Rc<TestSubscriber>,
 }
 
-impl Default for Application {
+impl Default for TestSystem {
     fn default() -> Self {
         Self {
-            event_bus: Rc::new(RefCell::new(
-                EventBus::<ThermiteEventType, ThermiteEvent>::default(),
-            )),
-            window: Window::default(),
-            publ: Rc::new(TestPublisher {}),
             sub: Rc::new(TestSubscriber {}),
         }
     }
 }
 
-impl Application {
-    pub fn new(name: &str, size: [u32; 2]) -> Self {
-        Self {
-            event_bus: Rc::new(RefCell::new(
-                EventBus::<ThermiteEventType, ThermiteEvent>::default(),
-            )),
-            window: Window::new(name, size).expect("Couldn't create window"),
-            publ: Rc::new(TestPublisher {}),
-            sub: Rc::new(TestSubscriber {}),
-        }
-    }
-
-    fn init(&mut self) {
-        thermite_logging::init().expect("Couldn't initialize logging");
-        // Subscribe our subscriber to Input events
-        self.event_bus
+impl System for TestSystem {
+    fn on_init(&mut self, ctx: &mut AppContext) {
+        let mut bus = ctx
+            .event_bus()
             .try_borrow_mut()
-            .expect("Couldn't borrow event bus as mutable")
-            .subscribe(&self.sub, ThermiteEventType::Input);
-    }
-
-    pub fn run(&mut self) {
-        self.init();
-        // Event loop requires ownership of captured environment, just clone our rc pointers for it to take...
-        let eb = self.event_bus.clone();
-        let publ = self.publ.clone();
-        self.window
-            .event_loop()
-            .run(move |event, _, control_flow| match event {
-                // Pre-event handling code
-                WinitEvent::NewEvents(_) => (),
-                // Custom events
-                WinitEvent::UserEvent(_) => (),
-                // Events coming straight from hardware devices
-                WinitEvent::DeviceEvent { .. } => (),
-                // Events emitted by the winit window
-                WinitEvent::WindowEvent { event, .. } => match event {
-                    // TODO: Would be nice to not have a monolithic handler...
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput { input, .. } => match input.state {
-                        ElementState::Pressed => {
-                            let evt = KeyboardEvent::KeyPressed(input.into());
-                            publ.publish_event(
-                                &evt.into(),
-                                &mut eb
-                                    .try_borrow_mut()
-                                    .expect("Couldn't borrow the event bus as mutable"),
-                            );
-                        }
-                        ElementState::Released => {
-                            let evt = KeyboardEvent::KeyReleased(input.into());
-                            publ.publish_event(
-                                &evt.into(),
-                                &mut eb
-                                    .try_borrow_mut()
-                                    .expect("Couldn't borrow the event bus as mutable"),
-                            );
-                        }
-                    },
-                    WindowEvent::ModifiersChanged(modifiers_state) => {
-                        let evt = KeyboardEvent::ModifiersChanged(modifiers_state.into());
-                        publ.publish_event(
-                            &evt.into(),
-                            &mut eb
-                                .try_borrow_mut()
-                                .expect("Couldn't borrow the event bus as mutable"),
-                        );
-                    }
-                    WindowEvent::MouseInput { state, button, .. } => match state {
-                        ElementState::Pressed => {
-                            let evt = MouseEvent::ButtonPressed(button);
-                            publ.publish_event(
-                                &evt.into(),
-                                &mut eb
-                                    .try_borrow_mut()
-                                    .expect("Couldn't borrow the event bus as mutable"),
-                            );
-                        }
-                        ElementState::Released => {
-                            let evt = MouseEvent::ButtonReleased(button);
-                            publ.publish_event(
-                                &evt.into(),
-                                &mut eb
-                                    .try_borrow_mut()
-                                    .expect("Couldn't borrow the event bus as mutable"),
-                            );
-                        }
-                    },
-                    WindowEvent::MouseWheel { delta, .. } => {
-                        let evt = MouseEvent::Scroll(delta.into());
-                        publ.publish_event(
-                            &evt.into(),
-                            &mut eb
-                                .try_borrow_mut()
-                                .expect("Couldn't borrow the event bus as mutable"),
-                        );
-                    }
-                    WindowEvent::CursorMoved { position, .. } => {
-                        // ! Leaving this commented out for now as it's really noisy
-                        // let evt = MouseEvent::Motion(position.into());
-                        // publ.publish_event(
-                        //     &evt.into(),
-                        //     &mut eb
-                        //         .try_borrow_mut()
-                        //         .expect("Couldn't borrow the event bus as mutable"),
-                        // );
-                    }
-                    WindowEvent::CursorEntered { .. } => {
-                        let evt = MouseEvent::EnteredWindow;
-                        publ.publish_event(
-                            &evt.into(),
-                            &mut eb
-                                .try_borrow_mut()
-                                .expect("Couldn't borrow the event bus as mutable"),
-                        );
-                    }
-                    WindowEvent::CursorLeft { .. } => {
-                        let evt = MouseEvent::LeftWindow;
-                        publ.publish_event(
-                            &evt.into(),
-                            &mut eb
-                                .try_borrow_mut()
-                                .expect("Couldn't borrow the event bus as mutable"),
-                        );
-                    }
-                    _ => (),
-                },
-                // Continuous dynamic graphics rendering (loop "main body")
-                WinitEvent::MainEventsCleared => (),
-                // Static graphics rendering (mainly for semi-static GUIs, etc.)
-                WinitEvent::RedrawRequested(_) => (),
-                // Rendering cleanup
-                WinitEvent::RedrawEventsCleared => (),
-                // Application resumed
-                WinitEvent::Resumed => (),
-                // Application suspended
-                WinitEvent::Suspended => (),
-                // Last event to be emitted, period.
-                WinitEvent::LoopDestroyed => (),
-            });
+            .expect("Couldn't borrow event bus as mutable");
+        // Subscribe our subscriber to Input and Application events
+        bus.subscribe(&self.sub, ThermiteEventType::Input);
+        bus.subscribe(&self.sub, ThermiteEventType::Application);
+        // Gameplay input sits at the bottom of the context stack, menus and consoles get pushed on top of it
+        ctx.input_contexts()
+            .try_borrow_mut()
+            .expect("Couldn't borrow the input context stack as mutable")
+            .push(
+                InputContext::new("gameplay", ConsumePolicy::ConsumeBound),
+                &mut bus,
+            );
     }
 }
@@ -1,7 +1,55 @@
 pub mod application;
-use application::Application;
+use application::TestSystem;
+use log::LevelFilter;
+use std::path::Path;
+use thermite_core::assets::{
+    integrity::AssetManifest,
+    vfs::{self, Vfs},
+};
+use thermite_core::console::{shell::Console, system::ConsoleSystem};
+use thermite_core::logging::logger::LoggerBuilder;
+use thermite_core::tools::crash::{CrashReportSystem, CrashReporter};
+use thermite_gfx::app::App;
+use thermite_gfx::hal::hal_state;
 
 fn main() {
-    let mut app = Application::new("Test Application", [800, 600]);
-    app.run();
+    let logger = LoggerBuilder::new()
+        .with_level(LevelFilter::Info)
+        .with_module_level("gfx_backend_vulkan", LevelFilter::Warn)
+        .with_console()
+        .with_file(Path::new("logs/thermite.log"), 10 * 1024 * 1024, 3)
+        .expect("Couldn't open the log file")
+        .init()
+        .expect("Couldn't initialize logging");
+    CrashReporter::new()
+        .with_report_dir("crashes")
+        .with_log_buffer(logger.ring_buffer(), 100)
+        .install();
+    let mut vfs = Vfs::with_default_mounts(Some("thermite"));
+    if let Some(user_data) = vfs::user_data_dir("thermite") {
+        vfs.mount_mods(&user_data.join("mods"))
+            .expect("Couldn't mount the mods");
+    }
+    let builder = App::builder()
+        .with_title("Test Application")
+        .with_size([800, 600])
+        .with_window_config_file("window.ron")
+        .with_user_config_file("thermite.toml")
+        .with_command_line_args(std::env::args().skip(1))
+        .with_vfs(vfs)
+        .with_hot_reload(cfg!(debug_assertions))
+        .with_system(CrashReportSystem::new())
+        .with_system(TestSystem::default());
+    let mut console = Console::new().with_persist_file("thermite.toml");
+    hal_state::register_console_commands(&mut console, &builder.renderer())
+        .expect("Couldn't register the renderer's console commands");
+    let mut builder = builder.with_system(ConsoleSystem::new(console));
+    // Shipped builds check their assets against the manifest made by thermite_manifest, when there's one
+    let manifest = Path::new("manifest.ron");
+    if !cfg!(debug_assertions) && manifest.exists() {
+        builder = builder.with_manifest(
+            AssetManifest::load(manifest).expect("Couldn't load the asset manifest"),
+        );
+    }
+    builder.build().expect("Couldn't create application").run();
 }
@@ -0,0 +1,784 @@
+/*
+    ABSTRACT: Packed asset archives, so that an application can ship a few archives rather than hundreds of loose files.
+    `ArchiveBuilder` packs files (see the `thermite_pack` tool), and `ArchiveSource` mounts an archive in the `Vfs`,
+    memory-mapped or read through the file.
+
+    Layout (all integers little-endian):
+    - Header: magic `TPAK`, format version (u16), reserved (u16), entry count (u32), index offset (u64),
+      index size (u64), CRC-32 of the index (u32)
+    - The data of every entry, one after the other, each compressed on its own
+    - The index: for every entry, its path length (u16) and UTF-8 virtual path, offset (u64), stored size (u64),
+      original size (u64), compression (u8) and CRC-32 of the original data (u32)
+*/
+use crate::assets::vfs::{MountSource, VfsError, VirtualPath};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Crc};
+use memmap::Mmap;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The first bytes of every archive
+pub const ARCHIVE_MAGIC: &[u8; 4] = b"TPAK";
+/// The version of the format written by `ArchiveBuilder`
+pub const ARCHIVE_VERSION: u16 = 1;
+/// The usual extension of archives, see `Vfs::with_default_mounts`
+pub const ARCHIVE_EXTENSION: &str = "tpak";
+const HEADER_SIZE: usize = 32;
+/// Size of an index entry with an empty path: its path length, offset, sizes, compression and checksum
+const MIN_ENTRY_SIZE: usize = 2 + 8 + 8 + 8 + 1 + 4;
+
+/// Extensions of formats which are already compressed, stored as they are by default (see `ArchiveBuilder::with_stored_extensions`)
+pub const STORED_EXTENSIONS: &[&str] =
+    &["png", "jpg", "jpeg", "ogg", "mp3", "zip", ARCHIVE_EXTENSION];
+
+/// Errors relating to archives
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    Vfs(VfsError),
+    /// The file doesn't start with `ARCHIVE_MAGIC`
+    NotAnArchive(String),
+    UnsupportedVersion(u16),
+    /// The header or index is truncated or inconsistent
+    Corrupt(String),
+    /// An entry's data doesn't match its checksum
+    ChecksumMismatch(String),
+    DuplicateEntry(String),
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(error: io::Error) -> Self {
+        ArchiveError::Io(error)
+    }
+}
+
+impl From<VfsError> for ArchiveError {
+    fn from(error: VfsError) -> Self {
+        ArchiveError::Vfs(error)
+    }
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Io(error) => write!(fmt, "{:?}: {}", self, error),
+            ArchiveError::Vfs(error) => write!(fmt, "{}", error),
+            ArchiveError::NotAnArchive(path) => write!(fmt, "{:?}: {}", self, path),
+            ArchiveError::UnsupportedVersion(version) => write!(fmt, "{:?}: {}", self, version),
+            ArchiveError::Corrupt(reason) => write!(fmt, "{:?}: {}", self, reason),
+            ArchiveError::ChecksumMismatch(path) => write!(fmt, "{:?}: {}", self, path),
+            ArchiveError::DuplicateEntry(path) => write!(fmt, "{:?}: {}", self, path),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::Io(error) => Some(error),
+            ArchiveError::Vfs(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ArchiveError> for io::Error {
+    fn from(error: ArchiveError) -> Self {
+        match error {
+            ArchiveError::Io(error) => error,
+            error => io::Error::new(io::ErrorKind::InvalidData, error.to_string()),
+        }
+    }
+}
+
+/// How an entry's data is stored
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ArchiveCompression {
+    Stored,
+    Deflate,
+}
+
+impl ArchiveCompression {
+    fn to_byte(self) -> u8 {
+        match self {
+            ArchiveCompression::Stored => 0,
+            ArchiveCompression::Deflate => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(ArchiveCompression::Stored),
+            1 => Some(ArchiveCompression::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// A file in an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    path: VirtualPath,
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    compression: ArchiveCompression,
+    checksum: u32,
+}
+
+impl ArchiveEntry {
+    pub fn path(&self) -> &VirtualPath {
+        &self.path
+    }
+
+    /// Where the entry's data starts in the archive
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Size of the data in the archive
+    pub fn stored_size(&self) -> u64 {
+        self.stored_size
+    }
+
+    /// Size of the data once decompressed
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn compression(&self) -> ArchiveCompression {
+        self.compression
+    }
+
+    /// CRC-32 of the decompressed data
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// Reads little-endian integers out of a header or index, failing on truncation
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ArchiveError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| ArchiveError::Corrupt(format!("Truncated at byte {}", self.pos)))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ArchiveError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ArchiveError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ArchiveError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ArchiveError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+struct Header {
+    entry_count: u32,
+    index_offset: u64,
+    index_size: u64,
+    index_checksum: u32,
+}
+
+impl Header {
+    fn parse(bytes: &[u8], archive: &Path) -> Result<Self, ArchiveError> {
+        let mut cursor = Cursor { bytes, pos: 0 };
+        if bytes.len() < HEADER_SIZE || cursor.take(4)? != ARCHIVE_MAGIC {
+            return Err(ArchiveError::NotAnArchive(archive.display().to_string()));
+        }
+        let version = cursor.u16()?;
+        if version != ARCHIVE_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(version));
+        }
+        let _reserved = cursor.u16()?;
+        Ok(Self {
+            entry_count: cursor.u32()?,
+            index_offset: cursor.u64()?,
+            index_size: cursor.u64()?,
+            index_checksum: cursor.u32()?,
+        })
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(ARCHIVE_MAGIC)?;
+        out.write_all(&ARCHIVE_VERSION.to_le_bytes())?;
+        out.write_all(&0u16.to_le_bytes())?;
+        out.write_all(&self.entry_count.to_le_bytes())?;
+        out.write_all(&self.index_offset.to_le_bytes())?;
+        out.write_all(&self.index_size.to_le_bytes())?;
+        out.write_all(&self.index_checksum.to_le_bytes())
+    }
+}
+
+fn parse_index(
+    header: &Header,
+    index: &[u8],
+    archive_len: u64,
+) -> Result<HashMap<VirtualPath, ArchiveEntry>, ArchiveError> {
+    if checksum(index) != header.index_checksum {
+        return Err(ArchiveError::Corrupt("Index checksum mismatch".to_string()));
+    }
+    let mut cursor = Cursor {
+        bytes: index,
+        pos: 0,
+    };
+    // The entry count isn't covered by the checksum, so it mustn't size the map before the index is known to hold that many
+    if header.entry_count as usize > index.len() / MIN_ENTRY_SIZE {
+        return Err(ArchiveError::Corrupt(format!(
+            "The index can't hold {} entries",
+            header.entry_count
+        )));
+    }
+    let mut entries = HashMap::with_capacity(header.entry_count as usize);
+    for _ in 0..header.entry_count {
+        let path_len = cursor.u16()? as usize;
+        let path = std::str::from_utf8(cursor.take(path_len)?)
+            .map_err(|_| ArchiveError::Corrupt("Entry path isn't UTF-8".to_string()))?;
+        let entry = ArchiveEntry {
+            path: VirtualPath::new(path)?,
+            offset: cursor.u64()?,
+            stored_size: cursor.u64()?,
+            size: cursor.u64()?,
+            compression: {
+                let byte = cursor.u8()?;
+                ArchiveCompression::from_byte(byte).ok_or_else(|| {
+                    ArchiveError::Corrupt(format!("Unknown compression {} for {}", byte, path))
+                })?
+            },
+            checksum: cursor.u32()?,
+        };
+        let in_bounds = entry
+            .offset
+            .checked_add(entry.stored_size)
+            .is_some_and(|end| end <= archive_len);
+        if !in_bounds {
+            return Err(ArchiveError::Corrupt(format!(
+                "{} lies outside the archive",
+                entry.path
+            )));
+        }
+        if let Some(duplicate) = entries.insert(entry.path.clone(), entry) {
+            return Err(ArchiveError::DuplicateEntry(duplicate.path.to_string()));
+        }
+    }
+    Ok(entries)
+}
+
+enum Storage {
+    Mapped(Mmap),
+    File(Mutex<File>),
+}
+
+/// An archive mounted in a `Vfs`. Entries are decompressed and checked against their checksum on every read.
+pub struct ArchiveSource {
+    path: PathBuf,
+    storage: Storage,
+    entries: HashMap<VirtualPath, ArchiveEntry>,
+}
+
+impl ArchiveSource {
+    /// Opens an archive and memory-maps it, so reads don't go through the file (see `mapped`).
+    ///
+    /// The archive must not be modified while it's open.
+    pub fn open(path: &Path) -> Result<Self, ArchiveError> {
+        let file = File::open(path)?;
+        // Safety: the map is read-only, and archives aren't written to while an application uses them
+        let map = unsafe { Mmap::map(&file)? };
+        let header = Header::parse(&map, path)?;
+        let index = usize::try_from(header.index_offset)
+            .ok()
+            .and_then(|start| Some(start..start.checked_add(header.index_size as usize)?))
+            .and_then(|range| map.get(range))
+            .ok_or_else(|| ArchiveError::Corrupt("Index lies outside the archive".to_string()))?;
+        let entries = parse_index(&header, index, map.len() as u64)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            storage: Storage::Mapped(map),
+            entries,
+        })
+    }
+
+    /// Opens an archive without memory-mapping it, every read seeks through the file instead
+    pub fn open_unmapped(path: &Path) -> Result<Self, ArchiveError> {
+        let mut file = File::open(path)?;
+        let archive_len = file.metadata()?.len();
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)
+            .map_err(|_| ArchiveError::NotAnArchive(path.display().to_string()))?;
+        let header = Header::parse(&header, path)?;
+        let index_in_bounds = header
+            .index_offset
+            .checked_add(header.index_size)
+            .is_some_and(|end| end <= archive_len);
+        if !index_in_bounds {
+            return Err(ArchiveError::Corrupt(
+                "Index lies outside the archive".to_string(),
+            ));
+        }
+        let mut index = vec![0; header.index_size as usize];
+        file.seek(SeekFrom::Start(header.index_offset))?;
+        file.read_exact(&mut index)?;
+        let entries = parse_index(&header, &index, archive_len)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            storage: Storage::File(Mutex::new(file)),
+            entries,
+        })
+    }
+
+    /// Where the archive lives on disk
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entry(&self, path: &VirtualPath) -> Option<&ArchiveEntry> {
+        self.entries.get(path)
+    }
+
+    /// Every entry, in no particular order
+    pub fn entries(&self) -> impl Iterator<Item = &ArchiveEntry> {
+        self.entries.values()
+    }
+
+    /// The data of a stored (uncompressed) entry, straight from the memory map, without copying or checking it.
+    ///
+    /// `None` if the entry doesn't exist, is compressed, or the archive isn't memory-mapped.
+    pub fn mapped(&self, path: &VirtualPath) -> Option<&[u8]> {
+        let entry = self.entries.get(path)?;
+        match (&self.storage, entry.compression) {
+            (Storage::Mapped(map), ArchiveCompression::Stored) => {
+                Some(&map[entry.offset as usize..(entry.offset + entry.stored_size) as usize])
+            }
+            _ => None,
+        }
+    }
+
+    /// The entry's data as it's stored in the archive
+    fn raw(&self, entry: &ArchiveEntry) -> io::Result<Cow<'_, [u8]>> {
+        match &self.storage {
+            Storage::Mapped(map) => Ok(Cow::Borrowed(
+                &map[entry.offset as usize..(entry.offset + entry.stored_size) as usize],
+            )),
+            Storage::File(file) => {
+                let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let mut bytes = vec![0; entry.stored_size as usize];
+                file.seek(SeekFrom::Start(entry.offset))?;
+                file.read_exact(&mut bytes)?;
+                Ok(Cow::Owned(bytes))
+            }
+        }
+    }
+
+    /// Reads, decompresses and checks an entry
+    pub fn read_entry(&self, path: &VirtualPath) -> Result<Vec<u8>, ArchiveError> {
+        let entry = self
+            .entries
+            .get(path)
+            .ok_or_else(|| VfsError::NotFound(path.to_string()))?;
+        let raw = self.raw(entry)?;
+        let bytes = match entry.compression {
+            ArchiveCompression::Stored => raw.into_owned(),
+            ArchiveCompression::Deflate => {
+                // The index isn't trusted: the size isn't preallocated, and decompression stops one byte past it
+                let mut bytes = vec![];
+                DeflateDecoder::new(raw.as_ref())
+                    .take(entry.size.saturating_add(1))
+                    .read_to_end(&mut bytes)
+                    .map_err(|err| {
+                        ArchiveError::Corrupt(format!(
+                            "{} in {}: {}",
+                            path,
+                            self.path.display(),
+                            err
+                        ))
+                    })?;
+                bytes
+            }
+        };
+        if bytes.len() as u64 > entry.size {
+            return Err(ArchiveError::Corrupt(format!(
+                "{} in {} is larger than its listed size of {} bytes",
+                path,
+                self.path.display(),
+                entry.size
+            )));
+        }
+        if bytes.len() as u64 != entry.size || checksum(&bytes) != entry.checksum {
+            return Err(ArchiveError::ChecksumMismatch(format!(
+                "{} in {}",
+                path,
+                self.path.display()
+            )));
+        }
+        Ok(bytes)
+    }
+}
+
+impl MountSource for ArchiveSource {
+    fn contains(&self, path: &VirtualPath) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn read(&self, path: &VirtualPath) -> io::Result<Vec<u8>> {
+        Ok(self.read_entry(path)?)
+    }
+
+    fn real_path(&self, _path: &VirtualPath) -> Option<PathBuf> {
+        None
+    }
+
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+enum PendingData {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+struct PendingEntry {
+    path: VirtualPath,
+    data: PendingData,
+    compression: ArchiveCompression,
+}
+
+/// What `ArchiveBuilder::write` packed
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ArchiveStats {
+    pub entries: usize,
+    /// Entries which ended up compressed, files which don't shrink are stored
+    pub compressed_entries: usize,
+    /// Total size of the files before packing
+    pub original_size: u64,
+    /// Size of the archive, including its header and index
+    pub archive_size: u64,
+}
+
+/// Packs files into an archive. Files are only read when the archive is written.
+pub struct ArchiveBuilder {
+    entries: Vec<PendingEntry>,
+    level: u32,
+    stored_extensions: Vec<String>,
+}
+
+impl Default for ArchiveBuilder {
+    /// Compression level 6, and `STORED_EXTENSIONS` stored as they are
+    fn default() -> Self {
+        Self {
+            entries: vec![],
+            level: 6,
+            stored_extensions: STORED_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the deflate compression level, from 0 (fastest) to 9 (smallest)
+    pub fn with_compression_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Sets the extensions (without the dot, case insensitive) of files to store rather than compress
+    pub fn with_stored_extensions<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        extensions: I,
+    ) -> Self {
+        self.stored_extensions = extensions
+            .into_iter()
+            .map(|ext| ext.into().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// How a file would be packed, based on its extension
+    pub fn compression_for(&self, path: &VirtualPath) -> ArchiveCompression {
+        let stored = path.extension().is_some_and(|ext| {
+            self.stored_extensions
+                .iter()
+                .any(|stored| stored.eq_ignore_ascii_case(ext))
+        });
+        if stored || self.level == 0 {
+            ArchiveCompression::Stored
+        } else {
+            ArchiveCompression::Deflate
+        }
+    }
+
+    fn push(&mut self, path: VirtualPath, data: PendingData) -> Result<(), ArchiveError> {
+        if self.entries.iter().any(|entry| entry.path == path) {
+            return Err(ArchiveError::DuplicateEntry(path.to_string()));
+        }
+        let compression = self.compression_for(&path);
+        self.entries.push(PendingEntry {
+            path,
+            data,
+            compression,
+        });
+        Ok(())
+    }
+
+    /// Adds data at the given virtual path
+    pub fn add_bytes(&mut self, path: &str, bytes: Vec<u8>) -> Result<(), ArchiveError> {
+        self.push(VirtualPath::new(path)?, PendingData::Bytes(bytes))
+    }
+
+    /// Adds a file on disk at the given virtual path
+    pub fn add_file(&mut self, path: &str, file: &Path) -> Result<(), ArchiveError> {
+        self.push(
+            VirtualPath::new(path)?,
+            PendingData::File(file.to_path_buf()),
+        )
+    }
+
+    /// Adds every file under a directory, recursively, under the given virtual path. Returns the number of files added.
+    pub fn add_directory(&mut self, dir: &Path, mount_point: &str) -> Result<usize, ArchiveError> {
+        let mount_point = VirtualPath::new(mount_point)?;
+        let mut entries: Vec<_> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        entries.sort();
+        let mut added = 0;
+        for path in entries {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| VfsError::InvalidPath(path.display().to_string()))?;
+            let virtual_path = mount_point.join(name)?;
+            if path.is_dir() {
+                added += self.add_directory(&path, virtual_path.as_str())?;
+            } else {
+                self.push(virtual_path, PendingData::File(path))?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes the archive, replacing the file if it exists
+    pub fn write(&self, path: &Path) -> Result<ArchiveStats, ArchiveError> {
+        // Nothing is written unless every entry fits in the index
+        for pending in &self.entries {
+            if pending.path.as_str().len() > u16::MAX as usize {
+                return Err(VfsError::InvalidPath(pending.path.to_string()).into());
+            }
+        }
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&[0; HEADER_SIZE])?;
+        let mut offset = HEADER_SIZE as u64;
+        let mut stats = ArchiveStats::default();
+        let mut index = vec![];
+        for pending in &self.entries {
+            let data = match &pending.data {
+                PendingData::Bytes(bytes) => Cow::Borrowed(bytes.as_slice()),
+                PendingData::File(file) => Cow::Owned(fs::read(file)?),
+            };
+            let mut compression = pending.compression;
+            let compressed = match compression {
+                ArchiveCompression::Stored => None,
+                ArchiveCompression::Deflate => {
+                    let mut encoder =
+                        DeflateEncoder::new(vec![], flate2::Compression::new(self.level));
+                    encoder.write_all(&data)?;
+                    Some(encoder.finish()?).filter(|compressed| compressed.len() < data.len())
+                }
+            };
+            if compressed.is_none() {
+                compression = ArchiveCompression::Stored;
+            }
+            let stored = compressed.as_deref().unwrap_or(&data);
+            out.write_all(stored)?;
+
+            let path = pending.path.as_str().as_bytes();
+            let path_len = path.len() as u16;
+            index.extend_from_slice(&path_len.to_le_bytes());
+            index.extend_from_slice(path);
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.push(compression.to_byte());
+            index.extend_from_slice(&checksum(&data).to_le_bytes());
+
+            offset += stored.len() as u64;
+            stats.entries += 1;
+            stats.original_size += data.len() as u64;
+            if compression == ArchiveCompression::Deflate {
+                stats.compressed_entries += 1;
+            }
+        }
+        out.write_all(&index)?;
+        let header = Header {
+            entry_count: self.entries.len() as u32,
+            index_offset: offset,
+            index_size: index.len() as u64,
+            index_checksum: checksum(&index),
+        };
+        out.seek(SeekFrom::Start(0))?;
+        header.write(&mut out)?;
+        out.flush()?;
+        stats.archive_size = offset + index.len() as u64;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str =
+        "Compressible text, compressible text, compressible text, compressible text.";
+    const IMAGE: &[u8] = &[0x89, b'P', b'N', b'G', 0, 1, 2, 3];
+
+    /// An archive written to the temporary directory, removed when dropped
+    struct TempArchive(PathBuf);
+
+    impl TempArchive {
+        fn write(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{}_{}.tpak", std::process::id(), name));
+            let mut builder = ArchiveBuilder::new();
+            builder
+                .add_bytes("data/text.txt", TEXT.as_bytes().to_vec())
+                .expect("Path is valid");
+            builder
+                .add_bytes("textures/image.png", IMAGE.to_vec())
+                .expect("Path is valid");
+            builder.write(&path).expect("Couldn't write the archive");
+            Self(path)
+        }
+
+        /// Overwrites bytes of the archive
+        fn patch(&self, offset: usize, bytes: &[u8]) {
+            let mut data = fs::read(&self.0).expect("Couldn't read the archive");
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            fs::write(&self.0, data).expect("Couldn't write the archive");
+        }
+    }
+
+    impl Drop for TempArchive {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn path(path: &str) -> VirtualPath {
+        VirtualPath::new(path).expect("Path is valid")
+    }
+
+    fn open_both(archive: &TempArchive) -> Vec<Result<ArchiveSource, ArchiveError>> {
+        vec![
+            ArchiveSource::open(&archive.0),
+            ArchiveSource::open_unmapped(&archive.0),
+        ]
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let archive = TempArchive::write("round_trip");
+        for source in open_both(&archive) {
+            let source = source.expect("Couldn't open the archive");
+            assert_eq!(source.entries().count(), 2);
+            let text = source.entry(&path("data/text.txt")).expect("Entry exists");
+            assert_eq!(text.compression(), ArchiveCompression::Deflate);
+            assert!(text.stored_size() < text.size());
+            let image = source
+                .entry(&path("textures/image.png"))
+                .expect("Entry exists");
+            assert_eq!(image.compression(), ArchiveCompression::Stored);
+            assert_eq!(
+                source.read_entry(&path("data/text.txt")).unwrap(),
+                TEXT.as_bytes()
+            );
+            assert_eq!(
+                source.read_entry(&path("textures/image.png")).unwrap(),
+                IMAGE
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_indices_are_rejected() {
+        let archive = TempArchive::write("corrupt_index");
+        let len = fs::metadata(&archive.0).unwrap().len() as usize;
+        // The last byte of the index is the last entry's checksum
+        archive.patch(len - 1, &[0xAA]);
+        for source in open_both(&archive) {
+            assert!(matches!(source, Err(ArchiveError::Corrupt(_))));
+        }
+    }
+
+    #[test]
+    fn entry_counts_the_index_cant_hold_are_rejected() {
+        let archive = TempArchive::write("entry_count");
+        // The entry count follows the magic, version and reserved bytes
+        archive.patch(8, &u32::MAX.to_le_bytes());
+        for source in open_both(&archive) {
+            assert!(matches!(source, Err(ArchiveError::Corrupt(_))));
+        }
+    }
+
+    #[test]
+    fn corrupt_entries_are_rejected() {
+        let archive = TempArchive::write("corrupt_entries");
+        let (text_offset, image_offset) = {
+            let source = ArchiveSource::open_unmapped(&archive.0).unwrap();
+            let offset = |name| source.entry(&path(name)).unwrap().offset() as usize;
+            (offset("data/text.txt"), offset("textures/image.png"))
+        };
+        archive.patch(text_offset, &[0xFF, 0xFF]);
+        archive.patch(image_offset, &[0]);
+        for source in open_both(&archive) {
+            let source = source.expect("The index is intact");
+            assert!(source.read_entry(&path("data/text.txt")).is_err());
+            assert!(matches!(
+                source.read_entry(&path("textures/image.png")),
+                Err(ArchiveError::ChecksumMismatch(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+/*
+    ABSTRACT: Memory accounting for the `AssetServer`'s cache (see server.rs). Assets no handle refers to anymore
+    stay cached while the memory of every asset fits in the CPU and GPU budgets, and are freed least recently used first
+    once it doesn't, skipping those which take none of the memory over budget. Pinned assets are never freed. The cache's state is reported, per asset type, by `CacheStats`.
+*/
+use std::{
+    collections::HashMap,
+    ops::{Add, AddAssign, SubAssign},
+};
+
+/// The memory an asset takes, in bytes. CPU memory is estimated by its loader (see `AssetLoader::memory`),
+/// GPU memory is that of its loader's estimate, plus whatever's been made from it (see `AssetServer::track_gpu_memory`).
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct AssetMemory {
+    pub cpu: usize,
+    pub gpu: usize,
+}
+
+impl AssetMemory {
+    pub fn cpu(cpu: usize) -> Self {
+        Self { cpu, gpu: 0 }
+    }
+}
+
+impl Add for AssetMemory {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            cpu: self.cpu + other.cpu,
+            gpu: self.gpu + other.gpu,
+        }
+    }
+}
+
+impl AddAssign for AssetMemory {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for AssetMemory {
+    fn sub_assign(&mut self, other: Self) {
+        self.cpu = self.cpu.saturating_sub(other.cpu);
+        self.gpu = self.gpu.saturating_sub(other.gpu);
+    }
+}
+
+/// How much memory assets may take before unreferenced ones are freed, in bytes.
+///
+/// A budget of 0 leaves that memory unlimited, but both budgets at 0 (the default) turn caching off:
+/// assets are freed as soon as their last handle drops.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct CacheBudget {
+    pub cpu: usize,
+    pub gpu: usize,
+}
+
+impl CacheBudget {
+    /// A budget of the given sizes, in mebibytes
+    pub fn from_mebibytes(cpu: usize, gpu: usize) -> Self {
+        Self {
+            cpu: cpu * 1024 * 1024,
+            gpu: gpu * 1024 * 1024,
+        }
+    }
+
+    /// Whether unreferenced assets are kept at all
+    pub fn is_caching(&self) -> bool {
+        self.cpu > 0 || self.gpu > 0
+    }
+
+    /// Whether the given memory goes over either (non zero) budget
+    pub fn is_exceeded_by(&self, memory: AssetMemory) -> bool {
+        self.exceeded_by(memory) != (false, false)
+    }
+
+    /// Whether freeing an asset taking `freed` out of `memory` brings it closer to the budgets,
+    /// i.e. whether the asset takes some of a memory over budget
+    pub fn is_relieved_by(&self, memory: AssetMemory, freed: AssetMemory) -> bool {
+        let (cpu, gpu) = self.exceeded_by(memory);
+        (cpu && freed.cpu > 0) || (gpu && freed.gpu > 0)
+    }
+
+    /// Whether the CPU and GPU budgets are exceeded
+    fn exceeded_by(&self, memory: AssetMemory) -> (bool, bool) {
+        (
+            self.cpu > 0 && memory.cpu > self.cpu,
+            self.gpu > 0 && memory.gpu > self.gpu,
+        )
+    }
+}
+
+/// The assets of one type in memory
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct TypeStats {
+    pub assets: usize,
+    /// Assets some handle (or asset depending on them) refers to
+    pub referenced: usize,
+    pub pinned: usize,
+    pub memory: AssetMemory,
+}
+
+/// The state of the `AssetServer`'s cache, see `AssetServer::stats`
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct CacheStats {
+    pub budget: CacheBudget,
+    /// The memory of every asset in memory, referenced or not
+    pub memory: AssetMemory,
+    pub assets: usize,
+    pub referenced: usize,
+    pub pinned: usize,
+    /// Loads of assets which were already in memory
+    pub hits: u64,
+    /// Loads of assets which weren't
+    pub misses: u64,
+    /// Assets freed to fit in the budget, or as soon as unreferenced when not caching
+    pub evictions: u64,
+    /// Statistics of each asset type, by type name
+    pub by_type: HashMap<&'static str, TypeStats>,
+}
+
+impl CacheStats {
+    /// Whether the assets take more memory than the budget allows, which happens when too many of them are referenced or pinned
+    pub fn is_over_budget(&self) -> bool {
+        self.budget.is_exceeded_by(self.memory)
+    }
+
+    /// How many loads found their asset in memory, from 0 to 1
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            loads => self.hits as f64 / loads as f64,
+        }
+    }
+}
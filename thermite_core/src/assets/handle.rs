@@ -0,0 +1,308 @@
+/*
+    ABSTRACT: Typed, reference counted handles on assets loaded by the `AssetServer` (see server.rs).
+    Every handle on the same asset shares one slot, filled in once the asset has loaded, and swapped when it's reloaded;
+    the asset is freed with the last handle. Slots also hold on to the assets their asset depends on (see loader.rs).
+*/
+use crate::assets::{cache::AssetMemory, vfs::VirtualPath};
+use crate::jobs::handle::JobDependency;
+use crate::tools::resources::ResourceError;
+use std::{
+    any::{self, Any},
+    collections::HashSet,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard,
+    },
+};
+
+/// Something the `AssetServer` can hand out handles on, loaded by the `AssetLoader` registered for its files (see loader.rs)
+pub trait Asset: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Asset for T {}
+
+/// Where an asset is in its loading
+#[derive(Debug, Clone)]
+pub enum LoadState {
+    Pending,
+    Loaded,
+    Failed(Arc<ResourceError>),
+}
+
+pub(crate) enum SlotState<T> {
+    Pending,
+    Loaded(Arc<T>),
+    Failed(Arc<ResourceError>),
+}
+
+/// What every handle on an asset shares
+pub(crate) struct AssetSlot<T> {
+    path: VirtualPath,
+    state: RwLock<SlotState<T>>,
+    /// Bumped whenever the asset is reloaded
+    version: AtomicU64,
+    /// The job (re)loading the asset, if any
+    load: Mutex<Option<JobDependency>>,
+    /// The assets the loader declared this one depends on, kept in memory as long as it is
+    dependencies: Mutex<Vec<Arc<dyn AnyAssetSlot>>>,
+    /// The memory the loader estimates the asset takes
+    memory: Mutex<AssetMemory>,
+    /// The GPU memory of what's been made from the asset, see `AssetServer::track_gpu_memory`
+    tracked_gpu_memory: AtomicUsize,
+}
+
+impl<T> AssetSlot<T> {
+    pub(crate) fn new(path: VirtualPath) -> Self {
+        Self {
+            path,
+            state: RwLock::new(SlotState::Pending),
+            version: AtomicU64::new(0),
+            load: Mutex::new(None),
+            dependencies: Mutex::new(vec![]),
+            memory: Mutex::new(AssetMemory::default()),
+            tracked_gpu_memory: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &VirtualPath {
+        &self.path
+    }
+
+    // Loaders run outside the lock, so a poisoned state is still a valid state
+    fn state(&self) -> RwLockReadGuard<'_, SlotState<T>> {
+        self.state
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn is_loaded(&self) -> bool {
+        matches!(&*self.state(), SlotState::Loaded(_))
+    }
+
+    pub(crate) fn set_state(&self, state: SlotState<T>) {
+        *self
+            .state
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = state;
+    }
+
+    /// Swaps a reloaded asset in, handles see it from their next `get`
+    pub(crate) fn reloaded(&self, asset: T) {
+        self.set_state(SlotState::Loaded(Arc::new(asset)));
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn load(&self) -> Option<JobDependency> {
+        self.load
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn set_load(&self, load: JobDependency) {
+        *self
+            .load
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(load);
+    }
+
+    pub(crate) fn set_dependencies(&self, dependencies: Vec<Arc<dyn AnyAssetSlot>>) {
+        *self
+            .dependencies
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = dependencies;
+    }
+
+    pub(crate) fn set_memory(&self, memory: AssetMemory) {
+        *self
+            .memory
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = memory;
+    }
+
+    pub(crate) fn set_tracked_gpu_memory(&self, bytes: usize) {
+        self.tracked_gpu_memory.store(bytes, Ordering::Release);
+    }
+}
+
+/// An `AssetSlot` of any asset type, as held by the assets depending on it
+pub(crate) trait AnyAssetSlot: Send + Sync {
+    fn path(&self) -> &VirtualPath;
+    fn is_loaded(&self) -> bool;
+    fn load(&self) -> Option<JobDependency>;
+    fn dependencies(&self) -> Vec<Arc<dyn AnyAssetSlot>>;
+    /// The memory the asset takes, including the GPU memory tracked for it
+    fn memory(&self) -> AssetMemory;
+    fn set_tracked_gpu_memory(&self, bytes: usize);
+    fn type_name(&self) -> &'static str;
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+impl<T: Asset> AnyAssetSlot for AssetSlot<T> {
+    fn path(&self) -> &VirtualPath {
+        AssetSlot::path(self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        AssetSlot::is_loaded(self)
+    }
+
+    fn load(&self) -> Option<JobDependency> {
+        AssetSlot::load(self)
+    }
+
+    fn dependencies(&self) -> Vec<Arc<dyn AnyAssetSlot>> {
+        self.dependencies
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn memory(&self) -> AssetMemory {
+        let memory = *self
+            .memory
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        memory
+            + AssetMemory {
+                cpu: 0,
+                gpu: self.tracked_gpu_memory.load(Ordering::Acquire),
+            }
+    }
+
+    fn set_tracked_gpu_memory(&self, bytes: usize) {
+        AssetSlot::set_tracked_gpu_memory(self, bytes)
+    }
+
+    fn type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Visits a slot and the slots it depends on, directly or not, each once (even if dependencies are cyclic).
+/// Stops at the first slot for which `visit` returns `false`, and returns whether it went through all of them.
+pub(crate) fn visit_with_dependencies(
+    slot: Arc<dyn AnyAssetSlot>,
+    mut visit: impl FnMut(&dyn AnyAssetSlot) -> bool,
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut to_visit = vec![slot];
+    while let Some(slot) = to_visit.pop() {
+        if !visited.insert(Arc::as_ptr(&slot) as *const () as usize) {
+            continue;
+        }
+        if !visit(slot.as_ref()) {
+            return false;
+        }
+        to_visit.extend(slot.dependencies());
+    }
+    true
+}
+
+/// A handle on an asset loaded by the `AssetServer`, which may still be loading.
+///
+/// Cloning a handle is cheap, and the asset stays in memory as long as one of its handles does.
+pub struct Handle<T: Asset> {
+    pub(crate) slot: Arc<AssetSlot<T>>,
+}
+
+impl<T: Asset> Handle<T> {
+    /// The virtual path the asset was loaded from
+    pub fn path(&self) -> &VirtualPath {
+        self.slot.path()
+    }
+
+    pub fn state(&self) -> LoadState {
+        match &*self.slot.state() {
+            SlotState::Pending => LoadState::Pending,
+            SlotState::Loaded(_) => LoadState::Loaded,
+            SlotState::Failed(error) => LoadState::Failed(error.clone()),
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.slot.is_loaded()
+    }
+
+    /// Whether the asset has loaded or failed to
+    pub fn is_done(&self) -> bool {
+        !matches!(&*self.slot.state(), SlotState::Pending)
+    }
+
+    /// Whether the asset and everything it depends on, directly or not, has loaded
+    pub fn is_loaded_with_dependencies(&self) -> bool {
+        visit_with_dependencies(self.slot.clone(), |slot| slot.is_loaded())
+    }
+
+    /// The paths of the assets this one depends on, as declared by its loader (see `LoadContext::dependency`)
+    pub fn dependencies(&self) -> Vec<VirtualPath> {
+        AnyAssetSlot::dependencies(self.slot.as_ref())
+            .iter()
+            .map(|dependency| dependency.path().clone())
+            .collect()
+    }
+
+    /// How many times the asset has been reloaded, to tell whether what was made from it is stale (see `AssetServer::set_hot_reload`)
+    pub fn version(&self) -> u64 {
+        self.slot.version()
+    }
+
+    /// A dependency on the job (re)loading the asset, for jobs which use it. `None` if there's no such job.
+    pub fn dependency(&self) -> Option<JobDependency> {
+        self.slot.load()
+    }
+
+    /// The asset, once it has loaded. After a reload, this is the new asset, while anything holding the old one keeps it.
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*self.slot.state() {
+            SlotState::Loaded(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    /// The memory the asset takes, see `AssetMemory`
+    pub fn memory(&self) -> AssetMemory {
+        AnyAssetSlot::memory(self.slot.as_ref())
+    }
+
+    /// The number of handles on this asset, including this one (and the assets depending on it)
+    pub fn handle_count(&self) -> usize {
+        // The server holds on to the slot too, see `AssetServer::set_budget`
+        Arc::strong_count(&self.slot) - 1
+    }
+}
+
+impl<T: Asset> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<T: Asset> PartialEq for Handle<T> {
+    /// Whether both handles are on the same asset
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+impl<T: Asset> Eq for Handle<T> {}
+
+impl<T: Asset> fmt::Debug for Handle<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Handle")
+            .field("path", self.path())
+            .field("state", &self.state())
+            .field("version", &self.version())
+            .finish()
+    }
+}
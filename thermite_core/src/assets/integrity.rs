@@ -0,0 +1,516 @@
+/*
+    ABSTRACT: Asset integrity. A manifest lists every file of an asset tree with its size, SHA-256 hash and format version,
+    so that the `AssetServer` can verify assets as it loads them (see `AssetServer::set_manifest`), and whole trees or archives
+    can be validated before shipping (see bin/thermite_manifest.rs).
+
+    Binary asset formats start with an `AssetHeader` naming their kind and format version, which loaders check
+    before parsing. Every failure is an `IntegrityError` naming the file, and where it makes sense the byte offset
+    and the expected and actual values.
+*/
+use crate::assets::{archive::ArchiveSource, vfs::VirtualPath};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryInto,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The version of the manifest format written by `AssetManifest::save`
+pub const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+/// Errors which can occur while verifying assets, or reading and writing manifests
+#[derive(Debug)]
+pub enum IntegrityError {
+    /// The file couldn't be read
+    Unreadable { path: String, message: String },
+    /// The manifest lists the file, which doesn't exist
+    Missing { path: String },
+    /// The file exists, but the manifest doesn't list it
+    Unlisted { path: String },
+    SizeMismatch {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+    HashMismatch {
+        path: String,
+        expected: ContentHash,
+        actual: ContentHash,
+    },
+    /// The file's format version isn't the one expected: the manifest's, or one the loader supports (up to `expected`)
+    VersionMismatch {
+        path: String,
+        offset: u64,
+        expected: u32,
+        actual: u32,
+    },
+    /// The file's contents don't match its format, from the given byte offset
+    Malformed {
+        path: String,
+        offset: u64,
+        message: String,
+    },
+    /// A manifest couldn't be read or written
+    Manifest { path: String, message: String },
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityError::Unreadable { path, message } => {
+                write!(fmt, "{}: couldn't be read: {}", path, message)
+            }
+            IntegrityError::Missing { path } => {
+                write!(fmt, "{}: listed in the manifest, but missing", path)
+            }
+            IntegrityError::Unlisted { path } => {
+                write!(fmt, "{}: not listed in the manifest", path)
+            }
+            IntegrityError::SizeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                fmt,
+                "{}: expected {} bytes, found {} bytes",
+                path, expected, actual
+            ),
+            IntegrityError::HashMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                fmt,
+                "{}: expected hash {}, found {}",
+                path, expected, actual
+            ),
+            IntegrityError::VersionMismatch {
+                path,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                fmt,
+                "{} (at byte {}): expected format version {}, found version {}",
+                path, offset, expected, actual
+            ),
+            IntegrityError::Malformed {
+                path,
+                offset,
+                message,
+            } => write!(fmt, "{} (at byte {}): {}", path, offset, message),
+            IntegrityError::Manifest { path, message } => {
+                write!(fmt, "Manifest {}: {}", path, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// The SHA-256 hash of a file's contents, written as lowercase hexadecimal
+#[derive(Eq, PartialEq, Hash, Clone, Copy)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self(Sha256::digest(bytes).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|byte| write!(fmt, "{:02x}", byte))
+    }
+}
+
+impl fmt::Debug for ContentHash {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "ContentHash({})", self)
+    }
+}
+
+impl FromStr for ContentHash {
+    type Err = String;
+
+    fn from_str(hex: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid SHA-256 hash {}", hex);
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut hash = [0; 32];
+        for (idx, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// The header binary asset formats start with: `AssetHeader::SIGNATURE`, the kind of asset, and its format version (little-endian)
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct AssetHeader {
+    pub kind: [u8; 4],
+    pub version: u32,
+}
+
+impl AssetHeader {
+    pub const SIGNATURE: [u8; 4] = *b"THRM";
+    pub const SIZE: usize = 12;
+
+    pub fn new(kind: [u8; 4], version: u32) -> Self {
+        Self { kind, version }
+    }
+
+    /// The header the file starts with, if it has one
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::SIZE || bytes[0..4] != Self::SIGNATURE {
+            return None;
+        }
+        Some(Self {
+            kind: bytes[4..8].try_into().ok()?,
+            version: u32::from_le_bytes(bytes[8..12].try_into().ok()?),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&Self::SIGNATURE);
+        bytes[4..8].copy_from_slice(&self.kind);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes
+    }
+
+    /// Checks that the file holds an asset of the given kind, in a format version up to `newest`. `path` only names it in errors.
+    ///
+    /// Returns the file's format version, and the offset its contents start at. Files without a header are version 0.
+    pub fn check(
+        bytes: &[u8],
+        path: &str,
+        kind: [u8; 4],
+        newest: u32,
+    ) -> Result<(u32, usize), IntegrityError> {
+        let header = match Self::parse(bytes) {
+            Some(header) => header,
+            None => return Ok((0, 0)),
+        };
+        if header.kind != kind {
+            return Err(IntegrityError::Malformed {
+                path: path.to_string(),
+                offset: 4,
+                message: format!(
+                    "expected a {} asset, found a {} asset",
+                    String::from_utf8_lossy(&kind),
+                    String::from_utf8_lossy(&header.kind)
+                ),
+            });
+        }
+        if header.version > newest {
+            return Err(IntegrityError::VersionMismatch {
+                path: path.to_string(),
+                offset: 8,
+                expected: newest,
+                actual: header.version,
+            });
+        }
+        Ok((header.version, Self::SIZE))
+    }
+}
+
+/// What a manifest knows about a file
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct ManifestEntry {
+    pub size: u64,
+    pub hash: ContentHash,
+    /// The format version from the file's `AssetHeader`, if it has one
+    #[serde(default)]
+    pub format_version: Option<u32>,
+}
+
+impl ManifestEntry {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self {
+            size: bytes.len() as u64,
+            hash: ContentHash::of(bytes),
+            format_version: AssetHeader::parse(bytes).map(|header| header.version),
+        }
+    }
+}
+
+/// Every file of an asset tree, by virtual path, with the version of the assets as a whole (e.g. the game's version)
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct AssetManifest {
+    pub format_version: u32,
+    pub version: String,
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+/// The result of validating an asset tree against a manifest, see `AssetManifest::validate`
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// The number of files checked
+    pub checked: usize,
+    pub errors: Vec<IntegrityError>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Every file under a directory, recursively, by their virtual path under `mount_point`
+fn files_in(
+    dir: &Path,
+    mount_point: &VirtualPath,
+) -> Result<Vec<(VirtualPath, PathBuf)>, IntegrityError> {
+    let unreadable = |error: std::io::Error| IntegrityError::Unreadable {
+        path: dir.display().to_string(),
+        message: error.to_string(),
+    };
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(unreadable)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(unreadable)?;
+    entries.sort();
+    let mut files = vec![];
+    for path in entries {
+        let virtual_path = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| String::from("not valid UTF-8"))
+            .and_then(|name| mount_point.join(name).map_err(|error| error.to_string()))
+            .map_err(|message| IntegrityError::Unreadable {
+                path: path.display().to_string(),
+                message,
+            })?;
+        if path.is_dir() {
+            files.extend(files_in(&path, &virtual_path)?);
+        } else {
+            files.push((virtual_path, path));
+        }
+    }
+    Ok(files)
+}
+
+/// The virtual path files under a directory are listed at
+fn virtual_dir(path: &str) -> Result<VirtualPath, IntegrityError> {
+    VirtualPath::new(path).map_err(|error| IntegrityError::Unreadable {
+        path: path.to_string(),
+        message: error.to_string(),
+    })
+}
+
+impl AssetManifest {
+    /// An empty manifest, for assets of the given version
+    pub fn new(version: &str) -> Self {
+        Self {
+            format_version: MANIFEST_FORMAT_VERSION,
+            version: version.to_string(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Lists a file with the given contents, replacing any previous entry for it
+    pub fn add(&mut self, path: &VirtualPath, bytes: &[u8]) {
+        self.entries
+            .insert(path.as_str().to_string(), ManifestEntry::of(bytes));
+    }
+
+    /// Lists every file under a directory, recursively, under the given virtual path. Returns the number of files listed.
+    pub fn add_directory(
+        &mut self,
+        dir: &Path,
+        mount_point: &str,
+    ) -> Result<usize, IntegrityError> {
+        let files = files_in(dir, &virtual_dir(mount_point)?)?;
+        for (path, file) in &files {
+            let bytes = fs::read(file).map_err(|error| IntegrityError::Unreadable {
+                path: path.to_string(),
+                message: error.to_string(),
+            })?;
+            self.add(path, &bytes);
+        }
+        Ok(files.len())
+    }
+
+    pub fn entry(&self, path: &VirtualPath) -> Option<&ManifestEntry> {
+        self.entries.get(path.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Parses a manifest from RON, `source` only names it in errors
+    pub fn from_ron(text: &str, source: &str) -> Result<Self, IntegrityError> {
+        let manifest: Self = ron::de::from_str(text).map_err(|error| IntegrityError::Manifest {
+            path: source.to_string(),
+            message: error.to_string(),
+        })?;
+        if manifest.format_version != MANIFEST_FORMAT_VERSION {
+            return Err(IntegrityError::Manifest {
+                path: source.to_string(),
+                message: format!(
+                    "expected format version {}, found version {}",
+                    MANIFEST_FORMAT_VERSION, manifest.format_version
+                ),
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Loads a manifest from the given RON file
+    pub fn load(path: &Path) -> Result<Self, IntegrityError> {
+        let text = fs::read_to_string(path).map_err(|error| IntegrityError::Manifest {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        })?;
+        Self::from_ron(&text, &path.display().to_string())
+    }
+
+    /// Saves this manifest to the given file, as RON
+    pub fn save(&self, path: &Path) -> Result<(), IntegrityError> {
+        let manifest_error = |message: String| IntegrityError::Manifest {
+            path: path.display().to_string(),
+            message,
+        };
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| manifest_error(error.to_string()))?;
+        fs::write(path, ron).map_err(|error| manifest_error(error.to_string()))
+    }
+
+    /// Checks a file's contents against its entry: format version, size, then hash. Files the manifest doesn't list pass.
+    pub fn verify(&self, path: &VirtualPath, bytes: &[u8]) -> Result<(), IntegrityError> {
+        let entry = match self.entry(path) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        let actual = ManifestEntry::of(bytes);
+        if let Some(expected) = entry.format_version {
+            let actual = actual.format_version.unwrap_or(0);
+            if actual != expected {
+                return Err(IntegrityError::VersionMismatch {
+                    path: path.to_string(),
+                    offset: 8,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        if actual.size != entry.size {
+            return Err(IntegrityError::SizeMismatch {
+                path: path.to_string(),
+                expected: entry.size,
+                actual: actual.size,
+            });
+        }
+        if actual.hash != entry.hash {
+            return Err(IntegrityError::HashMismatch {
+                path: path.to_string(),
+                expected: entry.hash,
+                actual: actual.hash,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks a whole asset tree, given as its files and their contents: every file must be listed and match its entry,
+    /// and every entry must have its file
+    pub fn validate<I>(&self, files: I) -> ValidationReport
+    where
+        I: IntoIterator<Item = (VirtualPath, Result<Vec<u8>, IntegrityError>)>,
+    {
+        let mut report = ValidationReport::default();
+        let mut found = BTreeSet::new();
+        for (path, bytes) in files {
+            report.checked += 1;
+            found.insert(path.as_str().to_string());
+            let result = bytes.and_then(|bytes| match self.entry(&path) {
+                Some(_) => self.verify(&path, &bytes),
+                None => Err(IntegrityError::Unlisted {
+                    path: path.to_string(),
+                }),
+            });
+            if let Err(error) = result {
+                report.errors.push(error);
+            }
+        }
+        for path in self.entries.keys().filter(|path| !found.contains(*path)) {
+            report.errors.push(IntegrityError::Missing {
+                path: format!("/{}", path),
+            });
+        }
+        report
+    }
+
+    /// Validates the files under a directory, as mounted at the given virtual path (see `validate`)
+    pub fn validate_directory(
+        &self,
+        dir: &Path,
+        mount_point: &str,
+    ) -> Result<ValidationReport, IntegrityError> {
+        let files = files_in(dir, &virtual_dir(mount_point)?)?;
+        Ok(self.validate(files.into_iter().map(|(path, file)| {
+            let bytes = fs::read(&file).map_err(|error| IntegrityError::Unreadable {
+                path: path.to_string(),
+                message: error.to_string(),
+            });
+            (path, bytes)
+        })))
+    }
+
+    /// Validates the files of an archive mounted at the given virtual path (see `validate`), whose own checksums are verified too
+    pub fn validate_archive(
+        &self,
+        archive: &ArchiveSource,
+        mount_point: &str,
+    ) -> Result<ValidationReport, IntegrityError> {
+        let mount_point = virtual_dir(mount_point)?;
+        let mut files = vec![];
+        for entry in archive.entries() {
+            let path = mount_point.join(entry.path().as_str()).map_err(|error| {
+                IntegrityError::Unreadable {
+                    path: entry.path().to_string(),
+                    message: error.to_string(),
+                }
+            })?;
+            let bytes =
+                archive
+                    .read_entry(entry.path())
+                    .map_err(|error| IntegrityError::Unreadable {
+                        path: path.to_string(),
+                        message: error.to_string(),
+                    });
+            files.push((path, bytes));
+        }
+        Ok(self.validate(files))
+    }
+}
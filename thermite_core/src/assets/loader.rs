@@ -0,0 +1,260 @@
+/*
+    ABSTRACT: Asset loaders, which turn the contents of files into assets for the `AssetServer` (see server.rs).
+    Loaders are registered by the file extensions and MIME types they read, for the type of asset they make,
+    so any crate can add formats of its own. A load can be given settings for its loader, and the loader can
+    declare dependencies on other assets (e.g. a material on its textures), which load alongside it.
+*/
+use crate::assets::{
+    cache::AssetMemory,
+    handle::{AnyAssetSlot, Asset, Handle},
+    server::{LoadRequest, Shared},
+    vfs::VirtualPath,
+};
+use crate::tools::resources::ResourceError;
+use std::{
+    any::{self, Any, TypeId},
+    collections::HashMap,
+    mem,
+    sync::Arc,
+};
+
+/// Loads assets of one type from the files it supports, see `AssetServer::register_loader`
+pub trait AssetLoader: Send + Sync + 'static {
+    type Asset: Asset;
+    /// What a load can be given to change how the asset is made (see `AssetServer::load_with_settings`), `Default` otherwise
+    type Settings: Default + Send + Sync + 'static;
+
+    /// The extensions of the files this loader reads, without the dot. `"*"` makes it read any file no other loader of its asset type reads.
+    fn extensions(&self) -> &[&str];
+
+    /// The MIME types of the files this loader reads, see `AssetServer::load_mime`
+    fn mime_types(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Builds the asset from the contents of the file at `ctx.path()`. Runs on one of the worker threads.
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        settings: &Self::Settings,
+        ctx: &mut LoadContext,
+    ) -> Result<Self::Asset, ResourceError>;
+
+    /// Estimates the memory the asset takes, for the `AssetServer`'s cache budgets. Only counts the asset's own size by default.
+    fn memory(&self, _asset: &Self::Asset) -> AssetMemory {
+        AssetMemory::cpu(mem::size_of::<Self::Asset>())
+    }
+}
+
+/// An `AssetLoader` whose settings type has been erased
+pub(crate) trait ErasedLoader<T>: Send + Sync {
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        settings: Option<&(dyn Any + Send + Sync)>,
+        ctx: &mut LoadContext,
+    ) -> Result<T, ResourceError>;
+
+    fn memory(&self, asset: &T) -> AssetMemory;
+}
+
+impl<L: AssetLoader> ErasedLoader<L::Asset> for L {
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        settings: Option<&(dyn Any + Send + Sync)>,
+        ctx: &mut LoadContext,
+    ) -> Result<L::Asset, ResourceError> {
+        match settings.map(|settings| settings.downcast_ref::<L::Settings>()) {
+            None => AssetLoader::load(self, bytes, &L::Settings::default(), ctx),
+            Some(Some(settings)) => AssetLoader::load(self, bytes, settings, ctx),
+            Some(None) => Err(ResourceError::WrongLoaderSettings(format!(
+                "{} (expected {})",
+                ctx.path(),
+                any::type_name::<L::Settings>()
+            ))),
+        }
+    }
+
+    fn memory(&self, asset: &L::Asset) -> AssetMemory {
+        AssetLoader::memory(self, asset)
+    }
+}
+
+/// An `Arc<dyn ErasedLoader<T>>`, for the asset type it's registered under
+type AnyLoader = Arc<dyn Any + Send + Sync>;
+
+/// The loaders the `AssetServer` picks from, by asset type and extension or MIME type
+#[derive(Default)]
+pub struct AssetLoaders {
+    by_extension: HashMap<(TypeId, String), AnyLoader>,
+    by_mime: HashMap<(TypeId, String), AnyLoader>,
+}
+
+impl AssetLoaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a loader for its extensions and MIME types, replacing the loaders of the same asset type registered for them
+    pub fn register<L: AssetLoader>(&mut self, loader: L) {
+        let asset = TypeId::of::<L::Asset>();
+        let extensions: Vec<String> = loader
+            .extensions()
+            .iter()
+            .map(|extension| extension.to_lowercase())
+            .collect();
+        let mime_types: Vec<String> = loader
+            .mime_types()
+            .iter()
+            .map(|mime| mime.to_lowercase())
+            .collect();
+        let loader: Arc<dyn ErasedLoader<L::Asset>> = Arc::new(loader);
+        let loader: AnyLoader = Arc::new(loader);
+        for extension in extensions {
+            self.by_extension.insert((asset, extension), loader.clone());
+        }
+        for mime in mime_types {
+            self.by_mime.insert((asset, mime), loader.clone());
+        }
+    }
+
+    /// Whether a loader of `T` reads files with the given extension (or any file)
+    pub fn supports<T: Asset>(&self, extension: &str) -> bool {
+        let asset = TypeId::of::<T>();
+        self.by_extension
+            .contains_key(&(asset, extension.to_lowercase()))
+            || self.by_extension.contains_key(&(asset, String::from("*")))
+    }
+
+    /// The loader of `T` for the given MIME type if any, for the path's extension otherwise
+    pub(crate) fn find<T: Asset>(
+        &self,
+        path: &VirtualPath,
+        mime: Option<&str>,
+    ) -> Result<Arc<dyn ErasedLoader<T>>, ResourceError> {
+        let asset = TypeId::of::<T>();
+        let loader = match mime {
+            Some(mime) => self.by_mime.get(&(asset, mime.to_lowercase())),
+            None => path
+                .extension()
+                .and_then(|extension| self.by_extension.get(&(asset, extension.to_lowercase())))
+                .or_else(|| self.by_extension.get(&(asset, String::from("*")))),
+        };
+        loader
+            .and_then(|loader| loader.downcast_ref::<Arc<dyn ErasedLoader<T>>>())
+            .cloned()
+            .ok_or_else(|| {
+                ResourceError::NoLoader(format!(
+                    "{} ({}{})",
+                    path,
+                    any::type_name::<T>(),
+                    mime.map(|mime| format!(", {}", mime)).unwrap_or_default()
+                ))
+            })
+    }
+}
+
+/// What a loader is given besides the file's contents: where it's from, and a way to load the assets it depends on
+pub struct LoadContext<'a> {
+    path: &'a VirtualPath,
+    server: &'a Arc<Shared>,
+    dependencies: Vec<Arc<dyn AnyAssetSlot>>,
+}
+
+impl<'a> LoadContext<'a> {
+    pub(crate) fn new(path: &'a VirtualPath, server: &'a Arc<Shared>) -> Self {
+        Self {
+            path,
+            server,
+            dependencies: vec![],
+        }
+    }
+
+    /// The virtual path of the asset being loaded
+    pub fn path(&self) -> &VirtualPath {
+        self.path
+    }
+
+    /// Declares that the asset being loaded depends on the asset at the given virtual path, which starts loading if it isn't already.
+    ///
+    /// The dependency stays in memory as long as the asset does, and `AssetServer::wait` waits for it too.
+    /// Dependencies shouldn't be cyclic, or the assets in the cycle are never freed.
+    pub fn dependency<T: Asset>(&mut self, path: &str) -> Result<Handle<T>, ResourceError> {
+        let path = VirtualPath::new(path)?;
+        let handle = self.server.load::<T>(path, LoadRequest::default());
+        self.dependencies.push(handle.slot.clone());
+        Ok(handle)
+    }
+
+    /// Like `dependency`, with a path relative to the directory of the asset being loaded
+    pub fn sibling<T: Asset>(&mut self, path: &str) -> Result<Handle<T>, ResourceError> {
+        let mut components: Vec<&str> = self.path.components().collect();
+        components.pop();
+        components.push(path);
+        self.dependency(&components.join("/"))
+    }
+
+    pub(crate) fn into_dependencies(self) -> Vec<Arc<dyn AnyAssetSlot>> {
+        self.dependencies
+    }
+}
+
+/// Loads any file as text, for `String` assets
+pub struct TextLoader;
+
+impl AssetLoader for TextLoader {
+    type Asset = String;
+    type Settings = ();
+
+    fn extensions(&self) -> &[&str] {
+        &["*"]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["text/plain"]
+    }
+
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        _settings: &(),
+        ctx: &mut LoadContext,
+    ) -> Result<String, ResourceError> {
+        String::from_utf8(bytes)
+            .map_err(|_| ResourceError::DeserializationFailure(ctx.path().to_string()))
+    }
+
+    fn memory(&self, text: &String) -> AssetMemory {
+        AssetMemory::cpu(mem::size_of::<String>() + text.capacity())
+    }
+}
+
+/// Loads any file as is, for `Vec<u8>` assets
+pub struct BytesLoader;
+
+impl AssetLoader for BytesLoader {
+    type Asset = Vec<u8>;
+    type Settings = ();
+
+    fn extensions(&self) -> &[&str] {
+        &["*"]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/octet-stream"]
+    }
+
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        _settings: &(),
+        _ctx: &mut LoadContext,
+    ) -> Result<Vec<u8>, ResourceError> {
+        Ok(bytes)
+    }
+
+    fn memory(&self, bytes: &Vec<u8>) -> AssetMemory {
+        AssetMemory::cpu(mem::size_of::<Vec<u8>>() + bytes.capacity())
+    }
+}
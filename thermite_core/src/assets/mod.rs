@@ -0,0 +1,7 @@
+pub mod archive;
+pub mod cache;
+pub mod handle;
+pub mod integrity;
+pub mod loader;
+pub mod server;
+pub mod vfs;
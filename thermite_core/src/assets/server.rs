@@ -0,0 +1,587 @@
+/*
+    ABSTRACT: The `AssetServer` loads assets on the worker threads, and hands out `Handle`s on them straight away.
+    Each asset is made by the `AssetLoader` registered for its type and file extension (or MIME type, see loader.rs).
+    Requests for the same asset (same type and virtual path) share one load and one copy in memory. Once the last handle
+    on an asset drops, it's either freed, or kept in the cache until the assets take more memory than the budget allows
+    (see cache.rs). Loads and frees are published on the bus, as `AssetEvent`s, at the next update.
+    Given a manifest, assets it lists are verified against it before their loader sees them (see integrity.rs).
+
+    With hot reloading on, the files assets were read from are polled for changes (like the configuration's watched files),
+    and changed assets are reloaded in the background, then swapped in their handles.
+*/
+use crate::assets::{
+    cache::{AssetMemory, CacheBudget, CacheStats},
+    handle::{
+        visit_with_dependencies, AnyAssetSlot, Asset, AssetSlot, Handle, LoadState, SlotState,
+    },
+    integrity::AssetManifest,
+    loader::{AssetLoader, AssetLoaders, BytesLoader, LoadContext, TextLoader},
+    vfs::{Vfs, VirtualPath},
+};
+use crate::jobs::scheduler::JobScheduler;
+use crate::messaging::event::ThermiteEvent;
+use crate::platform::app::AppContext;
+use crate::tools::resources::ResourceError;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Asset events, published on the bus under `ThermiteEventType::Asset`
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum AssetEvent {
+    Loaded {
+        path: VirtualPath,
+    },
+    /// The asset's file changed, and the new asset has been swapped in its handles (see `AssetServer::set_hot_reload`)
+    Reloaded {
+        path: VirtualPath,
+    },
+    /// Loading failed. A failed reload keeps the previous asset.
+    Failed {
+        path: VirtualPath,
+        error: String,
+    },
+    /// The last handle on the asset has dropped, and it's been freed (straight away, or when evicted from the cache)
+    Freed {
+        path: VirtualPath,
+    },
+}
+
+impl From<AssetEvent> for ThermiteEvent {
+    fn from(asset_evt: AssetEvent) -> Self {
+        ThermiteEvent::Asset(asset_evt)
+    }
+}
+
+/// Where an asset was read from, and when that file was modified
+type AssetSource = Option<(PathBuf, Option<SystemTime>)>;
+
+/// How an asset is loaded: by the loader for its MIME type if given (for its extension otherwise), with the given settings if any
+#[derive(Clone, Default)]
+pub(crate) struct LoadRequest {
+    mime: Option<String>,
+    settings: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+struct AssetEntry {
+    /// Keeps the asset in memory once no handle refers to it anymore, until it's evicted
+    slot: Arc<dyn AnyAssetSlot>,
+    /// Starts reloading the slot, which is of the entry's type
+    reload: fn(&Arc<Shared>, Arc<dyn AnyAssetSlot>, LoadRequest),
+    request: LoadRequest,
+    source: AssetSource,
+    /// Pinned assets are never evicted, see `AssetServer::pin`
+    pinned: bool,
+    /// The update the asset was last requested or referenced at, see `AssetServer::update`
+    last_used: u64,
+}
+
+impl AssetEntry {
+    /// Whether some handle, or asset depending on this one, refers to it
+    fn is_referenced(&self) -> bool {
+        Arc::strong_count(&self.slot) > 1
+    }
+}
+
+/// Every asset, by type and path
+type AssetSlots = HashMap<(TypeId, VirtualPath), AssetEntry>;
+
+/// What the server shares with the loads running on the worker threads, which may load their dependencies
+pub(crate) struct Shared {
+    vfs: RwLock<Arc<Vfs>>,
+    jobs: JobScheduler,
+    loaders: RwLock<AssetLoaders>,
+    /// The manifest loaded assets are verified against, if any
+    manifest: RwLock<Option<Arc<AssetManifest>>>,
+    slots: Mutex<AssetSlots>,
+    /// Events from the worker threads, published at the next `update`
+    events: Mutex<Vec<AssetEvent>>,
+    /// The number of updates so far
+    frame: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl Shared {
+    // Loads catch their own panics, so poisoned locks still guard valid values
+    fn vfs(&self) -> Arc<Vfs> {
+        self.vfs
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn loaders(&self) -> RwLockReadGuard<'_, AssetLoaders> {
+        self.loaders
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn manifest(&self) -> Option<Arc<AssetManifest>> {
+        self.manifest
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn slots(&self) -> MutexGuard<'_, AssetSlots> {
+        self.slots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn push_event(&self, event: AssetEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event);
+    }
+
+    /// Where the asset at the given virtual path is read from, if that's a plain file
+    fn source(&self, path: &VirtualPath) -> AssetSource {
+        self.vfs().real_path(path).ok().map(|real_path| {
+            let modified = fs::metadata(&real_path)
+                .and_then(|meta| meta.modified())
+                .ok();
+            (real_path, modified)
+        })
+    }
+
+    fn get<T: Asset>(&self, slots: &mut AssetSlots, path: &VirtualPath) -> Option<Handle<T>> {
+        let entry = slots.get_mut(&(TypeId::of::<T>(), path.clone()))?;
+        entry.last_used = self.frame.load(Ordering::Relaxed);
+        let slot = entry
+            .slot
+            .clone()
+            .into_any()
+            .downcast::<AssetSlot<T>>()
+            .ok()?;
+        Some(Handle { slot })
+    }
+
+    /// A handle on the asset at the given virtual path, which starts loading if it isn't already in memory
+    pub(crate) fn load<T: Asset>(
+        self: &Arc<Self>,
+        path: VirtualPath,
+        request: LoadRequest,
+    ) -> Handle<T> {
+        let slot = {
+            let mut slots = self.slots();
+            if let Some(handle) = self.get::<T>(&mut slots, &path) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return handle;
+            }
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            let slot = Arc::new(AssetSlot::<T>::new(path.clone()));
+            let entry = AssetEntry {
+                slot: slot.clone(),
+                reload: Self::reload_any::<T>,
+                request: request.clone(),
+                source: self.source(&path),
+                pinned: false,
+                last_used: self.frame.load(Ordering::Relaxed),
+            };
+            slots.insert((TypeId::of::<T>(), path), entry);
+            slot
+        };
+        self.spawn_load(&slot, request);
+        Handle { slot }
+    }
+
+    fn reload_any<T: Asset>(self: &Arc<Self>, slot: Arc<dyn AnyAssetSlot>, request: LoadRequest) {
+        if let Ok(slot) = slot.into_any().downcast::<AssetSlot<T>>() {
+            self.spawn_load(&slot, request);
+        }
+    }
+
+    /// Loads an asset into its slot on a worker thread. If the slot already holds the asset, it's a reload.
+    fn spawn_load<T: Asset>(self: &Arc<Self>, slot: &Arc<AssetSlot<T>>, request: LoadRequest) {
+        let loading = Arc::downgrade(slot);
+        let server = self.clone();
+        let job = self.jobs.spawn("AssetServer::load", move || {
+            // Every handle may have dropped before the load started
+            let slot = match loading.upgrade() {
+                Some(slot) => slot,
+                None => return,
+            };
+            let path = slot.path();
+            let mut ctx = LoadContext::new(path, &server);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let loader = server.loaders().find::<T>(path, request.mime.as_deref())?;
+                let bytes = server.vfs().read(path)?;
+                if let Some(manifest) = server.manifest() {
+                    manifest.verify(path, &bytes)?;
+                }
+                let asset = loader.load(bytes, request.settings.as_deref(), &mut ctx)?;
+                let memory = loader.memory(&asset);
+                Ok((asset, memory))
+            }))
+            .unwrap_or_else(|_| {
+                Err(ResourceError::DeserializationFailure(format!(
+                    "{} (the loader panicked)",
+                    path
+                )))
+            });
+            let event = match result {
+                Ok((asset, memory)) if slot.is_loaded() => {
+                    slot.set_dependencies(ctx.into_dependencies());
+                    slot.set_memory(memory);
+                    slot.reloaded(asset);
+                    AssetEvent::Reloaded { path: path.clone() }
+                }
+                Ok((asset, memory)) => {
+                    slot.set_dependencies(ctx.into_dependencies());
+                    slot.set_memory(memory);
+                    slot.set_state(SlotState::Loaded(Arc::new(asset)));
+                    AssetEvent::Loaded { path: path.clone() }
+                }
+                Err(error) => {
+                    let event = AssetEvent::Failed {
+                        path: path.clone(),
+                        error: error.to_string(),
+                    };
+                    if !slot.is_loaded() {
+                        slot.set_state(SlotState::Failed(Arc::new(error)));
+                    }
+                    event
+                }
+            };
+            server.push_event(event);
+        });
+        slot.set_load(job.dependency());
+    }
+}
+
+/// Loads assets through a VFS on a job scheduler's worker threads, see `AppContext::assets`
+pub struct AssetServer {
+    shared: Arc<Shared>,
+    budget: CacheBudget,
+    hot_reload: bool,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl AssetServer {
+    /// A server without caching nor hot reloading (which checks for changes every second once it's turned on),
+    /// loading any file as a `String` or `Vec<u8>` (see `TextLoader` and `BytesLoader`)
+    pub fn new(vfs: Arc<Vfs>, jobs: JobScheduler) -> Self {
+        let mut loaders = AssetLoaders::new();
+        loaders.register(TextLoader);
+        loaders.register(BytesLoader);
+        Self {
+            shared: Arc::new(Shared {
+                vfs: RwLock::new(vfs),
+                jobs,
+                loaders: RwLock::new(loaders),
+                manifest: RwLock::new(None),
+                slots: Mutex::new(HashMap::new()),
+                events: Mutex::new(vec![]),
+                frame: AtomicU64::new(0),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                evictions: AtomicU64::new(0),
+            }),
+            budget: CacheBudget::default(),
+            hot_reload: false,
+            poll_interval: Duration::from_secs(1),
+            last_poll: None,
+        }
+    }
+
+    /// The VFS assets are read through
+    pub fn vfs(&self) -> Arc<Vfs> {
+        self.shared.vfs()
+    }
+
+    /// Reads assets loaded from now on through the given VFS
+    pub fn set_vfs(&mut self, vfs: Arc<Vfs>) {
+        *self
+            .shared
+            .vfs
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = vfs;
+    }
+
+    /// Registers a loader for its asset type, extensions and MIME types (see `AssetLoaders::register`), used from the next load on
+    pub fn register_loader<L: AssetLoader>(&mut self, loader: L) {
+        self.shared
+            .loaders
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .register(loader);
+    }
+
+    /// Verifies assets loaded from now on against the given manifest: those it lists fail to load unless their format version,
+    /// size and hash match. `None` turns verification off.
+    pub fn set_manifest(&mut self, manifest: Option<AssetManifest>) {
+        *self
+            .shared
+            .manifest
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = manifest.map(Arc::new);
+    }
+
+    /// The manifest assets are verified against, if any
+    pub fn manifest(&self) -> Option<Arc<AssetManifest>> {
+        self.shared.manifest()
+    }
+
+    /// The registered loaders
+    pub fn loaders(&self) -> RwLockReadGuard<'_, AssetLoaders> {
+        self.shared.loaders()
+    }
+
+    /// Sets how much memory assets may take before those no handle refers to anymore are evicted, least recently used first.
+    /// Applied at the next `update`.
+    pub fn set_budget(&mut self, budget: CacheBudget) {
+        self.budget = budget;
+    }
+
+    pub fn budget(&self) -> CacheBudget {
+        self.budget
+    }
+
+    /// Keeps the asset in memory even once no handle refers to it anymore, until it's unpinned
+    pub fn pin<T: Asset>(&self, handle: &Handle<T>) {
+        self.set_pinned(handle, true);
+    }
+
+    /// Lets the asset be freed once no handle refers to it anymore (see `pin`)
+    pub fn unpin<T: Asset>(&self, handle: &Handle<T>) {
+        self.set_pinned(handle, false);
+    }
+
+    fn set_pinned<T: Asset>(&self, handle: &Handle<T>, pinned: bool) {
+        if let Some(entry) = self
+            .shared
+            .slots()
+            .get_mut(&(TypeId::of::<T>(), handle.path().clone()))
+        {
+            entry.pinned = pinned;
+        }
+    }
+
+    /// Counts the GPU memory of what's been made from the asset (e.g. a vertex buffer from a mesh) against the GPU budget,
+    /// until the last handle on the asset drops (whoever made it should free it then)
+    pub fn track_gpu_memory<T: Asset>(&self, handle: &Handle<T>, bytes: usize) {
+        handle.slot.set_tracked_gpu_memory(bytes);
+    }
+
+    /// The state of the cache: memory used against the budget, hits, evictions, and the same per asset type
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            budget: self.budget,
+            hits: self.shared.hits.load(Ordering::Relaxed),
+            misses: self.shared.misses.load(Ordering::Relaxed),
+            evictions: self.shared.evictions.load(Ordering::Relaxed),
+            ..CacheStats::default()
+        };
+        for entry in self.shared.slots().values() {
+            let memory = entry.slot.memory();
+            let referenced = entry.is_referenced() as usize;
+            let pinned = entry.pinned as usize;
+            let type_stats = stats.by_type.entry(entry.slot.type_name()).or_default();
+            type_stats.assets += 1;
+            type_stats.referenced += referenced;
+            type_stats.pinned += pinned;
+            type_stats.memory += memory;
+            stats.assets += 1;
+            stats.referenced += referenced;
+            stats.pinned += pinned;
+            stats.memory += memory;
+        }
+        stats
+    }
+
+    /// Turns hot reloading on or off: whether assets whose file changed on disk are reloaded during `update`.
+    ///
+    /// Only assets read from plain files are watched, not those in archives.
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+    }
+
+    pub fn hot_reload(&self) -> bool {
+        self.hot_reload
+    }
+
+    /// Sets how often `update` checks the assets' files, when hot reloading
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    /// A handle on the asset at the given virtual path, which starts loading on a worker thread if it isn't already loaded.
+    /// The loader is picked by the path's extension.
+    ///
+    /// Fails straight away if the path is invalid, any other failure is reported by the handle's `LoadState`.
+    pub fn load<T: Asset>(&self, path: &str) -> Result<Handle<T>, ResourceError> {
+        Ok(self
+            .shared
+            .load(VirtualPath::new(path)?, LoadRequest::default()))
+    }
+
+    /// Like `load`, giving the loader settings (see `AssetLoader::Settings`). Loading fails if they aren't those of the loader.
+    ///
+    /// An asset which is already in memory keeps the settings it was loaded with, until it's freed.
+    pub fn load_with_settings<T: Asset, S: Send + Sync + 'static>(
+        &self,
+        path: &str,
+        settings: S,
+    ) -> Result<Handle<T>, ResourceError> {
+        let request = LoadRequest {
+            mime: None,
+            settings: Some(Arc::new(settings)),
+        };
+        Ok(self.shared.load(VirtualPath::new(path)?, request))
+    }
+
+    /// Like `load`, picking the loader by the given MIME type rather than by the path's extension
+    pub fn load_mime<T: Asset>(&self, path: &str, mime: &str) -> Result<Handle<T>, ResourceError> {
+        let request = LoadRequest {
+            mime: Some(mime.to_string()),
+            settings: None,
+        };
+        Ok(self.shared.load(VirtualPath::new(path)?, request))
+    }
+
+    /// Loads the asset again in the background, and swaps it in its handles once it has loaded
+    pub fn reload<T: Asset>(&self, handle: &Handle<T>) {
+        let request = {
+            let mut slots = self.shared.slots();
+            match slots.get_mut(&(TypeId::of::<T>(), handle.path().clone())) {
+                Some(entry) => {
+                    entry.source = self.shared.source(handle.path());
+                    entry.request.clone()
+                }
+                None => LoadRequest::default(),
+            }
+        };
+        self.shared.spawn_load(&handle.slot, request);
+    }
+
+    /// Blocks until the asset's current (re)load is done, and those of the assets it depends on (see `Handle::dependencies`),
+    /// running other jobs on this thread meanwhile. Returns the asset's state.
+    pub fn wait<T: Asset>(&self, handle: &Handle<T>) -> LoadState {
+        visit_with_dependencies(handle.slot.clone(), |slot| {
+            if let Some(load) = slot.load() {
+                self.shared.jobs.help_until(&load.completion);
+            }
+            true
+        });
+        handle.state()
+    }
+
+    /// Reloads the assets whose file changed since they were last read, if it's time to check at `now`
+    fn poll_changes(&mut self, now: Instant) {
+        let polled_recently = self
+            .last_poll
+            .is_some_and(|last_poll| now - last_poll < self.poll_interval);
+        if !self.hot_reload || polled_recently {
+            return;
+        }
+        self.last_poll = Some(now);
+        let mut changed = vec![];
+        for ((_, path), entry) in self.shared.slots().iter_mut() {
+            // A file may also change by being overridden by another mount, or removed
+            let source = self.shared.source(path);
+            if source != entry.source {
+                entry.source = source;
+                changed.push((entry.reload, entry.slot.clone(), entry.request.clone()));
+            }
+        }
+        for (reload, slot, request) in changed {
+            reload(&self.shared, slot, request);
+        }
+    }
+
+    /// A handle on the asset at the given virtual path, if it's been requested and is still in memory (cached or not)
+    pub fn get<T: Asset>(&self, path: &VirtualPath) -> Option<Handle<T>> {
+        self.shared.get(&mut self.shared.slots(), path)
+    }
+
+    /// The number of assets in memory, loaded or not, referenced or cached
+    pub fn len(&self) -> usize {
+        self.shared.slots().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frees the assets no handle refers to anymore which don't fit in the budget (or all of them without caching),
+    /// least recently used first. Only assets taking some of a memory over budget are freed for it.
+    /// Assets they depended on may be freed in turn.
+    fn evict(&self, events: &mut Vec<AssetEvent>) {
+        let frame = self.shared.frame.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut slots = self.shared.slots();
+        let mut memory = AssetMemory::default();
+        for entry in slots.values_mut() {
+            if entry.is_referenced() {
+                entry.last_used = frame;
+            } else {
+                entry.slot.set_tracked_gpu_memory(0);
+            }
+            memory += entry.slot.memory();
+        }
+        let caching = self.budget.is_caching();
+        loop {
+            if caching && !self.budget.is_exceeded_by(memory) {
+                return;
+            }
+            let mut evictable: Vec<_> = slots
+                .iter()
+                .filter(|(_, entry)| !entry.pinned && !entry.is_referenced())
+                .map(|(key, entry)| (entry.last_used, key.clone()))
+                .collect();
+            evictable.sort_unstable_by_key(|(last_used, _)| *last_used);
+            let mut evicted = false;
+            for (_, key) in evictable {
+                if caching && !self.budget.is_exceeded_by(memory) {
+                    break;
+                }
+                let freed = slots[&key].slot.memory();
+                if caching && !self.budget.is_relieved_by(memory, freed) {
+                    continue;
+                }
+                if slots.remove(&key).is_some() {
+                    memory -= freed;
+                    evicted = true;
+                    self.shared.evictions.fetch_add(1, Ordering::Relaxed);
+                    events.push(AssetEvent::Freed { path: key.1 });
+                }
+            }
+            // Evicting assets can unreference those they depended on, which are looked at in the next pass
+            if !evicted {
+                return;
+            }
+        }
+    }
+
+    /// Starts reloading the assets whose file changed (with hot reloading on), evicts assets from the cache,
+    /// and publishes the `AssetEvent`s since the last update
+    pub fn update(ctx: &mut AppContext) {
+        // Polled on the context's clock, so a `ManualClock` decides when files are checked
+        let now = ctx.time().clock().now();
+        ctx.assets_mut().poll_changes(now);
+        let shared = ctx.assets().shared.clone();
+        let mut events = std::mem::take(
+            &mut *shared
+                .events
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        ctx.assets().evict(&mut events);
+        for event in events {
+            ctx.publish(event.into());
+        }
+    }
+}
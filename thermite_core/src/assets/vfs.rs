@@ -0,0 +1,559 @@
+/*
+    ABSTRACT: A virtual file system. Sources of files (directories, or anything implementing `MountSource`) are
+    mounted at virtual paths with a priority, and a virtual path resolves to the highest priority mount which has it,
+    so that the user data directory can override the shipped assets, and mods can override both.
+
+    Sources can be directories, or packed archives (see archive.rs).
+
+    Virtual paths always use `/`, are relative to the root of the VFS, and can never escape it (`..` is rejected).
+*/
+use crate::assets::archive::{ArchiveError, ArchiveSource, ARCHIVE_EXTENSION};
+use log::warn;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Priority of the working directory mount, see `Vfs::with_default_mounts`
+pub const WORKING_DIR_PRIORITY: i32 = 0;
+/// Priority of the first archive next to the executable, see `Vfs::with_default_mounts`
+pub const ARCHIVE_PRIORITY: i32 = 1;
+/// Priority of the executable's directory mount, see `Vfs::with_default_mounts`
+pub const EXE_DIR_PRIORITY: i32 = 10;
+/// Priority of the user data directory mount, see `Vfs::with_default_mounts`
+pub const USER_DATA_PRIORITY: i32 = 20;
+/// Priority of the first mod, see `Vfs::mount_mods`
+pub const MOD_PRIORITY: i32 = 100;
+
+/// Errors relating to the `Vfs`
+#[derive(Debug)]
+pub enum VfsError {
+    /// The path is absolute, or escapes the root of the VFS with `..`
+    InvalidPath(String),
+    NotFound(String),
+    AlreadyMounted(String),
+    Io(io::Error),
+}
+
+impl From<io::Error> for VfsError {
+    fn from(error: io::Error) -> Self {
+        VfsError::Io(error)
+    }
+}
+
+impl std::fmt::Display for VfsError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VfsError::InvalidPath(path) => write!(fmt, "{:?}: {}", self, path),
+            VfsError::NotFound(path) => write!(fmt, "{:?}: {}", self, path),
+            VfsError::AlreadyMounted(name) => write!(fmt, "{:?}: {}", self, name),
+            VfsError::Io(err) => write!(fmt, "{:?}: {}", self, err),
+        }
+    }
+}
+
+impl std::error::Error for VfsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VfsError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A normalized path inside the VFS: `/` separated, without `.` or empty components, and never escaping the root
+#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Clone, Default)]
+pub struct VirtualPath {
+    path: String,
+}
+
+impl VirtualPath {
+    /// Normalizes a path, accepting `\` as a separator. The empty path is the root.
+    ///
+    /// Fails on absolute paths (including drive letters), and on any `..` component.
+    pub fn new(path: &str) -> Result<Self, VfsError> {
+        let invalid = || VfsError::InvalidPath(path.to_string());
+        let unified = path.replace('\\', "/");
+        if unified.starts_with('/') {
+            return Err(invalid());
+        }
+        let mut components = vec![];
+        for component in unified.split('/') {
+            match component {
+                "" | "." => (),
+                ".." => return Err(invalid()),
+                // Drive letters and alternate data streams
+                component if component.contains(':') => return Err(invalid()),
+                component => components.push(component),
+            }
+        }
+        Ok(Self {
+            path: components.join("/"),
+        })
+    }
+
+    /// The root of the VFS
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Appends a relative path, which is normalized (and checked) like `new`
+    pub fn join(&self, path: &str) -> Result<Self, VfsError> {
+        let path = Self::new(path)?;
+        Ok(match (self.is_root(), path.is_root()) {
+            (true, _) => path,
+            (false, true) => self.clone(),
+            (false, false) => Self {
+                path: format!("{}/{}", self.path, path.path),
+            },
+        })
+    }
+
+    /// The rest of this path, if it's inside `prefix`
+    pub fn strip_prefix(&self, prefix: &VirtualPath) -> Option<VirtualPath> {
+        if prefix.is_root() {
+            return Some(self.clone());
+        }
+        if self.path == prefix.path {
+            return Some(Self::root());
+        }
+        self.path
+            .strip_prefix(&prefix.path)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .map(|rest| Self {
+                path: rest.to_string(),
+            })
+    }
+
+    /// The components of the path, from the root
+    pub fn components(&self) -> impl Iterator<Item = &str> {
+        self.path
+            .split('/')
+            .filter(|component| !component.is_empty())
+    }
+
+    /// The last component's extension, if any
+    pub fn extension(&self) -> Option<&str> {
+        let file_name = self.components().last()?;
+        file_name
+            .rfind('.')
+            .filter(|idx| *idx > 0)
+            .map(|idx| &file_name[idx + 1..])
+    }
+
+    /// Where this path lives under the given directory on disk
+    pub fn to_path(&self, root: &Path) -> PathBuf {
+        self.components()
+            .fold(root.to_path_buf(), |path, component| path.join(component))
+    }
+}
+
+impl std::fmt::Display for VirtualPath {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "/{}", self.path)
+    }
+}
+
+/// Somewhere files can be mounted from. Paths given to a source are relative to its mount point.
+pub trait MountSource: Send + Sync {
+    fn contains(&self, path: &VirtualPath) -> bool;
+    fn read(&self, path: &VirtualPath) -> io::Result<Vec<u8>>;
+    /// Where the file lives on disk, if it's a plain file (e.g. to watch it for changes)
+    fn real_path(&self, path: &VirtualPath) -> Option<PathBuf>;
+    /// Describes the source for logs and errors, e.g. its directory
+    fn describe(&self) -> String;
+}
+
+/// Mounts a directory on disk. The directory doesn't have to exist, it just won't contain anything until it does.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl MountSource for DirectorySource {
+    fn contains(&self, path: &VirtualPath) -> bool {
+        path.to_path(&self.root).is_file()
+    }
+
+    fn read(&self, path: &VirtualPath) -> io::Result<Vec<u8>> {
+        fs::read(path.to_path(&self.root))
+    }
+
+    fn real_path(&self, path: &VirtualPath) -> Option<PathBuf> {
+        Some(path.to_path(&self.root))
+    }
+
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+}
+
+/// A source mounted in a `Vfs`
+pub struct Mount {
+    name: String,
+    mount_point: VirtualPath,
+    priority: i32,
+    source: Box<dyn MountSource>,
+}
+
+impl Mount {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mount_point(&self) -> &VirtualPath {
+        &self.mount_point
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn source(&self) -> &dyn MountSource {
+        self.source.as_ref()
+    }
+}
+
+/// Mounted sources of files, from the highest to the lowest priority
+#[derive(Default)]
+pub struct Vfs {
+    mounts: Vec<Mount>,
+}
+
+impl Vfs {
+    /// A VFS without any mount
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A VFS with the working directory, the archives (`*.tpak`) next to the executable, the executable's directory
+    /// and (with an application name) the user data directory (e.g. `~/.local/share/<app_name>` on Linux) mounted at the root,
+    /// in increasing order of priority. Loose files next to the executable override the archives.
+    ///
+    /// Directories which can't be determined and archives which can't be opened are left out.
+    pub fn with_default_mounts(app_name: Option<&str>) -> Self {
+        let mut vfs = Self::new();
+        let mount_dir = |vfs: &mut Self, name: &str, dir: PathBuf, priority: i32| {
+            vfs.mount(name, "", priority, DirectorySource::new(dir))
+                .expect("Default mounts have distinct names and a valid mount point");
+        };
+        if let Ok(working_dir) = std::env::current_dir() {
+            mount_dir(&mut vfs, "working_dir", working_dir, WORKING_DIR_PRIORITY);
+        }
+        if let Some(exe_dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        {
+            vfs.mount_archives(&exe_dir, ARCHIVE_PRIORITY);
+            mount_dir(&mut vfs, "exe_dir", exe_dir, EXE_DIR_PRIORITY);
+        }
+        if let Some(user_data) = app_name.and_then(user_data_dir) {
+            mount_dir(&mut vfs, "user_data", user_data, USER_DATA_PRIORITY);
+        }
+        vfs
+    }
+
+    /// Mounts a source at the given virtual path. Between mounts of equal priority, the one mounted last wins.
+    pub fn mount<S: MountSource + 'static>(
+        &mut self,
+        name: &str,
+        mount_point: &str,
+        priority: i32,
+        source: S,
+    ) -> Result<(), VfsError> {
+        if self.mounts.iter().any(|mount| mount.name == name) {
+            return Err(VfsError::AlreadyMounted(name.to_string()));
+        }
+        let mount = Mount {
+            name: name.to_string(),
+            mount_point: VirtualPath::new(mount_point)?,
+            priority,
+            source: Box::new(source),
+        };
+        let idx = self
+            .mounts
+            .iter()
+            .position(|existing| existing.priority <= priority)
+            .unwrap_or(self.mounts.len());
+        self.mounts.insert(idx, mount);
+        Ok(())
+    }
+
+    /// Mounts every directory inside `mods_dir` at the root, above everything else, in alphabetical order (so later mods win).
+    /// Returns the number of mods mounted, which is zero if `mods_dir` doesn't exist.
+    pub fn mount_mods(&mut self, mods_dir: &Path) -> Result<usize, VfsError> {
+        if !mods_dir.is_dir() {
+            return Ok(0);
+        }
+        let mut mods: Vec<PathBuf> = fs::read_dir(mods_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        mods.sort();
+        for (idx, dir) in mods.iter().enumerate() {
+            let name = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            self.mount(
+                &format!("mod:{}", name),
+                "",
+                MOD_PRIORITY + idx as i32,
+                DirectorySource::new(dir.clone()),
+            )?;
+        }
+        Ok(mods.len())
+    }
+
+    /// Mounts a packed archive at the given virtual path, named after the archive's file name (see `ArchiveSource::open`)
+    pub fn mount_archive(
+        &mut self,
+        archive: &Path,
+        mount_point: &str,
+        priority: i32,
+    ) -> Result<(), ArchiveError> {
+        let name = format!(
+            "archive:{}",
+            archive
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        );
+        let source = ArchiveSource::open(archive)?;
+        self.mount(&name, mount_point, priority, source)?;
+        Ok(())
+    }
+
+    /// Mounts every archive (`*.tpak`) inside `dir` at the root, from `priority` upwards in alphabetical order.
+    /// Archives which can't be opened are logged and skipped. Returns the number of archives mounted.
+    pub fn mount_archives(&mut self, dir: &Path, priority: i32) -> usize {
+        let mut archives: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.is_file() && path.extension().is_some_and(|ext| ext == ARCHIVE_EXTENSION)
+                })
+                .collect(),
+            Err(_) => return 0,
+        };
+        archives.sort();
+        let mut mounted = 0;
+        for archive in archives {
+            match self.mount_archive(&archive, "", priority + mounted as i32) {
+                Ok(()) => mounted += 1,
+                Err(error) => warn!("Couldn't mount {}: {}", archive.display(), error),
+            }
+        }
+        mounted
+    }
+
+    /// Removes a mount, returns whether it existed
+    pub fn unmount(&mut self, name: &str) -> bool {
+        let len = self.mounts.len();
+        self.mounts.retain(|mount| mount.name != name);
+        self.mounts.len() != len
+    }
+
+    /// Every mount, from the highest to the lowest priority
+    pub fn mounts(&self) -> impl Iterator<Item = &Mount> {
+        self.mounts.iter()
+    }
+
+    /// The mount a virtual path resolves to, and the path relative to that mount
+    pub fn resolve(&self, path: &VirtualPath) -> Result<(&Mount, VirtualPath), VfsError> {
+        self.mounts
+            .iter()
+            .filter_map(|mount| {
+                path.strip_prefix(&mount.mount_point)
+                    .map(|relative| (mount, relative))
+            })
+            .find(|(mount, relative)| mount.source.contains(relative))
+            .ok_or_else(|| VfsError::NotFound(path.to_string()))
+    }
+
+    pub fn exists(&self, path: &VirtualPath) -> bool {
+        self.resolve(path).is_ok()
+    }
+
+    pub fn read(&self, path: &VirtualPath) -> Result<Vec<u8>, VfsError> {
+        let (mount, relative) = self.resolve(path)?;
+        Ok(mount.source.read(&relative)?)
+    }
+
+    pub fn read_to_string(&self, path: &VirtualPath) -> Result<String, VfsError> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| VfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err)))
+    }
+
+    /// Where the file a virtual path resolves to lives on disk, if it's a plain file
+    pub fn real_path(&self, path: &VirtualPath) -> Result<PathBuf, VfsError> {
+        let (mount, relative) = self.resolve(path)?;
+        mount
+            .source
+            .real_path(&relative)
+            .ok_or_else(|| VfsError::NotFound(path.to_string()))
+    }
+}
+
+/// The directory an application's per-user data lives in, e.g. `~/.local/share/<app_name>` on Linux
+pub fn user_data_dir(app_name: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(app_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Files held in memory, each containing the source's name
+    struct MemorySource {
+        name: &'static str,
+        files: Vec<&'static str>,
+    }
+
+    impl MemorySource {
+        fn new(name: &'static str, files: &[&'static str]) -> Self {
+            Self {
+                name,
+                files: files.to_vec(),
+            }
+        }
+    }
+
+    impl MountSource for MemorySource {
+        fn contains(&self, path: &VirtualPath) -> bool {
+            self.files.contains(&path.as_str())
+        }
+
+        fn read(&self, path: &VirtualPath) -> io::Result<Vec<u8>> {
+            if self.contains(path) {
+                Ok(self.name.as_bytes().to_vec())
+            } else {
+                Err(io::ErrorKind::NotFound.into())
+            }
+        }
+
+        fn real_path(&self, _path: &VirtualPath) -> Option<PathBuf> {
+            None
+        }
+
+        fn describe(&self) -> String {
+            self.name.to_string()
+        }
+    }
+
+    fn path(path: &str) -> VirtualPath {
+        VirtualPath::new(path).expect("Path is valid")
+    }
+
+    fn read(vfs: &Vfs, file: &str) -> String {
+        vfs.read_to_string(&path(file)).expect("File exists")
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        let cases = [
+            ("textures/stone.png", "textures/stone.png"),
+            ("textures\\stone.png", "textures/stone.png"),
+            ("./textures//./stone.png/", "textures/stone.png"),
+            ("", ""),
+            (".", ""),
+        ];
+        for &(raw, normalized) in cases.iter() {
+            assert_eq!(path(raw).as_str(), normalized, "{}", raw);
+        }
+        assert!(path("").is_root());
+        assert_eq!(path("a/b").join("c\\d").unwrap().as_str(), "a/b/c/d");
+    }
+
+    #[test]
+    fn escaping_paths_are_rejected() {
+        let escaping = [
+            "..",
+            "../secrets.txt",
+            "textures/../../secrets.txt",
+            "textures\\..\\..\\secrets.txt",
+            "/etc/passwd",
+            "\\\\server\\share",
+            "C:/Windows/System32",
+            "c:secrets.txt",
+            "textures/stone.png:stream",
+        ];
+        for raw in escaping.iter() {
+            assert!(
+                matches!(VirtualPath::new(raw), Err(VfsError::InvalidPath(_))),
+                "{}",
+                raw
+            );
+        }
+        assert!(path("textures").join("../secrets.txt").is_err());
+        assert!(path("textures").join("/secrets.txt").is_err());
+    }
+
+    #[test]
+    fn higher_priorities_win() {
+        let mut vfs = Vfs::new();
+        vfs.mount("low", "", 0, MemorySource::new("low", &["a.txt", "b.txt"]))
+            .unwrap();
+        vfs.mount("high", "", 10, MemorySource::new("high", &["a.txt"]))
+            .unwrap();
+        vfs.mount("mid", "", 5, MemorySource::new("mid", &["a.txt", "b.txt"]))
+            .unwrap();
+        let names: Vec<&str> = vfs.mounts().map(|mount| mount.name()).collect();
+        assert_eq!(names, vec!["high", "mid", "low"]);
+        assert_eq!(read(&vfs, "a.txt"), "high");
+        assert_eq!(read(&vfs, "b.txt"), "mid");
+        assert!(vfs.unmount("high"));
+        assert_eq!(read(&vfs, "a.txt"), "mid");
+        assert!(!vfs.exists(&path("c.txt")));
+        assert!(matches!(
+            vfs.mount("low", "", 0, MemorySource::new("again", &[])),
+            Err(VfsError::AlreadyMounted(_))
+        ));
+    }
+
+    #[test]
+    fn the_last_mount_wins_between_equal_priorities() {
+        let mut vfs = Vfs::new();
+        vfs.mount("first", "", 0, MemorySource::new("first", &["a.txt"]))
+            .unwrap();
+        vfs.mount("second", "", 0, MemorySource::new("second", &["a.txt"]))
+            .unwrap();
+        assert_eq!(read(&vfs, "a.txt"), "second");
+    }
+
+    #[test]
+    fn mounts_only_see_paths_under_their_mount_point() {
+        let mut vfs = Vfs::new();
+        vfs.mount("root", "", 0, MemorySource::new("root", &["mods/a.txt"]))
+            .unwrap();
+        vfs.mount("mods", "mods", 10, MemorySource::new("mods", &["a.txt"]))
+            .unwrap();
+        assert_eq!(read(&vfs, "mods/a.txt"), "mods");
+        // The mount point's own prefix isn't in the mount
+        assert!(!vfs.exists(&path("a.txt")));
+        let (mount, relative) = vfs.resolve(&path("mods/a.txt")).unwrap();
+        assert_eq!((mount.name(), relative.as_str()), ("mods", "a.txt"));
+        assert!(matches!(
+            vfs.mount("escape", "../outside", 0, MemorySource::new("escape", &[])),
+            Err(VfsError::InvalidPath(_))
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+/*
+    ABSTRACT: Command line tool building asset manifests, and validating asset trees or archives against them
+    before shipping (see assets::integrity).
+
+    thermite_manifest <assets dir> <manifest.ron> [--version <version>] [--prefix <virtual dir>]
+    thermite_manifest --validate <assets dir|archive.tpak> <manifest.ron> [--prefix <virtual dir>]
+*/
+use std::{path::Path, process};
+use thermite_core::assets::{archive::ArchiveSource, integrity::AssetManifest};
+
+const USAGE: &str = "Usage:
+    thermite_manifest <assets dir> <manifest.ron> [--version <version>] [--prefix <virtual dir>]
+    thermite_manifest --validate <assets dir|archive.tpak> <manifest.ron> [--prefix <virtual dir>]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// The values of the given options, in order, failing on any other option
+fn parse_options(options: &[String], known: &[&str]) -> Vec<Option<String>> {
+    let mut values = vec![None; known.len()];
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .unwrap_or_else(|| fail(&format!("Missing value for {}\n{}", option, USAGE)));
+        match known.iter().position(|known| known == option) {
+            Some(idx) => values[idx] = Some(value.clone()),
+            None => fail(&format!("Unknown option {}\n{}", option, USAGE)),
+        }
+    }
+    values
+}
+
+fn build(assets: &Path, manifest_path: &Path, options: &[String]) {
+    let values = parse_options(options, &["--version", "--prefix"]);
+    let version = values[0].as_deref().unwrap_or("0.0.0");
+    let prefix = values[1].as_deref().unwrap_or("");
+    let mut manifest = AssetManifest::new(version);
+    let files = manifest
+        .add_directory(assets, prefix)
+        .unwrap_or_else(|err| fail(&err.to_string()));
+    manifest
+        .save(manifest_path)
+        .unwrap_or_else(|err| fail(&err.to_string()));
+    println!(
+        "Listed {} files from {} in {} (version {})",
+        files,
+        assets.display(),
+        manifest_path.display(),
+        version
+    );
+}
+
+fn validate(assets: &Path, manifest_path: &Path, options: &[String]) {
+    let values = parse_options(options, &["--prefix"]);
+    let manifest = AssetManifest::load(manifest_path).unwrap_or_else(|err| fail(&err.to_string()));
+    let prefix = values[0].as_deref().unwrap_or("");
+    let report = if assets.is_dir() {
+        manifest.validate_directory(assets, prefix)
+    } else {
+        let archive = ArchiveSource::open(assets).unwrap_or_else(|err| fail(&err.to_string()));
+        manifest.validate_archive(&archive, prefix)
+    }
+    .unwrap_or_else(|err| fail(&err.to_string()));
+    for error in &report.errors {
+        eprintln!("{}", error);
+    }
+    if !report.is_valid() {
+        fail(&format!(
+            "{} errors in {} files checked against version {}",
+            report.errors.len(),
+            report.checked,
+            manifest.version
+        ));
+    }
+    println!(
+        "{} files match version {} of the manifest",
+        report.checked, manifest.version
+    );
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, assets, manifest, options @ ..] if command == "--validate" => {
+            validate(Path::new(assets), Path::new(manifest), options)
+        }
+        [assets, manifest, options @ ..] if !assets.starts_with("--") => {
+            build(Path::new(assets), Path::new(manifest), options)
+        }
+        _ => fail(USAGE),
+    }
+}
@@ -0,0 +1,102 @@
+/*
+    ABSTRACT: Command line tool building and inspecting packed asset archives (see assets::archive).
+
+    thermite_pack <assets dir> <archive.tpak> [--level <0-9>] [--store <ext,ext,...>] [--prefix <virtual dir>]
+    thermite_pack --list <archive.tpak>
+    thermite_pack --verify <archive.tpak>
+*/
+use std::{path::Path, process};
+use thermite_core::assets::archive::{ArchiveBuilder, ArchiveCompression, ArchiveSource};
+
+const USAGE: &str = "Usage:
+    thermite_pack <assets dir> <archive.tpak> [--level <0-9>] [--store <ext,ext,...>] [--prefix <virtual dir>]
+    thermite_pack --list <archive.tpak>
+    thermite_pack --verify <archive.tpak>";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+fn list(archive: &Path) {
+    let archive = ArchiveSource::open(archive).unwrap_or_else(|err| fail(&err.to_string()));
+    let mut entries: Vec<_> = archive.entries().collect();
+    entries.sort_by(|a, b| a.path().cmp(b.path()));
+    for entry in &entries {
+        let compression = match entry.compression() {
+            ArchiveCompression::Stored => "stored",
+            ArchiveCompression::Deflate => "deflate",
+        };
+        println!(
+            "{:>10} {:>10} {:<8} {:08x} {}",
+            entry.size(),
+            entry.stored_size(),
+            compression,
+            entry.checksum(),
+            entry.path()
+        );
+    }
+    println!("{} entries", entries.len());
+}
+
+fn verify(archive: &Path) {
+    let archive = ArchiveSource::open(archive).unwrap_or_else(|err| fail(&err.to_string()));
+    let failures = archive
+        .entries()
+        .filter_map(|entry| archive.read_entry(entry.path()).err())
+        .inspect(|err| eprintln!("{}", err))
+        .count();
+    if failures > 0 {
+        fail(&format!("{} entries failed verification", failures));
+    }
+    println!("{} entries verified", archive.entries().count());
+}
+
+fn pack(assets: &Path, archive: &Path, options: &[String]) {
+    let mut builder = ArchiveBuilder::new();
+    let mut prefix = String::new();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .unwrap_or_else(|| fail(&format!("Missing value for {}\n{}", option, USAGE)));
+        match option.as_str() {
+            "--level" => {
+                let level = value
+                    .parse()
+                    .unwrap_or_else(|_| fail(&format!("Invalid level {}", value)));
+                builder = builder.with_compression_level(level);
+            }
+            "--store" => builder = builder.with_stored_extensions(value.split(',')),
+            "--prefix" => prefix = value.clone(),
+            _ => fail(&format!("Unknown option {}\n{}", option, USAGE)),
+        }
+    }
+    builder
+        .add_directory(assets, &prefix)
+        .unwrap_or_else(|err| fail(&err.to_string()));
+    let stats = builder
+        .write(archive)
+        .unwrap_or_else(|err| fail(&err.to_string()));
+    println!(
+        "Packed {} files ({} compressed) from {} into {}: {} bytes -> {} bytes",
+        stats.entries,
+        stats.compressed_entries,
+        assets.display(),
+        archive.display(),
+        stats.original_size,
+        stats.archive_size
+    );
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, archive] if command == "--list" => list(Path::new(archive)),
+        [command, archive] if command == "--verify" => verify(Path::new(archive)),
+        [assets, archive, options @ ..] if !assets.starts_with("--") => {
+            pack(Path::new(assets), Path::new(archive), options)
+        }
+        _ => fail(USAGE),
+    }
+}
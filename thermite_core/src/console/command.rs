@@ -0,0 +1,351 @@
+/*
+    ABSTRACT: Console commands: a name, a help string, typed argument specifications and a handler.
+    Command lines are split into tokens (with "double quotes" for spaces), and the tokens are parsed
+    and checked against the command's arguments before its handler ever runs.
+*/
+use crate::platform::app::AppContext;
+use crate::tools::config::{ConfigError, ConfigRegistry, ConfigValue};
+
+/// Errors relating to the console and its commands
+#[derive(Debug)]
+pub enum ConsoleError {
+    UnknownCommand(String),
+    AlreadyRegistered(String),
+    /// A double quote wasn't closed before the end of the line
+    UnterminatedQuote,
+    MissingArgument {
+        command: String,
+        argument: String,
+    },
+    TooManyArguments {
+        command: String,
+        expected: usize,
+    },
+    InvalidArgument {
+        command: String,
+        argument: String,
+        expected: &'static str,
+        found: String,
+    },
+    Config(ConfigError),
+    /// The command ran, but failed
+    Failed(String),
+}
+
+impl From<ConfigError> for ConsoleError {
+    fn from(error: ConfigError) -> Self {
+        ConsoleError::Config(error)
+    }
+}
+
+impl std::fmt::Display for ConsoleError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsoleError::UnknownCommand(name) => write!(fmt, "Unknown command: {}", name),
+            ConsoleError::AlreadyRegistered(name) => write!(fmt, "{:?}: {}", self, name),
+            ConsoleError::UnterminatedQuote => write!(fmt, "Unterminated quote"),
+            ConsoleError::MissingArgument { command, argument } => {
+                write!(fmt, "{}: missing argument <{}>", command, argument)
+            }
+            ConsoleError::TooManyArguments { command, expected } => {
+                write!(
+                    fmt,
+                    "{}: expected at most {} argument(s)",
+                    command, expected
+                )
+            }
+            ConsoleError::InvalidArgument {
+                command,
+                argument,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "{}: <{}> expected {}, found {}",
+                command, argument, expected, found
+            ),
+            ConsoleError::Config(err) => write!(fmt, "{}", err),
+            ConsoleError::Failed(reason) => write!(fmt, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConsoleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConsoleError::Config(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The type of a command's argument
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum ArgKind {
+    /// `true`/`false`, `on`/`off` or `1`/`0`
+    Bool,
+    Int,
+    Float,
+    String,
+    /// The name of a registered console variable (configuration key)
+    CVar,
+}
+
+impl ArgKind {
+    pub fn type_name(self) -> &'static str {
+        match self {
+            ArgKind::Bool => "bool",
+            ArgKind::Int => "int",
+            ArgKind::Float => "float",
+            ArgKind::String => "string",
+            ArgKind::CVar => "cvar",
+        }
+    }
+
+    /// Parses a token as an argument of this kind
+    fn parse(self, token: &str, config: &ConfigRegistry) -> Option<ConfigValue> {
+        match self {
+            ArgKind::Bool => parse_bool(token).map(ConfigValue::Bool),
+            ArgKind::Int => token.parse().ok().map(ConfigValue::Int),
+            ArgKind::Float => token.parse().ok().map(ConfigValue::Float),
+            ArgKind::String => Some(ConfigValue::String(token.to_string())),
+            ArgKind::CVar if config.is_registered(token) => {
+                Some(ConfigValue::String(token.to_string()))
+            }
+            ArgKind::CVar => None,
+        }
+    }
+}
+
+/// Parses the ways a console user might spell a boolean
+pub fn parse_bool(token: &str) -> Option<bool> {
+    match token.to_ascii_lowercase().as_str() {
+        "true" | "on" | "1" | "yes" => Some(true),
+        "false" | "off" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// The specification of one of a command's arguments
+#[derive(Debug, Clone)]
+pub struct ArgSpec {
+    pub name: String,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+/// A command's parsed arguments, in the order of its `ArgSpec`s. Optional arguments which weren't given are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct CommandArgs {
+    values: Vec<Option<ConfigValue>>,
+}
+
+impl CommandArgs {
+    pub fn get(&self, idx: usize) -> Option<&ConfigValue> {
+        self.values.get(idx).and_then(|value| value.as_ref())
+    }
+
+    pub fn bool(&self, idx: usize) -> Option<bool> {
+        match self.get(idx) {
+            Some(ConfigValue::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, idx: usize) -> Option<i64> {
+        match self.get(idx) {
+            Some(ConfigValue::Int(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, idx: usize) -> Option<f64> {
+        match self.get(idx) {
+            Some(ConfigValue::Float(f)) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// The argument as text, for `String` and `CVar` arguments
+    pub fn string(&self, idx: usize) -> Option<&str> {
+        match self.get(idx) {
+            Some(ConfigValue::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Runs a command with its parsed arguments, and returns the text to print (which may be empty)
+pub type CommandHandler =
+    Box<dyn FnMut(&mut AppContext, &CommandArgs) -> Result<String, ConsoleError>>;
+
+/// A named console command
+pub struct ConsoleCommand {
+    name: String,
+    help: String,
+    args: Vec<ArgSpec>,
+    handler: CommandHandler,
+}
+
+impl ConsoleCommand {
+    /// Creates a command without arguments, add them with `with_arg` and `with_optional_arg`
+    pub fn new<N, H, F>(name: N, help: H, handler: F) -> Self
+    where
+        N: Into<String>,
+        H: Into<String>,
+        F: FnMut(&mut AppContext, &CommandArgs) -> Result<String, ConsoleError> + 'static,
+    {
+        Self {
+            name: name.into(),
+            help: help.into(),
+            args: vec![],
+            handler: Box::new(handler),
+        }
+    }
+
+    /// Adds a required argument. Required arguments must come before optional ones.
+    pub fn with_arg<N: Into<String>>(mut self, name: N, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec {
+            name: name.into(),
+            kind,
+            optional: false,
+        });
+        self
+    }
+
+    /// Adds an optional argument, which must come after every required one
+    pub fn with_optional_arg<N: Into<String>>(mut self, name: N, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec {
+            name: name.into(),
+            kind,
+            optional: true,
+        });
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn help(&self) -> &str {
+        &self.help
+    }
+
+    pub fn args(&self) -> &[ArgSpec] {
+        &self.args
+    }
+
+    /// e.g. `wireframe [enabled:bool]`
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+        for arg in &self.args {
+            let (open, close) = if arg.optional { ('[', ']') } else { ('<', '>') };
+            usage.push_str(&format!(
+                " {}{}:{}{}",
+                open,
+                arg.name,
+                arg.kind.type_name(),
+                close
+            ));
+        }
+        usage
+    }
+
+    /// Checks and parses the tokens following the command's name against its arguments
+    pub fn parse_args(
+        &self,
+        tokens: &[String],
+        config: &ConfigRegistry,
+    ) -> Result<CommandArgs, ConsoleError> {
+        if tokens.len() > self.args.len() {
+            return Err(ConsoleError::TooManyArguments {
+                command: self.name.clone(),
+                expected: self.args.len(),
+            });
+        }
+        let mut values = Vec::with_capacity(self.args.len());
+        for (idx, spec) in self.args.iter().enumerate() {
+            let token = match tokens.get(idx) {
+                Some(token) => token,
+                None if spec.optional => {
+                    values.push(None);
+                    continue;
+                }
+                None => {
+                    return Err(ConsoleError::MissingArgument {
+                        command: self.name.clone(),
+                        argument: spec.name.clone(),
+                    })
+                }
+            };
+            let value =
+                spec.kind
+                    .parse(token, config)
+                    .ok_or_else(|| ConsoleError::InvalidArgument {
+                        command: self.name.clone(),
+                        argument: spec.name.clone(),
+                        expected: spec.kind.type_name(),
+                        found: token.clone(),
+                    })?;
+            values.push(Some(value));
+        }
+        Ok(CommandArgs { values })
+    }
+
+    /// Runs the command's handler
+    pub fn run(
+        &mut self,
+        ctx: &mut AppContext,
+        args: &CommandArgs,
+    ) -> Result<String, ConsoleError> {
+        (self.handler)(ctx, args)
+    }
+}
+
+/// Splits a command line into tokens on whitespace. Double quotes group words into one token, and `\"` or `\\` escape inside them.
+pub fn tokenize(line: &str) -> Result<Vec<String>, ConsoleError> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut in_token = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => token.push(escaped),
+                            None => return Err(ConsoleError::UnterminatedQuote),
+                        },
+                        Some(c) => token.push(c),
+                        None => return Err(ConsoleError::UnterminatedQuote),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            c => {
+                in_token = true;
+                token.push(c);
+            }
+        }
+    }
+    if in_token {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
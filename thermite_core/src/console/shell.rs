@@ -0,0 +1,535 @@
+/*
+    ABSTRACT: The developer console itself: an input line with history and autocompletion, a scrollback,
+    registered commands, and console variables. Console variables are the keys of the application's
+    `ConfigRegistry`: setting one sets it in the user layer, and the keys commands change can be saved back to the
+    user's configuration file.
+*/
+use crate::console::{
+    command::{parse_bool, tokenize, ArgKind, ConsoleCommand, ConsoleError},
+    system::ConsoleEvent,
+};
+use crate::platform::app::AppContext;
+use crate::tools::config::{ConfigError, ConfigEvent, ConfigLayer, ConfigRegistry, ConfigValue};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    path::PathBuf,
+};
+
+/// Lines kept in the scrollback by default
+pub const DEFAULT_SCROLLBACK: usize = 512;
+
+/// Commands implemented by the console itself, because they work on the console or its console variables
+const BUILTINS: [(&str, &str); 6] = [
+    (
+        "help",
+        "help [command]: lists every command, or describes a command or console variable",
+    ),
+    ("clear", "clear: clears the console's scrollback"),
+    (
+        "cvars",
+        "cvars [prefix]: lists the console variables, optionally only those starting with a prefix",
+    ),
+    (
+        "get",
+        "get <cvar>: shows a console variable's value, type, default and description",
+    ),
+    (
+        "set",
+        "set <cvar> <value>: sets a console variable (typing `<cvar> <value>` does the same)",
+    ),
+    (
+        "reset",
+        "reset <cvar>: resets a console variable to the value it had before being set in the console or user configuration",
+    ),
+];
+
+/// A developer console, see `ConsoleSystem` for hooking one up to an application
+pub struct Console {
+    commands: BTreeMap<String, ConsoleCommand>,
+    input: String,
+    history: Vec<String>,
+    /// Position in `history` while browsing it, `None` when editing a new line
+    history_cursor: Option<usize>,
+    scrollback: VecDeque<String>,
+    max_scrollback: usize,
+    persist_file: Option<PathBuf>,
+}
+
+impl Default for Console {
+    /// A console with the `echo`, `bus_stats` and `quit` commands, which doesn't persist console variables
+    fn default() -> Self {
+        let mut console = Self {
+            commands: BTreeMap::new(),
+            input: String::new(),
+            history: vec![],
+            history_cursor: None,
+            scrollback: VecDeque::new(),
+            max_scrollback: DEFAULT_SCROLLBACK,
+            persist_file: None,
+        };
+        for command in default_commands() {
+            console
+                .register(command)
+                .expect("Default console commands have unique names");
+        }
+        console
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Saves the console variables commands change in the user configuration layer to this TOML or RON file, so they persist
+    /// between runs. The rest of the file is left as it is.
+    pub fn with_persist_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.persist_file = Some(path.into());
+        self
+    }
+
+    /// Sets how many lines the scrollback keeps
+    pub fn with_max_scrollback(mut self, max_scrollback: usize) -> Self {
+        self.max_scrollback = max_scrollback;
+        self
+    }
+
+    /// Adds a command. Fails if a command with the same name exists.
+    pub fn register(&mut self, command: ConsoleCommand) -> Result<(), ConsoleError> {
+        let name = command.name().to_string();
+        if self.commands.contains_key(&name) || is_builtin(&name) {
+            return Err(ConsoleError::AlreadyRegistered(name));
+        }
+        self.commands.insert(name, command);
+        Ok(())
+    }
+
+    pub fn command(&self, name: &str) -> Option<&ConsoleCommand> {
+        self.commands.get(name)
+    }
+
+    /// The names of every command, built in or registered, in alphabetical order
+    pub fn command_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = BUILTINS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.commands.keys().map(|name| name.as_str()))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The line being typed
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn set_input<T: Into<String>>(&mut self, input: T) {
+        self.input = input.into();
+        self.history_cursor = None;
+    }
+
+    /// Types a character into the input line. Control characters are ignored.
+    pub fn push_char(&mut self, character: char) {
+        if !character.is_control() {
+            self.input.push(character);
+        }
+    }
+
+    /// Erases the last character of the input line
+    pub fn pop_char(&mut self) {
+        self.input.pop();
+    }
+
+    /// Replaces the input line with the previous line in the history
+    pub fn history_previous(&mut self) {
+        let cursor = match self.history_cursor {
+            Some(0) => return,
+            Some(cursor) => cursor - 1,
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.history_cursor = Some(cursor);
+        self.input = self.history[cursor].clone();
+    }
+
+    /// Replaces the input line with the next line in the history, or an empty line past its end
+    pub fn history_next(&mut self) {
+        match self.history_cursor {
+            Some(cursor) if cursor + 1 < self.history.len() => {
+                self.history_cursor = Some(cursor + 1);
+                self.input = self.history[cursor + 1].clone();
+            }
+            Some(_) => {
+                self.history_cursor = None;
+                self.input.clear();
+            }
+            None => (),
+        }
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// The console's output, from oldest to newest
+    pub fn scrollback(&self) -> impl Iterator<Item = &str> {
+        self.scrollback.iter().map(|line| line.as_str())
+    }
+
+    /// Adds lines of text to the scrollback
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.scrollback.len() == self.max_scrollback {
+                self.scrollback.pop_front();
+            }
+            self.scrollback.push_back(line.to_string());
+        }
+    }
+
+    /// Executes the input line, and clears it
+    pub fn submit(&mut self, ctx: &mut AppContext) -> Vec<String> {
+        let line = std::mem::take(&mut self.input);
+        self.execute(&line, ctx)
+    }
+
+    /// Executes a command line, adds it to the history, and returns the lines it printed to the scrollback
+    pub fn execute(&mut self, line: &str, ctx: &mut AppContext) -> Vec<String> {
+        let line = line.trim();
+        self.history_cursor = None;
+        if line.is_empty() {
+            return vec![];
+        }
+        if self.history.last().map(|last| last.as_str()) != Some(line) {
+            self.history.push(line.to_string());
+        }
+        if line == "clear" {
+            self.scrollback.clear();
+            return vec![];
+        }
+        // Only the console variables this line changed are saved, so the rest of the file is left as the user wrote it
+        let before = self
+            .persist_file
+            .as_ref()
+            .map(|_| user_values(&ctx.config().borrow()));
+        let mut output = format!("> {}\n", line);
+        match self.run_line(line, ctx) {
+            Ok(text) => output.push_str(&text),
+            Err(err) => output.push_str(&format!("error: {}", err)),
+        }
+        if let (Some(path), Some(before)) = (&self.persist_file, before) {
+            let mut config = ctx.config().borrow_mut();
+            let after = user_values(&config);
+            let changed: BTreeSet<&String> = before
+                .keys()
+                .chain(after.keys())
+                .filter(|key| before.get(*key) != after.get(*key))
+                .collect();
+            let changed: Vec<String> = changed.into_iter().cloned().collect();
+            if !changed.is_empty() {
+                if let Err(err) = config.save_keys(path, ConfigLayer::User, &changed) {
+                    output.push_str(&format!(
+                        "\nerror: couldn't save {}: {}",
+                        path.display(),
+                        err
+                    ));
+                }
+            }
+        }
+        self.print(&output);
+        output.lines().map(|line| line.to_string()).collect()
+    }
+
+    fn run_line(&mut self, line: &str, ctx: &mut AppContext) -> Result<String, ConsoleError> {
+        let tokens = tokenize(line)?;
+        let (name, args) = match tokens.split_first() {
+            Some((name, args)) => (name.as_str(), args),
+            None => return Ok(String::new()),
+        };
+        match (name, args) {
+            ("help", []) => Ok(self.help_all()),
+            ("help", [topic]) => self.help(topic, &ctx.config().borrow()),
+            ("cvars", []) => Ok(list_cvars(&ctx.config().borrow(), "")),
+            ("cvars", [prefix]) => Ok(list_cvars(&ctx.config().borrow(), prefix)),
+            ("get", [key]) => describe_cvar(&ctx.config().borrow(), key),
+            ("set", [key, value]) => set_cvar(ctx, key, value),
+            ("reset", [key]) => reset_cvar(ctx, key),
+            (builtin, _) if is_builtin(builtin) => Err(ConsoleError::Failed(format!(
+                "usage: {}",
+                builtin_help(builtin)
+            ))),
+            (name, args) if self.commands.contains_key(name) => {
+                let command = self.commands.get_mut(name).expect("Command exists");
+                let parsed = command.parse_args(args, &ctx.config().borrow())?;
+                let text = command.run(ctx, &parsed)?;
+                ctx.publish(
+                    ConsoleEvent::CommandExecuted {
+                        name: name.to_string(),
+                        args: args.to_vec(),
+                    }
+                    .into(),
+                );
+                Ok(text)
+            }
+            (key, []) if ctx.config().borrow().is_registered(key) => {
+                describe_cvar(&ctx.config().borrow(), key)
+            }
+            (key, [value]) if ctx.config().borrow().is_registered(key) => set_cvar(ctx, key, value),
+            (key, _) if ctx.config().borrow().is_registered(key) => {
+                Err(ConsoleError::TooManyArguments {
+                    command: key.to_string(),
+                    expected: 1,
+                })
+            }
+            (name, _) => Err(ConsoleError::UnknownCommand(name.to_string())),
+        }
+    }
+
+    fn help_all(&self) -> String {
+        let builtins = BUILTINS.iter().map(|(_, help)| help.to_string());
+        let commands = self
+            .commands
+            .values()
+            .map(|command| format!("{}: {}", command.usage(), command.help()));
+        let mut lines: Vec<String> = builtins.chain(commands).collect();
+        lines.sort_unstable();
+        lines.push(String::from(
+            "Type a console variable's name to see it, or its name and a value to set it (see `cvars`)",
+        ));
+        lines.join("\n")
+    }
+
+    fn help(&self, topic: &str, config: &ConfigRegistry) -> Result<String, ConsoleError> {
+        if is_builtin(topic) {
+            Ok(builtin_help(topic).to_string())
+        } else if let Some(command) = self.commands.get(topic) {
+            Ok(format!("{}: {}", command.usage(), command.help()))
+        } else if config.is_registered(topic) {
+            describe_cvar(config, topic)
+        } else {
+            Err(ConsoleError::UnknownCommand(topic.to_string()))
+        }
+    }
+
+    /// Every possible completion of the last word of the input line: command names first, then argument values
+    pub fn completions(&self, config: &ConfigRegistry) -> Vec<String> {
+        let words: Vec<&str> = self.input.split_whitespace().collect();
+        let new_word = self.input.is_empty() || self.input.ends_with(char::is_whitespace);
+        let (position, prefix) = if new_word {
+            (words.len(), "")
+        } else {
+            (words.len() - 1, words[words.len() - 1])
+        };
+        let mut candidates: Vec<String> = if position == 0 {
+            self.command_names()
+                .into_iter()
+                .chain(config.keys())
+                .map(|name| name.to_string())
+                .collect()
+        } else {
+            self.argument_completions(words[0], position - 1, &words, config)
+        };
+        candidates.retain(|candidate| candidate.starts_with(prefix));
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    fn argument_completions(
+        &self,
+        name: &str,
+        idx: usize,
+        words: &[&str],
+        config: &ConfigRegistry,
+    ) -> Vec<String> {
+        let keys = || config.keys().map(|key| key.to_string()).collect();
+        let values_of = |key: &str| match config.default_value(key) {
+            Some(ConfigValue::Bool(_)) => bools(),
+            _ => vec![],
+        };
+        match (name, idx) {
+            ("help", 0) => self
+                .command_names()
+                .into_iter()
+                .chain(config.keys())
+                .map(|name| name.to_string())
+                .collect(),
+            ("cvars", 0) | ("get", 0) | ("set", 0) | ("reset", 0) => keys(),
+            ("set", 1) => words.get(1).map_or_else(Vec::new, |key| values_of(key)),
+            (name, idx) => match self.commands.get(name) {
+                Some(command) => match command.args().get(idx).map(|arg| arg.kind) {
+                    Some(ArgKind::CVar) => keys(),
+                    Some(ArgKind::Bool) => bools(),
+                    _ => vec![],
+                },
+                // A console variable's value
+                None if idx == 0 => values_of(name),
+                None => vec![],
+            },
+        }
+    }
+
+    /// Completes the last word of the input line as far as every possible completion agrees, and returns the completions.
+    ///
+    /// If there's more than one, they're also printed to the scrollback.
+    pub fn complete(&mut self, config: &ConfigRegistry) -> Vec<String> {
+        let candidates = self.completions(config);
+        let common = match candidates.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.as_str(), |common, candidate| {
+                let len = common
+                    .char_indices()
+                    .zip(candidate.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((idx, a), _)| idx + a.len_utf8());
+                &common[..len]
+            }),
+            None => return candidates,
+        };
+        let word_start = self
+            .input
+            .rfind(char::is_whitespace)
+            .map_or(0, |idx| idx + 1);
+        let mut input = self.input[..word_start].to_string();
+        input.push_str(common);
+        if candidates.len() == 1 {
+            input.push(' ');
+        } else {
+            self.print(&candidates.join("  "));
+        }
+        self.set_input(input);
+        candidates
+    }
+}
+
+fn default_commands() -> Vec<ConsoleCommand> {
+    vec![
+        ConsoleCommand::new("echo", "Prints its argument", |_, args| {
+            Ok(args.string(0).unwrap_or("").to_string())
+        })
+        .with_optional_arg("text", ArgKind::String),
+        ConsoleCommand::new(
+            "bus_stats",
+            "Shows how many events of each category went through the event bus, and how many subscribers each category has",
+            |ctx, _| {
+                let stats = ctx.event_bus().borrow().stats();
+                let mut categories: Vec<_> = stats
+                    .dispatched
+                    .keys()
+                    .chain(stats.subscribers.keys())
+                    .cloned()
+                    .collect();
+                categories.sort_by_key(|category| format!("{:?}", category));
+                categories.dedup();
+                let mut lines: Vec<String> = categories
+                    .iter()
+                    .map(|category| {
+                        format!(
+                            "{:?}: {} dispatched, {} subscriber(s)",
+                            category,
+                            stats.dispatched.get(category).unwrap_or(&0),
+                            stats.subscribers.get(category).unwrap_or(&0)
+                        )
+                    })
+                    .collect();
+                lines.push(format!("{} dispatch(es) stopped early", stats.stopped));
+                Ok(lines.join("\n"))
+            },
+        ),
+        ConsoleCommand::new("quit", "Exits the application", |ctx, _| {
+            ctx.request_exit();
+            Ok(String::new())
+        }),
+    ]
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|(builtin, _)| *builtin == name)
+}
+
+fn builtin_help(name: &str) -> &'static str {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map_or("", |(_, help)| help)
+}
+
+fn user_values(config: &ConfigRegistry) -> HashMap<String, ConfigValue> {
+    config
+        .layer_values(ConfigLayer::User)
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+}
+
+fn bools() -> Vec<String> {
+    vec![String::from("false"), String::from("true")]
+}
+
+fn describe_cvar(config: &ConfigRegistry, key: &str) -> Result<String, ConsoleError> {
+    let (value, default) = match (config.get(key), config.default_value(key)) {
+        (Some(value), Some(default)) => (value, default),
+        _ => return Err(ConsoleError::Config(unknown_key(key))),
+    };
+    let mut text = format!(
+        "{} = {} ({}, default {})",
+        key,
+        value,
+        default.type_name(),
+        default
+    );
+    if config.layer_value(ConfigLayer::CommandLine, key).is_some() {
+        text.push_str(" [set on the command line]");
+    }
+    if let Some(description) = config.description(key).filter(|desc| !desc.is_empty()) {
+        text.push_str(": ");
+        text.push_str(description);
+    }
+    Ok(text)
+}
+
+fn list_cvars(config: &ConfigRegistry, prefix: &str) -> String {
+    config
+        .keys()
+        .filter(|key| key.starts_with(prefix))
+        .map(|key| format!("{} = {}", key, config.get(key).expect("Key is registered")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn set_cvar(ctx: &mut AppContext, key: &str, text: &str) -> Result<String, ConsoleError> {
+    let value = match config_value_of(ctx, key)? {
+        ConfigValue::Bool(_) => {
+            parse_bool(text).map_or_else(|| text.to_string(), |b| b.to_string())
+        }
+        _ => text.to_string(),
+    };
+    let changed = ctx
+        .config()
+        .borrow_mut()
+        .set_from_str(ConfigLayer::User, key, &value)?;
+    if changed {
+        ctx.publish(ConfigEvent::Changed(key.to_string()).into());
+    }
+    describe_cvar(&ctx.config().borrow(), key)
+}
+
+fn reset_cvar(ctx: &mut AppContext, key: &str) -> Result<String, ConsoleError> {
+    config_value_of(ctx, key)?;
+    let changed = ctx.config().borrow_mut().unset(ConfigLayer::User, key);
+    if changed {
+        ctx.publish(ConfigEvent::Changed(key.to_string()).into());
+    }
+    describe_cvar(&ctx.config().borrow(), key)
+}
+
+fn config_value_of(ctx: &AppContext, key: &str) -> Result<ConfigValue, ConsoleError> {
+    ctx.config()
+        .borrow()
+        .get(key)
+        .cloned()
+        .ok_or_else(|| ConsoleError::Config(unknown_key(key)))
+}
+
+fn unknown_key(key: &str) -> ConfigError {
+    ConfigError::UnknownKey(key.to_string())
+}
@@ -0,0 +1,263 @@
+/*
+    ABSTRACT: Hooks a `Console` up to an application as a `System`. In a windowed application, the console
+    is opened with the backquote key, which pushes its input context, and is typed into through text input events.
+    Nothing draws the console yet, so its context doesn't take keys away from gameplay, and the lines it prints
+    go to the log. In a headless application, lines are read from stdin instead.
+*/
+use crate::console::shell::Console;
+use crate::input::{
+    context::{ActionEvent, ActionState, ConsumePolicy, InputContext, InputTrigger},
+    keyboard::KeyboardEvent,
+    layout::PhysicalKey,
+};
+use crate::messaging::{
+    bus::BusRequest,
+    event::{ThermiteEvent, ThermiteEventType},
+    subscribe::Subscriber,
+};
+use crate::platform::app::{AppContext, System};
+use log::info;
+use std::{
+    cell::RefCell,
+    io::{self, BufRead},
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// The input context pushed while the console is open. It consumes nothing, as the console isn't drawn (see the module's documentation).
+pub const CONSOLE_CONTEXT: &str = "console";
+/// The input context which opens the console, always on the stack (below everything pushed after the console system's `on_init`)
+pub const CONSOLE_TOGGLE_CONTEXT: &str = "console_toggle";
+
+const TOGGLE: &str = "toggle";
+const CLOSE: &str = "close";
+const SUBMIT: &str = "submit";
+const BACKSPACE: &str = "backspace";
+const COMPLETE: &str = "complete";
+const HISTORY_PREVIOUS: &str = "history_previous";
+const HISTORY_NEXT: &str = "history_next";
+
+/// Console events, published on the bus under `ThermiteEventType::Console`
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum ConsoleEvent {
+    Opened,
+    Closed,
+    /// A registered command ran successfully, e.g. for systems which own what the command acts on
+    CommandExecuted {
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+impl From<ConsoleEvent> for ThermiteEvent {
+    fn from(console_evt: ConsoleEvent) -> Self {
+        ThermiteEvent::Console(console_evt)
+    }
+}
+
+/// Collects the console's input from the bus, until the `ConsoleSystem` handles it during its update
+#[derive(Default)]
+struct ConsoleInput {
+    events: RefCell<Vec<ThermiteEvent>>,
+}
+
+impl Subscriber<ThermiteEventType, ThermiteEvent> for ConsoleInput {
+    fn on_event(&self, event: &ThermiteEvent) -> BusRequest {
+        let wanted = match event {
+            ThermiteEvent::Action(ActionEvent { context, .. }) => {
+                context == CONSOLE_CONTEXT || context == CONSOLE_TOGGLE_CONTEXT
+            }
+            ThermiteEvent::Keyboard(KeyboardEvent::Character(_)) => true,
+            _ => false,
+        };
+        if wanted {
+            self.events.borrow_mut().push(event.clone());
+        }
+        BusRequest::NoActionNeeded
+    }
+}
+
+enum ConsoleSource {
+    Window { input: Rc<ConsoleInput>, open: bool },
+    Stdin(Receiver<String>),
+}
+
+/// Runs a `Console` within an application
+pub struct ConsoleSystem {
+    console: Console,
+    source: ConsoleSource,
+}
+
+impl ConsoleSystem {
+    /// A console opened and closed with the backquote key, typed into with text input
+    pub fn new(console: Console) -> Self {
+        Self {
+            console,
+            source: ConsoleSource::Window {
+                input: Rc::new(ConsoleInput::default()),
+                open: false,
+            },
+        }
+    }
+
+    /// A console which executes every line read from stdin and prints the result to stdout, e.g. for dedicated servers
+    pub fn headless(console: Console) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        // The thread blocks on stdin until the process exits, so it's never joined
+        thread::Builder::new()
+            .name(String::from("thermite-console-stdin"))
+            .spawn(move || {
+                for line in io::stdin().lock().lines() {
+                    let sent = line.map(|line| sender.send(line).is_ok());
+                    if !sent.unwrap_or(false) {
+                        break;
+                    }
+                }
+            })
+            .expect("Couldn't spawn the console's stdin thread");
+        Self {
+            console,
+            source: ConsoleSource::Stdin(receiver),
+        }
+    }
+
+    pub fn console(&self) -> &Console {
+        &self.console
+    }
+
+    pub fn console_mut(&mut self) -> &mut Console {
+        &mut self.console
+    }
+
+    pub fn is_open(&self) -> bool {
+        match &self.source {
+            ConsoleSource::Window { open, .. } => *open,
+            ConsoleSource::Stdin(_) => true,
+        }
+    }
+
+    fn set_open(&mut self, ctx: &mut AppContext, should_open: bool) {
+        match &mut self.source {
+            ConsoleSource::Window { open, .. } if *open != should_open => *open = should_open,
+            _ => return,
+        }
+        {
+            let mut bus = ctx
+                .event_bus()
+                .try_borrow_mut()
+                .expect("Couldn't borrow the event bus as mutable");
+            let mut contexts = ctx
+                .input_contexts()
+                .try_borrow_mut()
+                .expect("Couldn't borrow the input context stack as mutable");
+            if should_open {
+                contexts.push(console_context(), &mut bus);
+            } else {
+                contexts.remove(CONSOLE_CONTEXT, &mut bus);
+            }
+        }
+        let evt = if should_open {
+            ConsoleEvent::Opened
+        } else {
+            ConsoleEvent::Closed
+        };
+        ctx.publish(evt.into());
+    }
+
+    fn handle_window_input(&mut self, ctx: &mut AppContext, events: Vec<ThermiteEvent>) {
+        // The key which opens the console usually types a character too, which mustn't end up in the input line
+        let mut just_opened = false;
+        for event in events {
+            match event {
+                ThermiteEvent::Action(ActionEvent {
+                    action,
+                    state: ActionState::Pressed,
+                    ..
+                }) => match action.as_str() {
+                    TOGGLE => {
+                        let open = !self.is_open();
+                        self.set_open(ctx, open);
+                        just_opened = open;
+                    }
+                    CLOSE => self.set_open(ctx, false),
+                    SUBMIT => {
+                        for line in self.console.submit(ctx) {
+                            info!("{}", line);
+                        }
+                    }
+                    BACKSPACE => self.console.pop_char(),
+                    COMPLETE => {
+                        let config = ctx.config().clone();
+                        self.console.complete(&config.borrow());
+                    }
+                    HISTORY_PREVIOUS => self.console.history_previous(),
+                    HISTORY_NEXT => self.console.history_next(),
+                    _ => (),
+                },
+                ThermiteEvent::Keyboard(KeyboardEvent::Character(character)) => {
+                    if just_opened {
+                        just_opened = false;
+                    } else if self.is_open() {
+                        self.console.push_char(character);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl System for ConsoleSystem {
+    fn on_init(&mut self, ctx: &mut AppContext) {
+        if let ConsoleSource::Window { input, .. } = &self.source {
+            let mut bus = ctx
+                .event_bus()
+                .try_borrow_mut()
+                .expect("Couldn't borrow the event bus as mutable");
+            bus.subscribe(input, ThermiteEventType::Input);
+            let mut toggle = InputContext::new(CONSOLE_TOGGLE_CONTEXT, ConsumePolicy::ConsumeBound);
+            toggle.bind(InputTrigger::Key(PhysicalKey::Backquote), TOGGLE);
+            ctx.input_contexts()
+                .try_borrow_mut()
+                .expect("Couldn't borrow the input context stack as mutable")
+                .push(toggle, &mut bus);
+        }
+    }
+
+    fn on_update(&mut self, ctx: &mut AppContext, _delta_sec: f32) {
+        match &self.source {
+            ConsoleSource::Window { input, .. } => {
+                let events = std::mem::take(&mut *input.events.borrow_mut());
+                self.handle_window_input(ctx, events);
+            }
+            ConsoleSource::Stdin(receiver) => {
+                let lines: Vec<String> = receiver.try_iter().collect();
+                for line in lines {
+                    // The first line echoes the command, which is already on the terminal
+                    for output in self.console.execute(&line, ctx).iter().skip(1) {
+                        println!("{}", output);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn console_context() -> InputContext {
+    let mut context = InputContext::new(CONSOLE_CONTEXT, ConsumePolicy::PassThrough);
+    // The backquote key still reaches the toggle context, which closes the console
+    let bindings = [
+        (PhysicalKey::Escape, CLOSE),
+        (PhysicalKey::Enter, SUBMIT),
+        (PhysicalKey::NumpadEnter, SUBMIT),
+        (PhysicalKey::Backspace, BACKSPACE),
+        (PhysicalKey::Tab, COMPLETE),
+        (PhysicalKey::ArrowUp, HISTORY_PREVIOUS),
+        (PhysicalKey::ArrowDown, HISTORY_NEXT),
+    ];
+    for (key, action) in bindings.iter() {
+        context.bind(InputTrigger::Key(*key), *action);
+    }
+    context
+}
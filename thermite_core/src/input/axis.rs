@@ -0,0 +1,490 @@
+/*
+    ABSTRACT: Virtual input axes, which combine digital keys, mouse movement and analog sticks
+    into normalized values so that gameplay code can read a single value regardless of the device.
+*/
+use crate::input::{keyboard::KeyboardEvent, mouse::MouseEvent};
+use crate::messaging::event::ThermiteEvent;
+use std::collections::{HashMap, HashSet};
+use winit::event::VirtualKeyCode;
+
+/// Filters out noise around the resting position of an axis.
+///
+/// Magnitudes below `inner` read as `0.0`, magnitudes above `outer` read as `1.0`, and everything in between is rescaled to fill `0.0..=1.0`.
+/// Virtual axes only apply it to absolute sources (keys, sticks): mouse movement isn't bounded, so it would saturate.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum DeadZone {
+    #[default]
+    None,
+    /// Each component of the axis is filtered independently.
+    Axial { inner: f32, outer: f32 },
+    /// The magnitude of the whole 2D vector is filtered, preserving its direction. 1D axes treat this the same as `Axial`.
+    Radial { inner: f32, outer: f32 },
+}
+
+impl DeadZone {
+    /// Applies this dead zone to a single 1D value
+    pub fn apply(&self, value: f32) -> f32 {
+        match *self {
+            DeadZone::None => value,
+            DeadZone::Axial { inner, outer } | DeadZone::Radial { inner, outer } => {
+                rescale(value.abs(), inner, outer) * value.signum()
+            }
+        }
+    }
+
+    /// Applies this dead zone to a 2D vector
+    pub fn apply_2d(&self, value: [f32; 2]) -> [f32; 2] {
+        match *self {
+            DeadZone::None => value,
+            DeadZone::Axial { .. } => [self.apply(value[0]), self.apply(value[1])],
+            DeadZone::Radial { inner, outer } => {
+                let magnitude = (value[0] * value[0] + value[1] * value[1]).sqrt();
+                if magnitude <= f32::EPSILON {
+                    return [0.0, 0.0];
+                }
+                // Scale the direction by the rescaled magnitude so that the vector keeps pointing the same way
+                let scale = rescale(magnitude, inner, outer) / magnitude;
+                [value[0] * scale, value[1] * scale]
+            }
+        }
+    }
+}
+
+/// Maps `magnitude` from `inner..=outer` into `0.0..=1.0`
+fn rescale(magnitude: f32, inner: f32, outer: f32) -> f32 {
+    if magnitude <= inner {
+        0.0
+    } else if magnitude >= outer || outer <= inner {
+        1.0
+    } else {
+        (magnitude - inner) / (outer - inner)
+    }
+}
+
+/// Shapes the response of an axis after its dead zone has been applied.
+///
+/// Curves operate on the magnitude of the value and preserve its sign, so `Quadratic` turns `-0.5` into `-0.25`.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    Quadratic,
+    Cubic,
+    /// Raises the magnitude to the given exponent
+    Power(f32),
+    /// A user-provided curve, which receives and should return a magnitude in `0.0..=1.0`
+    Custom(fn(f32) -> f32),
+}
+
+impl ResponseCurve {
+    /// Applies this curve to the given value
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = value.abs();
+        let shaped = match *self {
+            ResponseCurve::Linear => magnitude,
+            ResponseCurve::Quadratic => magnitude * magnitude,
+            ResponseCurve::Cubic => magnitude * magnitude * magnitude,
+            ResponseCurve::Power(exponent) => magnitude.powf(exponent),
+            ResponseCurve::Custom(curve) => curve(magnitude),
+        };
+        shaped * value.signum()
+    }
+}
+
+/// Processing settings for a `VirtualAxis`, applied in the order: dead zone (absolute sources only), curve, sensitivity, inversion, smoothing.
+#[derive(Debug, Clone, Copy)]
+pub struct AxisSettings {
+    pub dead_zone: DeadZone,
+    pub curve: ResponseCurve,
+    pub sensitivity: f32,
+    pub inverted: bool,
+    /// Time constant (in seconds) of the exponential smoothing applied to the final value. `0.0` disables smoothing.
+    pub smoothing: f32,
+}
+
+impl Default for AxisSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: DeadZone::None,
+            curve: ResponseCurve::Linear,
+            sensitivity: 1.0,
+            inverted: false,
+            smoothing: 0.0,
+        }
+    }
+}
+
+impl AxisSettings {
+    /// Applies curve, sensitivity and inversion to an already dead-zoned value
+    fn shape(&self, value: f32) -> f32 {
+        let value = self.curve.apply(value) * self.sensitivity;
+        if self.inverted {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+/// A physical input which can drive a `VirtualAxis`
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum AxisSource {
+    /// A pair of digital keys, reading `-1.0` while `negative` is held and `1.0` while `positive` is held
+    Keys {
+        negative: VirtualKeyCode,
+        positive: VirtualKeyCode,
+    },
+    /// Horizontal raw mouse movement accumulated over the last frame (not clamped to `-1.0..=1.0`)
+    MouseX,
+    /// Vertical raw mouse movement accumulated over the last frame (not clamped to `-1.0..=1.0`)
+    MouseY,
+    /// Vertical mouse wheel movement accumulated over the last frame
+    MouseWheel,
+    /// An analog device axis (gamepad stick, trigger, etc.), identified by its device axis ID
+    Analog(u32),
+}
+
+impl AxisSource {
+    /// Whether this source reports relative movement rather than an absolute position
+    fn is_relative(&self) -> bool {
+        match self {
+            AxisSource::MouseX | AxisSource::MouseY | AxisSource::MouseWheel => true,
+            AxisSource::Keys { .. } | AxisSource::Analog(_) => false,
+        }
+    }
+}
+
+/// The raw state of every device that can feed an axis
+#[derive(Debug, Default)]
+struct RawAxisState {
+    held_keys: HashSet<VirtualKeyCode>,
+    mouse_delta: [f32; 2],
+    wheel_delta: f32,
+    analog: HashMap<u32, f32>,
+}
+
+impl RawAxisState {
+    fn read(&self, source: &AxisSource) -> f32 {
+        match source {
+            AxisSource::Keys { negative, positive } => {
+                let mut value = 0.0;
+                if self.held_keys.contains(negative) {
+                    value -= 1.0;
+                }
+                if self.held_keys.contains(positive) {
+                    value += 1.0;
+                }
+                value
+            }
+            AxisSource::MouseX => self.mouse_delta[0],
+            AxisSource::MouseY => self.mouse_delta[1],
+            AxisSource::MouseWheel => self.wheel_delta,
+            AxisSource::Analog(id) => self.analog.get(id).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Reads every given source, and keeps the one with the largest magnitude so a held key and a resting stick don't cancel out
+    fn read_strongest(&self, sources: &[AxisSource]) -> (f32, bool) {
+        sources
+            .iter()
+            .map(|source| (self.read(source), source.is_relative()))
+            .fold((0.0, false), |strongest, candidate| {
+                if candidate.0.abs() > strongest.0.abs() {
+                    candidate
+                } else {
+                    strongest
+                }
+            })
+    }
+}
+
+/// Moves `current` towards `target` with an exponential falloff of the given time constant
+fn smooth(current: f32, target: f32, time_constant: f32, dt: f32) -> f32 {
+    if time_constant <= 0.0 || dt <= 0.0 {
+        return target;
+    }
+    current + (target - current) * (1.0 - (-dt / time_constant).exp())
+}
+
+/// A one-dimensional virtual axis, fed by one or more `AxisSource`s
+#[derive(Debug, Clone)]
+pub struct VirtualAxis {
+    pub sources: Vec<AxisSource>,
+    pub settings: AxisSettings,
+    value: f32,
+}
+
+impl VirtualAxis {
+    /// Creates a new `VirtualAxis` reading from the given sources with default settings
+    pub fn new(sources: Vec<AxisSource>) -> Self {
+        Self::with_settings(sources, AxisSettings::default())
+    }
+
+    /// Creates a new `VirtualAxis` reading from the given sources with the given settings
+    pub fn with_settings(sources: Vec<AxisSource>, settings: AxisSettings) -> Self {
+        Self {
+            sources,
+            settings,
+            value: 0.0,
+        }
+    }
+
+    /// The current processed value of this axis
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    fn update(&mut self, raw: &RawAxisState, dt: f32) {
+        let (raw_value, relative) = raw.read_strongest(&self.sources);
+        let value = if relative {
+            raw_value
+        } else {
+            self.settings.dead_zone.apply(raw_value)
+        };
+        let mut target = self.settings.shape(value);
+        if !relative {
+            target = target.clamp(-1.0, 1.0);
+        }
+        self.value = smooth(self.value, target, self.settings.smoothing, dt);
+    }
+}
+
+/// A two-dimensional virtual axis, such as WASD movement or a gamepad stick.
+///
+/// The dead zone in `settings` is applied to the combined vector of absolute sources, so `DeadZone::Radial` behaves as expected for sticks.
+/// Absolute sources (keys, sticks) are clamped to the unit circle, which keeps diagonal key movement from being faster.
+#[derive(Debug, Clone)]
+pub struct VirtualAxis2D {
+    pub x_sources: Vec<AxisSource>,
+    pub y_sources: Vec<AxisSource>,
+    pub settings: AxisSettings,
+    /// Inverts only the Y component (as is common for camera look controls), on top of `settings.inverted`
+    pub invert_y: bool,
+    value: [f32; 2],
+}
+
+impl VirtualAxis2D {
+    /// Creates a new `VirtualAxis2D` reading its components from the given sources with default settings
+    pub fn new(x_sources: Vec<AxisSource>, y_sources: Vec<AxisSource>) -> Self {
+        Self::with_settings(x_sources, y_sources, AxisSettings::default())
+    }
+
+    /// Creates a new `VirtualAxis2D` reading its components from the given sources with the given settings
+    pub fn with_settings(
+        x_sources: Vec<AxisSource>,
+        y_sources: Vec<AxisSource>,
+        settings: AxisSettings,
+    ) -> Self {
+        Self {
+            x_sources,
+            y_sources,
+            settings,
+            invert_y: false,
+            value: [0.0, 0.0],
+        }
+    }
+
+    /// Convenience constructor for the classic WASD (or arrow key, etc.) movement vector, where "up" is positive Y
+    pub fn from_keys(
+        up: VirtualKeyCode,
+        left: VirtualKeyCode,
+        down: VirtualKeyCode,
+        right: VirtualKeyCode,
+    ) -> Self {
+        Self::new(
+            vec![AxisSource::Keys {
+                negative: left,
+                positive: right,
+            }],
+            vec![AxisSource::Keys {
+                negative: down,
+                positive: up,
+            }],
+        )
+    }
+
+    /// The current processed value of this axis
+    pub fn value(&self) -> [f32; 2] {
+        self.value
+    }
+
+    fn update(&mut self, raw: &RawAxisState, dt: f32) {
+        let (raw_x, relative_x) = raw.read_strongest(&self.x_sources);
+        let (raw_y, relative_y) = raw.read_strongest(&self.y_sources);
+        let dead_zone = self.settings.dead_zone;
+        let [x, y] = match (relative_x, relative_y) {
+            (false, false) => dead_zone.apply_2d([raw_x, raw_y]),
+            (false, true) => [dead_zone.apply(raw_x), raw_y],
+            (true, false) => [raw_x, dead_zone.apply(raw_y)],
+            (true, true) => [raw_x, raw_y],
+        };
+        let mut target = [self.settings.shape(x), self.settings.shape(y)];
+        if self.invert_y {
+            target[1] = -target[1];
+        }
+        if !relative_x && !relative_y {
+            let magnitude = (target[0] * target[0] + target[1] * target[1]).sqrt();
+            if magnitude > 1.0 {
+                target = [target[0] / magnitude, target[1] / magnitude];
+            }
+        }
+        self.value = [
+            smooth(self.value[0], target[0], self.settings.smoothing, dt),
+            smooth(self.value[1], target[1], self.settings.smoothing, dt),
+        ];
+    }
+}
+
+/// A named collection of virtual axes, and the raw device state that drives them.
+///
+/// Feed it input with `handle_event` (or the `set_*`/`add_*` methods for devices that don't publish events yet),
+/// call `update` once per frame, then read values by name.
+#[derive(Debug, Default)]
+pub struct InputAxes {
+    raw: RawAxisState,
+    axes: HashMap<String, VirtualAxis>,
+    axes_2d: HashMap<String, VirtualAxis2D>,
+}
+
+impl InputAxes {
+    /// Binds the given 1D axis under `name`, replacing any axis previously bound to it
+    pub fn bind_axis<N: Into<String>>(&mut self, name: N, axis: VirtualAxis) {
+        self.axes.insert(name.into(), axis);
+    }
+
+    /// Binds the given 2D axis under `name`, replacing any axis previously bound to it
+    pub fn bind_axis_2d<N: Into<String>>(&mut self, name: N, axis: VirtualAxis2D) {
+        self.axes_2d.insert(name.into(), axis);
+    }
+
+    /// Removes the 1D and/or 2D axis bound under `name`
+    pub fn unbind(&mut self, name: &str) {
+        self.axes.remove(name);
+        self.axes_2d.remove(name);
+    }
+
+    /// Mutable access to the 1D axis bound under `name`, for changing its sources or settings at runtime
+    pub fn axis_mut(&mut self, name: &str) -> Option<&mut VirtualAxis> {
+        self.axes.get_mut(name)
+    }
+
+    /// Mutable access to the 2D axis bound under `name`, for changing its sources or settings at runtime
+    pub fn axis_2d_mut(&mut self, name: &str) -> Option<&mut VirtualAxis2D> {
+        self.axes_2d.get_mut(name)
+    }
+
+    /// The current value of the 1D axis bound under `name`, or `0.0` if there is none
+    pub fn axis(&self, name: &str) -> f32 {
+        self.axes.get(name).map_or(0.0, |axis| axis.value())
+    }
+
+    /// The current value of the 2D axis bound under `name`, or `[0.0, 0.0]` if there is none
+    pub fn axis_2d(&self, name: &str) -> [f32; 2] {
+        self.axes_2d
+            .get(name)
+            .map_or([0.0, 0.0], |axis| axis.value())
+    }
+
+    /// Updates the raw device state from an input event
+    pub fn handle_event(&mut self, event: &ThermiteEvent) {
+        match event {
+            ThermiteEvent::Keyboard(KeyboardEvent::KeyPressed(key_code)) => {
+                if let Some(key) = key_code.virtual_keycode() {
+                    self.raw.held_keys.insert(key);
+                }
+            }
+            ThermiteEvent::Keyboard(KeyboardEvent::KeyReleased(key_code)) => {
+                if let Some(key) = key_code.virtual_keycode() {
+                    self.raw.held_keys.remove(&key);
+                }
+            }
+            ThermiteEvent::Mouse(MouseEvent::RawMotion(delta)) => {
+                self.add_mouse_delta(delta.x() as f32, delta.y() as f32)
+            }
+            ThermiteEvent::Mouse(MouseEvent::Scroll(delta)) => {
+                self.raw.wheel_delta += delta.y() as f32
+            }
+            _ => (),
+        }
+    }
+
+    /// Accumulates raw mouse movement for this frame
+    pub fn add_mouse_delta(&mut self, dx: f32, dy: f32) {
+        self.raw.mouse_delta[0] += dx;
+        self.raw.mouse_delta[1] += dy;
+    }
+
+    /// Sets the current position of an analog device axis, expected to be in `-1.0..=1.0`
+    pub fn set_analog(&mut self, axis_id: u32, value: f32) {
+        self.raw.analog.insert(axis_id, value);
+    }
+
+    /// Releases every held key and recenters every analog axis, e.g. when the window loses focus
+    pub fn reset(&mut self) {
+        self.raw = RawAxisState::default();
+    }
+
+    /// Recomputes every axis from the current device state, then clears the per-frame relative (mouse) movement.
+    ///
+    /// `dt` is the frame time in seconds, used for smoothing.
+    pub fn update(&mut self, dt: f32) {
+        for axis in self.axes.values_mut() {
+            axis.update(&self.raw, dt);
+        }
+        for axis in self.axes_2d.values_mut() {
+            axis.update(&self.raw, dt);
+        }
+        self.raw.mouse_delta = [0.0, 0.0];
+        self.raw.wheel_delta = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_zones_only_filter_absolute_sources() {
+        let settings = AxisSettings {
+            dead_zone: DeadZone::Radial {
+                inner: 0.2,
+                outer: 0.9,
+            },
+            sensitivity: 2.0,
+            ..AxisSettings::default()
+        };
+        let mut axes = InputAxes::default();
+        axes.bind_axis_2d(
+            "look",
+            VirtualAxis2D::with_settings(
+                vec![AxisSource::MouseX],
+                vec![AxisSource::MouseY],
+                settings,
+            ),
+        );
+        axes.bind_axis_2d(
+            "move",
+            VirtualAxis2D::with_settings(
+                vec![AxisSource::Analog(0)],
+                vec![AxisSource::Analog(1)],
+                settings,
+            ),
+        );
+        axes.bind_axis(
+            "turn",
+            VirtualAxis::with_settings(vec![AxisSource::MouseX], settings),
+        );
+        axes.add_mouse_delta(30.0, -0.1);
+        axes.set_analog(0, 0.1);
+        axes.set_analog(1, 0.0);
+        axes.update(0.016);
+        // Mouse movement keeps its magnitude, small movements included
+        assert_eq!(axes.axis_2d("look"), [60.0, -0.2]);
+        assert_eq!(axes.axis("turn"), 60.0);
+        // A resting stick reads as centered, and a pushed one saturates
+        assert_eq!(axes.axis_2d("move"), [0.0, 0.0]);
+        axes.set_analog(0, 1.0);
+        axes.update(0.016);
+        assert_eq!(axes.axis_2d("move"), [1.0, 0.0]);
+    }
+}
@@ -0,0 +1,257 @@
+/*
+    ABSTRACT: A stack of input contexts (menu, gameplay, console, etc.), each with its own bindings,
+    which routes input to the topmost context first and lets it consume events before lower contexts,
+    or the rest of the event bus, ever see them.
+*/
+use crate::input::{keyboard::KeyboardEvent, layout::PhysicalKey, mouse::MouseEvent};
+use crate::messaging::{
+    bus::{EventBus, EventDispatchResult},
+    event::{ThermiteEvent, ThermiteEventType},
+    publish::Publisher,
+};
+use std::collections::HashMap;
+use winit::event::MouseButton;
+
+/// A physical input which can be bound to an action within an `InputContext`
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum InputTrigger {
+    Key(PhysicalKey),
+    MouseButton(MouseButton),
+}
+
+/// Whether an action's trigger was pressed or released
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum ActionState {
+    Pressed,
+    Released,
+}
+
+/// Published on the bus when a trigger bound in an `InputContext` is pressed or released
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct ActionEvent {
+    pub context: String,
+    pub action: String,
+    pub state: ActionState,
+}
+
+impl From<ActionEvent> for ThermiteEvent {
+    fn from(action_evt: ActionEvent) -> Self {
+        ThermiteEvent::Action(action_evt)
+    }
+}
+
+/// Published on the bus whenever the `InputContextStack` changes
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum InputContextEvent {
+    Pushed(String),
+    Popped(String),
+}
+
+impl From<InputContextEvent> for ThermiteEvent {
+    fn from(ctx_evt: InputContextEvent) -> Self {
+        ThermiteEvent::InputContext(ctx_evt)
+    }
+}
+
+/// How much of the input reaching an `InputContext` it keeps from the contexts below it
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum ConsumePolicy {
+    /// Never consume anything, bound actions still fire (e.g. a debug overlay)
+    PassThrough,
+    /// Consume only the input which triggered one of this context's bindings (e.g. gameplay)
+    ConsumeBound,
+    /// Consume all input, bound or not (e.g. a modal menu or a text console)
+    ConsumeAll,
+}
+
+/// A named set of bindings from `InputTrigger`s to actions
+#[derive(Debug, Clone)]
+pub struct InputContext {
+    name: String,
+    policy: ConsumePolicy,
+    bindings: HashMap<InputTrigger, String>,
+}
+
+impl InputContext {
+    /// Creates a new `InputContext` with no bindings
+    pub fn new<N: Into<String>>(name: N, policy: ConsumePolicy) -> Self {
+        Self {
+            name: name.into(),
+            policy,
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// The name of this context, which is also reported in its `ActionEvent`s
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The `ConsumePolicy` of this context
+    pub fn policy(&self) -> ConsumePolicy {
+        self.policy
+    }
+
+    /// Changes the `ConsumePolicy` of this context
+    pub fn set_policy(&mut self, policy: ConsumePolicy) {
+        self.policy = policy;
+    }
+
+    /// Binds `trigger` to `action`, returning the action it was previously bound to, if any
+    pub fn bind<A: Into<String>>(&mut self, trigger: InputTrigger, action: A) -> Option<String> {
+        self.bindings.insert(trigger, action.into())
+    }
+
+    /// Removes the binding for `trigger`, returning the action it was bound to, if any
+    pub fn unbind(&mut self, trigger: &InputTrigger) -> Option<String> {
+        self.bindings.remove(trigger)
+    }
+
+    /// The action bound to `trigger` in this context, if any
+    pub fn action_for(&self, trigger: &InputTrigger) -> Option<&str> {
+        self.bindings.get(trigger).map(|action| action.as_str())
+    }
+
+    /// Iterates over every (trigger, action) binding in this context
+    pub fn bindings(&self) -> impl Iterator<Item = (&InputTrigger, &str)> {
+        self.bindings
+            .iter()
+            .map(|(trigger, action)| (trigger, action.as_str()))
+    }
+}
+
+/// Extracts the trigger and its state from an input event, if it is a button-like event
+pub(crate) fn trigger_of(event: &ThermiteEvent) -> Option<(InputTrigger, ActionState)> {
+    match event {
+        ThermiteEvent::Keyboard(KeyboardEvent::KeyPressed(key)) => key
+            .physical_key()
+            .map(|physical| (InputTrigger::Key(physical), ActionState::Pressed)),
+        ThermiteEvent::Keyboard(KeyboardEvent::KeyReleased(key)) => key
+            .physical_key()
+            .map(|physical| (InputTrigger::Key(physical), ActionState::Released)),
+        ThermiteEvent::Mouse(MouseEvent::ButtonPressed(button)) => {
+            Some((InputTrigger::MouseButton(*button), ActionState::Pressed))
+        }
+        ThermiteEvent::Mouse(MouseEvent::ButtonReleased(button)) => {
+            Some((InputTrigger::MouseButton(*button), ActionState::Released))
+        }
+        _ => None,
+    }
+}
+
+/// A stack of `InputContext`s. Input is offered to the topmost context first, working down the stack until it is consumed.
+///
+/// Route raw input through `route` before publishing it on the bus, and only publish it if it wasn't consumed.
+/// This way, subscribers to raw input only ever see what no context claimed.
+#[derive(Debug, Default)]
+pub struct InputContextStack {
+    contexts: Vec<InputContext>,
+}
+
+impl Publisher<ThermiteEventType, ThermiteEvent> for InputContextStack {}
+
+impl InputContextStack {
+    /// Pushes a context on top of the stack, and publishes `InputContextEvent::Pushed`
+    pub fn push(
+        &mut self,
+        context: InputContext,
+        bus: &mut EventBus<ThermiteEventType, ThermiteEvent>,
+    ) {
+        let evt = InputContextEvent::Pushed(context.name.clone());
+        self.contexts.push(context);
+        self.publish_event(&evt.into(), bus);
+    }
+
+    /// Pops the topmost context off the stack, and publishes `InputContextEvent::Popped` if there was one
+    pub fn pop(
+        &mut self,
+        bus: &mut EventBus<ThermiteEventType, ThermiteEvent>,
+    ) -> Option<InputContext> {
+        let context = self.contexts.pop()?;
+        let evt = InputContextEvent::Popped(context.name.clone());
+        self.publish_event(&evt.into(), bus);
+        Some(context)
+    }
+
+    /// Removes the topmost context with the given name, wherever it is in the stack, and publishes `InputContextEvent::Popped`
+    pub fn remove(
+        &mut self,
+        name: &str,
+        bus: &mut EventBus<ThermiteEventType, ThermiteEvent>,
+    ) -> Option<InputContext> {
+        let idx = self.contexts.iter().rposition(|ctx| ctx.name == name)?;
+        let context = self.contexts.remove(idx);
+        let evt = InputContextEvent::Popped(context.name.clone());
+        self.publish_event(&evt.into(), bus);
+        Some(context)
+    }
+
+    /// The topmost (focused) context, if any
+    pub fn top(&self) -> Option<&InputContext> {
+        self.contexts.last()
+    }
+
+    /// Whether a context with the given name is on the stack
+    pub fn contains(&self, name: &str) -> bool {
+        self.contexts.iter().any(|ctx| ctx.name == name)
+    }
+
+    /// Mutable access to the topmost context with the given name, for rebinding at runtime
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut InputContext> {
+        self.contexts.iter_mut().rev().find(|ctx| ctx.name == name)
+    }
+
+    /// Iterates over the contexts from the top of the stack down
+    pub fn iter(&self) -> impl Iterator<Item = &InputContext> {
+        self.contexts.iter().rev()
+    }
+
+    /// Offers an input event to each context from the top of the stack down, publishing an `ActionEvent` for every
+    /// binding it triggers along the way.
+    ///
+    /// Returns `EventDispatchResult::Stopped` if a context consumed the event, in which case it should not be published.
+    ///
+    /// **NOTE:** Releases (and modifier changes) are never consumed, so that keys held while a context is pushed
+    /// don't get stuck down for the subscribers below it. Text input is never consumed either, so that text fields
+    /// and consoles can read it from the bus while their context consumes every key.
+    pub fn route(
+        &self,
+        event: &ThermiteEvent,
+        bus: &mut EventBus<ThermiteEventType, ThermiteEvent>,
+    ) -> EventDispatchResult {
+        let trigger = trigger_of(event);
+        let consumable = match event {
+            ThermiteEvent::Keyboard(KeyboardEvent::ModifiersChanged(_))
+            | ThermiteEvent::Keyboard(KeyboardEvent::Character(_)) => false,
+            _ => !matches!(trigger, Some((_, ActionState::Released))),
+        };
+        for context in self.contexts.iter().rev() {
+            let action = trigger.and_then(|(trigger, state)| {
+                context
+                    .action_for(&trigger)
+                    .map(|action| (action.to_string(), state))
+            });
+            let bound = action.is_some();
+            if let Some((action, state)) = action {
+                let evt = ActionEvent {
+                    context: context.name.clone(),
+                    action,
+                    state,
+                };
+                self.publish_event(&evt.into(), bus);
+            }
+            let consumed = match context.policy {
+                ConsumePolicy::PassThrough => false,
+                ConsumePolicy::ConsumeBound => bound,
+                ConsumePolicy::ConsumeAll => true,
+            };
+            if consumed && consumable {
+                return EventDispatchResult::Stopped;
+            } else if consumed && bound {
+                // Releases stop at the first context that bound them so the action doesn't fire twice, but still reach the bus
+                return EventDispatchResult::Finished;
+            }
+        }
+        EventDispatchResult::Finished
+    }
+}
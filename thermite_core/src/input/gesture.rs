@@ -0,0 +1,226 @@
+/*
+    ABSTRACT: Gesture recognition over keys and mouse buttons: taps, double taps and holds. Recognition only depends
+    on the instants it's given, read from the application's `Clock` (see `AppContext::route_input`), so it can be
+    driven by a `ManualClock` in tests and replays.
+*/
+use crate::input::context::{trigger_of, ActionState, InputTrigger};
+use crate::messaging::event::ThermiteEvent;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// A gesture recognized on an `InputTrigger`
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum Gesture {
+    /// Pressed and released before it counted as a hold
+    Tap,
+    /// A second tap soon enough after the first one. The first tap was already reported as a `Tap`.
+    DoubleTap,
+    /// Held down long enough, reported once while still held (its release isn't a tap)
+    Hold,
+}
+
+/// Published on the bus under `ThermiteEventType::Input` when a gesture is recognized
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub struct GestureEvent {
+    pub trigger: InputTrigger,
+    pub gesture: Gesture,
+}
+
+impl From<GestureEvent> for ThermiteEvent {
+    fn from(gesture_evt: GestureEvent) -> Self {
+        ThermiteEvent::Gesture(gesture_evt)
+    }
+}
+
+/// How long gestures take
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct GestureSettings {
+    /// Longest time between the release of a tap and the release of the next for them to make a double tap
+    pub double_tap_interval: Duration,
+    /// How long a trigger has to be held down to make a hold
+    pub hold_duration: Duration,
+}
+
+impl Default for GestureSettings {
+    /// 300ms between double taps, 500ms holds
+    fn default() -> Self {
+        Self {
+            double_tap_interval: Duration::from_millis(300),
+            hold_duration: Duration::from_millis(500),
+        }
+    }
+}
+
+struct Press {
+    at: Instant,
+    held: bool,
+}
+
+/// Recognizes `Gesture`s from input events, and the passing of time (see `update`)
+#[derive(Default)]
+pub struct GestureRecognizer {
+    settings: GestureSettings,
+    pressed: HashMap<InputTrigger, Press>,
+    /// When each trigger's last tap (which didn't complete a double tap) was released
+    last_taps: HashMap<InputTrigger, Instant>,
+}
+
+impl GestureRecognizer {
+    pub fn new(settings: GestureSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    pub fn settings(&self) -> GestureSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: GestureSettings) {
+        self.settings = settings;
+    }
+
+    /// Feeds an input event which happened at `now`, and returns the gestures it completed
+    pub fn handle_event(&mut self, event: &ThermiteEvent, now: Instant) -> Vec<GestureEvent> {
+        // Key repeats come as more presses, which don't restart the press
+        let (trigger, state) = match trigger_of(event) {
+            Some(trigger) => trigger,
+            None => return vec![],
+        };
+        match state {
+            ActionState::Pressed => {
+                self.pressed.entry(trigger).or_insert(Press {
+                    at: now,
+                    held: false,
+                });
+                vec![]
+            }
+            ActionState::Released => {
+                let mut gestures = self.update(now);
+                let press = match self.pressed.remove(&trigger) {
+                    Some(press) if !press.held => press,
+                    _ => return gestures,
+                };
+                let gesture = match self.last_taps.remove(&trigger) {
+                    Some(last_tap)
+                        if now.saturating_duration_since(last_tap)
+                            <= self.settings.double_tap_interval
+                            && press.at >= last_tap =>
+                    {
+                        Gesture::DoubleTap
+                    }
+                    _ => {
+                        self.last_taps.insert(trigger, now);
+                        Gesture::Tap
+                    }
+                };
+                gestures.push(GestureEvent { trigger, gesture });
+                gestures
+            }
+        }
+    }
+
+    /// Returns the holds completed by `now`, call it every frame so they're recognized while the trigger is still down
+    pub fn update(&mut self, now: Instant) -> Vec<GestureEvent> {
+        let hold_duration = self.settings.hold_duration;
+        let holds: Vec<GestureEvent> = self
+            .pressed
+            .iter_mut()
+            .filter(|(_, press)| {
+                !press.held && now.saturating_duration_since(press.at) >= hold_duration
+            })
+            .map(|(trigger, press)| {
+                press.held = true;
+                GestureEvent {
+                    trigger: *trigger,
+                    gesture: Gesture::Hold,
+                }
+            })
+            .collect();
+        for hold in &holds {
+            self.last_taps.remove(&hold.trigger);
+        }
+        holds
+    }
+
+    /// Forgets every trigger held down and every pending tap, e.g. when the window loses focus
+    pub fn reset(&mut self) {
+        self.pressed.clear();
+        self.last_taps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::mouse::MouseEvent;
+    use winit::event::MouseButton;
+
+    fn click(
+        recognizer: &mut GestureRecognizer,
+        start: Instant,
+        at_ms: u64,
+        held_ms: u64,
+    ) -> Vec<Gesture> {
+        let press = ThermiteEvent::Mouse(MouseEvent::ButtonPressed(MouseButton::Left));
+        let release = ThermiteEvent::Mouse(MouseEvent::ButtonReleased(MouseButton::Left));
+        let mut gestures = recognizer.handle_event(&press, start + Duration::from_millis(at_ms));
+        gestures.extend(
+            recognizer.handle_event(&release, start + Duration::from_millis(at_ms + held_ms)),
+        );
+        gestures
+            .into_iter()
+            .map(|gesture| gesture.gesture)
+            .collect()
+    }
+
+    #[test]
+    fn taps_and_double_taps() {
+        let mut recognizer = GestureRecognizer::default();
+        let start = Instant::now();
+        assert_eq!(click(&mut recognizer, start, 0, 50), vec![Gesture::Tap]);
+        assert_eq!(
+            click(&mut recognizer, start, 200, 50),
+            vec![Gesture::DoubleTap]
+        );
+        // A third tap starts over
+        assert_eq!(click(&mut recognizer, start, 400, 50), vec![Gesture::Tap]);
+        // Too late for a double tap
+        assert_eq!(click(&mut recognizer, start, 1000, 50), vec![Gesture::Tap]);
+    }
+
+    #[test]
+    fn holds_are_reported_while_held() {
+        let mut recognizer = GestureRecognizer::default();
+        let start = Instant::now();
+        let press = ThermiteEvent::Mouse(MouseEvent::ButtonPressed(MouseButton::Right));
+        assert!(recognizer.handle_event(&press, start).is_empty());
+        assert!(recognizer
+            .update(start + Duration::from_millis(499))
+            .is_empty());
+        let holds = recognizer.update(start + Duration::from_millis(500));
+        assert_eq!(
+            holds,
+            vec![GestureEvent {
+                trigger: InputTrigger::MouseButton(MouseButton::Right),
+                gesture: Gesture::Hold,
+            }]
+        );
+        // Reported once, and its release isn't a tap
+        assert!(recognizer.update(start + Duration::from_secs(1)).is_empty());
+        let release = ThermiteEvent::Mouse(MouseEvent::ButtonReleased(MouseButton::Right));
+        assert!(recognizer
+            .handle_event(&release, start + Duration::from_secs(2))
+            .is_empty());
+    }
+
+    #[test]
+    fn releasing_late_is_a_hold() {
+        let mut recognizer = GestureRecognizer::default();
+        let start = Instant::now();
+        assert_eq!(click(&mut recognizer, start, 0, 600), vec![Gesture::Hold]);
+    }
+}
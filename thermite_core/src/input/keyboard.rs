@@ -1,3 +1,4 @@
+use crate::input::layout::{key_name, PhysicalKey};
 use crate::messaging::event::ThermiteEvent;
 use bitflags::bitflags;
 use winit::event::{KeyboardInput, ModifiersState, ScanCode, VirtualKeyCode};
@@ -17,6 +18,50 @@ impl From<KeyboardInput> for KeyCode {
     }
 }
 
+impl KeyCode {
+    /// The platform-dependent scancode of the physical key
+    pub fn scancode(&self) -> ScanCode {
+        self.physical
+    }
+
+    /// The virtual key this physical key is mapped to under the current layout, if any
+    pub fn virtual_keycode(&self) -> Option<VirtualKeyCode> {
+        self.mapped
+    }
+
+    /// The stable, cross-platform identifier of the physical key, if it is a known key
+    pub fn physical_key(&self) -> Option<PhysicalKey> {
+        PhysicalKey::from_key_code(self)
+    }
+
+    /// A human-readable name for this key under the layout that was active when it was pressed
+    pub fn display_name(&self) -> String {
+        if let Some(mapped) = self.mapped {
+            key_name(mapped).to_string()
+        } else if let Some(physical) = self.physical_key() {
+            physical.us_name().to_string()
+        } else {
+            format!("Scancode {:#04x}", self.physical)
+        }
+    }
+}
+
+impl From<PhysicalKey> for KeyCode {
+    /// Builds the `KeyCode` the platform would report for the given physical key on a US QWERTY layout
+    fn from(physical: PhysicalKey) -> Self {
+        Self {
+            physical: physical.to_scancode().unwrap_or(0),
+            mapped: Some(physical.us_virtual_keycode()),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyCode {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.display_name())
+    }
+}
+
 bitflags! {
     #[derive(Default)]
     pub struct KeyboardModifiers: u8 {
@@ -52,6 +97,8 @@ pub enum KeyboardEvent {
     KeyPressed(KeyCode),
     KeyReleased(KeyCode),
     ModifiersChanged(KeyboardModifiers),
+    /// A character of text input, after the layout, modifiers and any IME are applied (e.g. for text fields and consoles)
+    Character(char),
 }
 
 impl From<KeyboardEvent> for ThermiteEvent {
@@ -59,3 +106,128 @@ impl From<KeyboardEvent> for ThermiteEvent {
         ThermiteEvent::Keyboard(kb_evt)
     }
 }
+
+/// The outcome of feeding an event to a `KeyCapture`
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum KeyCaptureResult {
+    /// Not capturing, or the event wasn't a key press we care about. Let it propagate as normal.
+    Ignored,
+    /// The event was swallowed by the capture, which is still waiting for a key
+    Consumed,
+    /// A key was captured, along with the modifiers held at the time. Capturing stops.
+    Captured {
+        key: KeyCode,
+        modifiers: KeyboardModifiers,
+    },
+    /// The cancel key was pressed. Capturing stops.
+    Cancelled,
+}
+
+/// A "press any key" capture, for rebinding UIs.
+///
+/// While capturing, every keyboard event fed to `handle_event` is consumed until a key is pressed. Modifier keys are
+/// captured on release (so `Ctrl` + `S` captures `S` with `CTRL`, but tapping `Shift` alone captures `Shift`).
+#[derive(Debug, Clone)]
+pub struct KeyCapture {
+    capturing: bool,
+    cancel_key: Option<VirtualKeyCode>,
+    modifiers: KeyboardModifiers,
+    pending_modifier: Option<KeyCode>,
+}
+
+impl Default for KeyCapture {
+    /// A capture which is cancelled with `Escape`
+    fn default() -> Self {
+        Self::new(Some(VirtualKeyCode::Escape))
+    }
+}
+
+impl KeyCapture {
+    /// Creates a new, inactive `KeyCapture`, which will be cancelled if `cancel_key` is pressed
+    pub fn new(cancel_key: Option<VirtualKeyCode>) -> Self {
+        Self {
+            capturing: false,
+            cancel_key,
+            modifiers: KeyboardModifiers::empty(),
+            pending_modifier: None,
+        }
+    }
+
+    /// Begins waiting for the next key press
+    pub fn start(&mut self) {
+        self.capturing = true;
+        self.pending_modifier = None;
+    }
+
+    /// Stops waiting without capturing anything
+    pub fn stop(&mut self) {
+        self.capturing = false;
+        self.pending_modifier = None;
+    }
+
+    /// Whether this capture is currently waiting for a key
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    /// Feeds an event to this capture. Modifier state is tracked even while not capturing.
+    pub fn handle_event(&mut self, event: &ThermiteEvent) -> KeyCaptureResult {
+        let keyboard_event = match event {
+            ThermiteEvent::Keyboard(keyboard_event) => keyboard_event,
+            _ => return KeyCaptureResult::Ignored,
+        };
+        if let KeyboardEvent::ModifiersChanged(modifiers) = keyboard_event {
+            self.modifiers = *modifiers;
+        }
+        if !self.capturing {
+            return KeyCaptureResult::Ignored;
+        }
+        match keyboard_event {
+            KeyboardEvent::KeyPressed(key) => {
+                if key.mapped.is_some() && key.mapped == self.cancel_key {
+                    self.stop();
+                    KeyCaptureResult::Cancelled
+                } else if key.mapped.is_some_and(is_modifier_key) {
+                    // Wait for the release, in case this modifier is part of a chord
+                    self.pending_modifier = Some(key.clone());
+                    KeyCaptureResult::Consumed
+                } else {
+                    self.stop();
+                    KeyCaptureResult::Captured {
+                        key: key.clone(),
+                        modifiers: self.modifiers,
+                    }
+                }
+            }
+            KeyboardEvent::KeyReleased(key) => {
+                if self.pending_modifier.as_ref() == Some(key) {
+                    self.stop();
+                    KeyCaptureResult::Captured {
+                        key: key.clone(),
+                        modifiers: KeyboardModifiers::empty(),
+                    }
+                } else {
+                    KeyCaptureResult::Consumed
+                }
+            }
+            KeyboardEvent::ModifiersChanged(_) | KeyboardEvent::Character(_) => {
+                KeyCaptureResult::Consumed
+            }
+        }
+    }
+}
+
+/// Whether the given virtual key is a modifier key (Shift, Ctrl, Alt or Logo)
+fn is_modifier_key(virtual_keycode: VirtualKeyCode) -> bool {
+    matches!(
+        virtual_keycode,
+        VirtualKeyCode::LShift
+            | VirtualKeyCode::RShift
+            | VirtualKeyCode::LControl
+            | VirtualKeyCode::RControl
+            | VirtualKeyCode::LAlt
+            | VirtualKeyCode::RAlt
+            | VirtualKeyCode::LWin
+            | VirtualKeyCode::RWin
+    )
+}
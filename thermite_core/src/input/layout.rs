@@ -0,0 +1,723 @@
+/*
+    ABSTRACT: Stable, cross-platform physical key identifiers (for bindings) and human-readable
+    key names under the active keyboard layout (for display).
+*/
+use crate::input::keyboard::{KeyCode, KeyboardEvent};
+use crate::messaging::event::ThermiteEvent;
+use std::collections::HashMap;
+use std::str::FromStr;
+use winit::event::{ScanCode, VirtualKeyCode};
+
+macro_rules! physical_keys {
+    ($($key:ident => $id:expr, $vk:ident;)*) => {
+        /// A key identified by its physical position on the keyboard rather than by the symbol printed on it.
+        ///
+        /// Positions are named after the key found there on a US QWERTY keyboard, so `PhysicalKey::KeyW` is the top-left
+        /// key of the classic movement cluster on every layout (`Z` on AZERTY). These identifiers are stable across
+        /// platforms and layouts, which makes them the right thing to store in bindings.
+        #[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+        pub enum PhysicalKey {
+            $($key,)*
+        }
+
+        impl PhysicalKey {
+            /// Every known `PhysicalKey`
+            pub const ALL: &'static [PhysicalKey] = &[$(PhysicalKey::$key,)*];
+
+            /// The stable string identifier of this key, suitable for serializing bindings
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(PhysicalKey::$key => $id,)*
+                }
+            }
+
+            /// The virtual key this physical key produces on a US QWERTY layout
+            pub fn us_virtual_keycode(&self) -> VirtualKeyCode {
+                match self {
+                    $(PhysicalKey::$key => VirtualKeyCode::$vk,)*
+                }
+            }
+        }
+
+        impl FromStr for PhysicalKey {
+            type Err = UnknownKeyError;
+
+            fn from_str(id: &str) -> Result<Self, Self::Err> {
+                match id {
+                    $($id => Ok(PhysicalKey::$key),)*
+                    _ => Err(UnknownKeyError(id.to_string())),
+                }
+            }
+        }
+    };
+}
+
+physical_keys! {
+    Escape => "Escape", Escape;
+    Digit1 => "Digit1", Key1;
+    Digit2 => "Digit2", Key2;
+    Digit3 => "Digit3", Key3;
+    Digit4 => "Digit4", Key4;
+    Digit5 => "Digit5", Key5;
+    Digit6 => "Digit6", Key6;
+    Digit7 => "Digit7", Key7;
+    Digit8 => "Digit8", Key8;
+    Digit9 => "Digit9", Key9;
+    Digit0 => "Digit0", Key0;
+    Minus => "Minus", Minus;
+    Equal => "Equal", Equals;
+    Backspace => "Backspace", Back;
+    Tab => "Tab", Tab;
+    KeyQ => "KeyQ", Q;
+    KeyW => "KeyW", W;
+    KeyE => "KeyE", E;
+    KeyR => "KeyR", R;
+    KeyT => "KeyT", T;
+    KeyY => "KeyY", Y;
+    KeyU => "KeyU", U;
+    KeyI => "KeyI", I;
+    KeyO => "KeyO", O;
+    KeyP => "KeyP", P;
+    BracketLeft => "BracketLeft", LBracket;
+    BracketRight => "BracketRight", RBracket;
+    Enter => "Enter", Return;
+    ControlLeft => "ControlLeft", LControl;
+    KeyA => "KeyA", A;
+    KeyS => "KeyS", S;
+    KeyD => "KeyD", D;
+    KeyF => "KeyF", F;
+    KeyG => "KeyG", G;
+    KeyH => "KeyH", H;
+    KeyJ => "KeyJ", J;
+    KeyK => "KeyK", K;
+    KeyL => "KeyL", L;
+    Semicolon => "Semicolon", Semicolon;
+    Quote => "Quote", Apostrophe;
+    Backquote => "Backquote", Grave;
+    ShiftLeft => "ShiftLeft", LShift;
+    Backslash => "Backslash", Backslash;
+    KeyZ => "KeyZ", Z;
+    KeyX => "KeyX", X;
+    KeyC => "KeyC", C;
+    KeyV => "KeyV", V;
+    KeyB => "KeyB", B;
+    KeyN => "KeyN", N;
+    KeyM => "KeyM", M;
+    Comma => "Comma", Comma;
+    Period => "Period", Period;
+    Slash => "Slash", Slash;
+    ShiftRight => "ShiftRight", RShift;
+    NumpadMultiply => "NumpadMultiply", Multiply;
+    AltLeft => "AltLeft", LAlt;
+    Space => "Space", Space;
+    CapsLock => "CapsLock", Capital;
+    F1 => "F1", F1;
+    F2 => "F2", F2;
+    F3 => "F3", F3;
+    F4 => "F4", F4;
+    F5 => "F5", F5;
+    F6 => "F6", F6;
+    F7 => "F7", F7;
+    F8 => "F8", F8;
+    F9 => "F9", F9;
+    F10 => "F10", F10;
+    F11 => "F11", F11;
+    F12 => "F12", F12;
+    NumLock => "NumLock", Numlock;
+    ScrollLock => "ScrollLock", Scroll;
+    Numpad7 => "Numpad7", Numpad7;
+    Numpad8 => "Numpad8", Numpad8;
+    Numpad9 => "Numpad9", Numpad9;
+    NumpadSubtract => "NumpadSubtract", Subtract;
+    Numpad4 => "Numpad4", Numpad4;
+    Numpad5 => "Numpad5", Numpad5;
+    Numpad6 => "Numpad6", Numpad6;
+    NumpadAdd => "NumpadAdd", Add;
+    Numpad1 => "Numpad1", Numpad1;
+    Numpad2 => "Numpad2", Numpad2;
+    Numpad3 => "Numpad3", Numpad3;
+    Numpad0 => "Numpad0", Numpad0;
+    NumpadDecimal => "NumpadDecimal", Decimal;
+    IntlBackslash => "IntlBackslash", OEM102;
+    NumpadEnter => "NumpadEnter", NumpadEnter;
+    ControlRight => "ControlRight", RControl;
+    NumpadDivide => "NumpadDivide", Divide;
+    PrintScreen => "PrintScreen", Snapshot;
+    AltRight => "AltRight", RAlt;
+    Pause => "Pause", Pause;
+    Home => "Home", Home;
+    ArrowUp => "ArrowUp", Up;
+    PageUp => "PageUp", PageUp;
+    ArrowLeft => "ArrowLeft", Left;
+    ArrowRight => "ArrowRight", Right;
+    End => "End", End;
+    ArrowDown => "ArrowDown", Down;
+    PageDown => "PageDown", PageDown;
+    Insert => "Insert", Insert;
+    Delete => "Delete", Delete;
+    MetaLeft => "MetaLeft", LWin;
+    MetaRight => "MetaRight", RWin;
+    ContextMenu => "ContextMenu", Apps;
+}
+
+/// Error returned when parsing an unknown `PhysicalKey` identifier
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct UnknownKeyError(pub String);
+
+impl std::fmt::Display for UnknownKeyError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "Unknown physical key identifier: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownKeyError {}
+
+impl std::fmt::Display for PhysicalKey {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+impl PhysicalKey {
+    /// Looks up the physical key for a platform scancode, as reported in `KeyCode::scancode`.
+    ///
+    /// **NOTE:** On Windows only the low byte of the scancode is reported, so extended keys (arrows, navigation cluster,
+    /// right-hand modifiers) alias numpad and left-hand keys. Prefer `PhysicalKey::from_key_code`, which disambiguates
+    /// them using the mapped virtual key.
+    pub fn from_scancode(scancode: ScanCode) -> Option<PhysicalKey> {
+        platform_scancodes()
+            .find(|(code, _)| *code == scancode)
+            .map(|(_, key)| *key)
+    }
+
+    /// The platform scancode of this physical key, if it has one on this platform
+    pub fn to_scancode(&self) -> Option<ScanCode> {
+        platform_scancodes()
+            .find(|(_, key)| key == self)
+            .map(|(code, _)| *code)
+    }
+
+    /// Determines the physical key of a `KeyCode` received from the platform
+    pub fn from_key_code(key_code: &KeyCode) -> Option<PhysicalKey> {
+        let from_scancode = PhysicalKey::from_scancode(key_code.scancode());
+        if cfg!(windows) {
+            // Extended keys share their low scancode byte with another key, but their virtual key is unambiguous
+            if let Some(extended) = key_code.virtual_keycode().and_then(windows_extended_key) {
+                return Some(extended);
+            }
+        }
+        from_scancode
+    }
+
+    /// The name printed on this key on a US QWERTY keyboard
+    pub fn us_name(&self) -> &'static str {
+        key_name(self.us_virtual_keycode())
+    }
+}
+
+/// Maps virtual keys which can only come from an extended (0xE0 prefixed) key on Windows to that key,
+/// and Pause (0xE1 prefixed), which shares scancode 0x45 with NumLock
+fn windows_extended_key(virtual_keycode: VirtualKeyCode) -> Option<PhysicalKey> {
+    match virtual_keycode {
+        VirtualKeyCode::RControl => Some(PhysicalKey::ControlRight),
+        VirtualKeyCode::RAlt => Some(PhysicalKey::AltRight),
+        VirtualKeyCode::NumpadEnter => Some(PhysicalKey::NumpadEnter),
+        VirtualKeyCode::Divide => Some(PhysicalKey::NumpadDivide),
+        VirtualKeyCode::Home => Some(PhysicalKey::Home),
+        VirtualKeyCode::Up => Some(PhysicalKey::ArrowUp),
+        VirtualKeyCode::PageUp => Some(PhysicalKey::PageUp),
+        VirtualKeyCode::Left => Some(PhysicalKey::ArrowLeft),
+        VirtualKeyCode::Right => Some(PhysicalKey::ArrowRight),
+        VirtualKeyCode::End => Some(PhysicalKey::End),
+        VirtualKeyCode::Down => Some(PhysicalKey::ArrowDown),
+        VirtualKeyCode::PageDown => Some(PhysicalKey::PageDown),
+        VirtualKeyCode::Insert => Some(PhysicalKey::Insert),
+        VirtualKeyCode::Delete => Some(PhysicalKey::Delete),
+        VirtualKeyCode::Snapshot => Some(PhysicalKey::PrintScreen),
+        VirtualKeyCode::Pause => Some(PhysicalKey::Pause),
+        _ => None,
+    }
+}
+
+/// Iterates over the scancode table of the platform we're compiled for
+fn platform_scancodes() -> impl Iterator<Item = &'static (ScanCode, PhysicalKey)> {
+    BASE_SCANCODES.iter().chain(EXTENDED_SCANCODES.iter())
+}
+
+/// PC scancode set 1, which Linux evdev codes also follow. Shared by the Windows and Linux tables.
+#[cfg(any(windows, all(unix, not(target_os = "macos"))))]
+const BASE_SCANCODES: &[(ScanCode, PhysicalKey)] = &[
+    (0x01, PhysicalKey::Escape),
+    (0x02, PhysicalKey::Digit1),
+    (0x03, PhysicalKey::Digit2),
+    (0x04, PhysicalKey::Digit3),
+    (0x05, PhysicalKey::Digit4),
+    (0x06, PhysicalKey::Digit5),
+    (0x07, PhysicalKey::Digit6),
+    (0x08, PhysicalKey::Digit7),
+    (0x09, PhysicalKey::Digit8),
+    (0x0A, PhysicalKey::Digit9),
+    (0x0B, PhysicalKey::Digit0),
+    (0x0C, PhysicalKey::Minus),
+    (0x0D, PhysicalKey::Equal),
+    (0x0E, PhysicalKey::Backspace),
+    (0x0F, PhysicalKey::Tab),
+    (0x10, PhysicalKey::KeyQ),
+    (0x11, PhysicalKey::KeyW),
+    (0x12, PhysicalKey::KeyE),
+    (0x13, PhysicalKey::KeyR),
+    (0x14, PhysicalKey::KeyT),
+    (0x15, PhysicalKey::KeyY),
+    (0x16, PhysicalKey::KeyU),
+    (0x17, PhysicalKey::KeyI),
+    (0x18, PhysicalKey::KeyO),
+    (0x19, PhysicalKey::KeyP),
+    (0x1A, PhysicalKey::BracketLeft),
+    (0x1B, PhysicalKey::BracketRight),
+    (0x1C, PhysicalKey::Enter),
+    (0x1D, PhysicalKey::ControlLeft),
+    (0x1E, PhysicalKey::KeyA),
+    (0x1F, PhysicalKey::KeyS),
+    (0x20, PhysicalKey::KeyD),
+    (0x21, PhysicalKey::KeyF),
+    (0x22, PhysicalKey::KeyG),
+    (0x23, PhysicalKey::KeyH),
+    (0x24, PhysicalKey::KeyJ),
+    (0x25, PhysicalKey::KeyK),
+    (0x26, PhysicalKey::KeyL),
+    (0x27, PhysicalKey::Semicolon),
+    (0x28, PhysicalKey::Quote),
+    (0x29, PhysicalKey::Backquote),
+    (0x2A, PhysicalKey::ShiftLeft),
+    (0x2B, PhysicalKey::Backslash),
+    (0x2C, PhysicalKey::KeyZ),
+    (0x2D, PhysicalKey::KeyX),
+    (0x2E, PhysicalKey::KeyC),
+    (0x2F, PhysicalKey::KeyV),
+    (0x30, PhysicalKey::KeyB),
+    (0x31, PhysicalKey::KeyN),
+    (0x32, PhysicalKey::KeyM),
+    (0x33, PhysicalKey::Comma),
+    (0x34, PhysicalKey::Period),
+    (0x35, PhysicalKey::Slash),
+    (0x36, PhysicalKey::ShiftRight),
+    (0x37, PhysicalKey::NumpadMultiply),
+    (0x38, PhysicalKey::AltLeft),
+    (0x39, PhysicalKey::Space),
+    (0x3A, PhysicalKey::CapsLock),
+    (0x3B, PhysicalKey::F1),
+    (0x3C, PhysicalKey::F2),
+    (0x3D, PhysicalKey::F3),
+    (0x3E, PhysicalKey::F4),
+    (0x3F, PhysicalKey::F5),
+    (0x40, PhysicalKey::F6),
+    (0x41, PhysicalKey::F7),
+    (0x42, PhysicalKey::F8),
+    (0x43, PhysicalKey::F9),
+    (0x44, PhysicalKey::F10),
+    (0x45, PhysicalKey::NumLock),
+    (0x46, PhysicalKey::ScrollLock),
+    (0x47, PhysicalKey::Numpad7),
+    (0x48, PhysicalKey::Numpad8),
+    (0x49, PhysicalKey::Numpad9),
+    (0x4A, PhysicalKey::NumpadSubtract),
+    (0x4B, PhysicalKey::Numpad4),
+    (0x4C, PhysicalKey::Numpad5),
+    (0x4D, PhysicalKey::Numpad6),
+    (0x4E, PhysicalKey::NumpadAdd),
+    (0x4F, PhysicalKey::Numpad1),
+    (0x50, PhysicalKey::Numpad2),
+    (0x51, PhysicalKey::Numpad3),
+    (0x52, PhysicalKey::Numpad0),
+    (0x53, PhysicalKey::NumpadDecimal),
+    (0x56, PhysicalKey::IntlBackslash),
+    (0x57, PhysicalKey::F11),
+    (0x58, PhysicalKey::F12),
+];
+
+/// Windows reports the low byte of set 1 scancodes, plus a few extended keys whose low byte is unique
+#[cfg(windows)]
+const EXTENDED_SCANCODES: &[(ScanCode, PhysicalKey)] = &[
+    (0x5B, PhysicalKey::MetaLeft),
+    (0x5C, PhysicalKey::MetaRight),
+    (0x5D, PhysicalKey::ContextMenu),
+];
+
+/// Linux (X11 and Wayland) reports evdev codes, which extend set 1 with unique codes for extended keys
+#[cfg(all(unix, not(target_os = "macos")))]
+const EXTENDED_SCANCODES: &[(ScanCode, PhysicalKey)] = &[
+    (96, PhysicalKey::NumpadEnter),
+    (97, PhysicalKey::ControlRight),
+    (98, PhysicalKey::NumpadDivide),
+    (99, PhysicalKey::PrintScreen),
+    (100, PhysicalKey::AltRight),
+    (102, PhysicalKey::Home),
+    (103, PhysicalKey::ArrowUp),
+    (104, PhysicalKey::PageUp),
+    (105, PhysicalKey::ArrowLeft),
+    (106, PhysicalKey::ArrowRight),
+    (107, PhysicalKey::End),
+    (108, PhysicalKey::ArrowDown),
+    (109, PhysicalKey::PageDown),
+    (110, PhysicalKey::Insert),
+    (111, PhysicalKey::Delete),
+    (119, PhysicalKey::Pause),
+    (125, PhysicalKey::MetaLeft),
+    (126, PhysicalKey::MetaRight),
+    (127, PhysicalKey::ContextMenu),
+];
+
+/// macOS reports Carbon virtual keycodes (`kVK_*`), which despite the name identify physical positions
+#[cfg(target_os = "macos")]
+const BASE_SCANCODES: &[(ScanCode, PhysicalKey)] = &[
+    (0x00, PhysicalKey::KeyA),
+    (0x01, PhysicalKey::KeyS),
+    (0x02, PhysicalKey::KeyD),
+    (0x03, PhysicalKey::KeyF),
+    (0x04, PhysicalKey::KeyH),
+    (0x05, PhysicalKey::KeyG),
+    (0x06, PhysicalKey::KeyZ),
+    (0x07, PhysicalKey::KeyX),
+    (0x08, PhysicalKey::KeyC),
+    (0x09, PhysicalKey::KeyV),
+    (0x0A, PhysicalKey::IntlBackslash),
+    (0x0B, PhysicalKey::KeyB),
+    (0x0C, PhysicalKey::KeyQ),
+    (0x0D, PhysicalKey::KeyW),
+    (0x0E, PhysicalKey::KeyE),
+    (0x0F, PhysicalKey::KeyR),
+    (0x10, PhysicalKey::KeyY),
+    (0x11, PhysicalKey::KeyT),
+    (0x12, PhysicalKey::Digit1),
+    (0x13, PhysicalKey::Digit2),
+    (0x14, PhysicalKey::Digit3),
+    (0x15, PhysicalKey::Digit4),
+    (0x16, PhysicalKey::Digit6),
+    (0x17, PhysicalKey::Digit5),
+    (0x18, PhysicalKey::Equal),
+    (0x19, PhysicalKey::Digit9),
+    (0x1A, PhysicalKey::Digit7),
+    (0x1B, PhysicalKey::Minus),
+    (0x1C, PhysicalKey::Digit8),
+    (0x1D, PhysicalKey::Digit0),
+    (0x1E, PhysicalKey::BracketRight),
+    (0x1F, PhysicalKey::KeyO),
+    (0x20, PhysicalKey::KeyU),
+    (0x21, PhysicalKey::BracketLeft),
+    (0x22, PhysicalKey::KeyI),
+    (0x23, PhysicalKey::KeyP),
+    (0x24, PhysicalKey::Enter),
+    (0x25, PhysicalKey::KeyL),
+    (0x26, PhysicalKey::KeyJ),
+    (0x27, PhysicalKey::Quote),
+    (0x28, PhysicalKey::KeyK),
+    (0x29, PhysicalKey::Semicolon),
+    (0x2A, PhysicalKey::Backslash),
+    (0x2B, PhysicalKey::Comma),
+    (0x2C, PhysicalKey::Slash),
+    (0x2D, PhysicalKey::KeyN),
+    (0x2E, PhysicalKey::KeyM),
+    (0x2F, PhysicalKey::Period),
+    (0x30, PhysicalKey::Tab),
+    (0x31, PhysicalKey::Space),
+    (0x32, PhysicalKey::Backquote),
+    (0x33, PhysicalKey::Backspace),
+    (0x35, PhysicalKey::Escape),
+    (0x36, PhysicalKey::MetaRight),
+    (0x37, PhysicalKey::MetaLeft),
+    (0x38, PhysicalKey::ShiftLeft),
+    (0x39, PhysicalKey::CapsLock),
+    (0x3A, PhysicalKey::AltLeft),
+    (0x3B, PhysicalKey::ControlLeft),
+    (0x3C, PhysicalKey::ShiftRight),
+    (0x3D, PhysicalKey::AltRight),
+    (0x3E, PhysicalKey::ControlRight),
+    (0x41, PhysicalKey::NumpadDecimal),
+    (0x43, PhysicalKey::NumpadMultiply),
+    (0x45, PhysicalKey::NumpadAdd),
+    (0x47, PhysicalKey::NumLock),
+    (0x4B, PhysicalKey::NumpadDivide),
+    (0x4C, PhysicalKey::NumpadEnter),
+    (0x4E, PhysicalKey::NumpadSubtract),
+    (0x52, PhysicalKey::Numpad0),
+    (0x53, PhysicalKey::Numpad1),
+    (0x54, PhysicalKey::Numpad2),
+    (0x55, PhysicalKey::Numpad3),
+    (0x56, PhysicalKey::Numpad4),
+    (0x57, PhysicalKey::Numpad5),
+    (0x58, PhysicalKey::Numpad6),
+    (0x59, PhysicalKey::Numpad7),
+    (0x5B, PhysicalKey::Numpad8),
+    (0x5C, PhysicalKey::Numpad9),
+    (0x60, PhysicalKey::F5),
+    (0x61, PhysicalKey::F6),
+    (0x62, PhysicalKey::F7),
+    (0x63, PhysicalKey::F3),
+    (0x64, PhysicalKey::F8),
+    (0x65, PhysicalKey::F9),
+    (0x67, PhysicalKey::F11),
+    (0x6D, PhysicalKey::F10),
+    (0x6E, PhysicalKey::ContextMenu),
+    (0x6F, PhysicalKey::F12),
+    (0x72, PhysicalKey::Insert),
+    (0x73, PhysicalKey::Home),
+    (0x74, PhysicalKey::PageUp),
+    (0x75, PhysicalKey::Delete),
+    (0x76, PhysicalKey::F4),
+    (0x77, PhysicalKey::End),
+    (0x78, PhysicalKey::F2),
+    (0x79, PhysicalKey::PageDown),
+    (0x7A, PhysicalKey::F1),
+    (0x7B, PhysicalKey::ArrowLeft),
+    (0x7C, PhysicalKey::ArrowRight),
+    (0x7D, PhysicalKey::ArrowDown),
+    (0x7E, PhysicalKey::ArrowUp),
+];
+
+#[cfg(target_os = "macos")]
+const EXTENDED_SCANCODES: &[(ScanCode, PhysicalKey)] = &[];
+
+/// Any other platform has no known scancode table, so bindings fall back to virtual keys
+#[cfg(not(any(unix, windows)))]
+const BASE_SCANCODES: &[(ScanCode, PhysicalKey)] = &[];
+
+#[cfg(not(any(unix, windows)))]
+const EXTENDED_SCANCODES: &[(ScanCode, PhysicalKey)] = &[];
+
+/// The human-readable name of a virtual key, i.e. the symbol or label on the key producing it
+pub fn key_name(virtual_keycode: VirtualKeyCode) -> &'static str {
+    use VirtualKeyCode::*;
+    match virtual_keycode {
+        Key1 => "1",
+        Key2 => "2",
+        Key3 => "3",
+        Key4 => "4",
+        Key5 => "5",
+        Key6 => "6",
+        Key7 => "7",
+        Key8 => "8",
+        Key9 => "9",
+        Key0 => "0",
+        A => "A",
+        B => "B",
+        C => "C",
+        D => "D",
+        E => "E",
+        F => "F",
+        G => "G",
+        H => "H",
+        I => "I",
+        J => "J",
+        K => "K",
+        L => "L",
+        M => "M",
+        N => "N",
+        O => "O",
+        P => "P",
+        Q => "Q",
+        R => "R",
+        S => "S",
+        T => "T",
+        U => "U",
+        V => "V",
+        W => "W",
+        X => "X",
+        Y => "Y",
+        Z => "Z",
+        Escape => "Escape",
+        F1 => "F1",
+        F2 => "F2",
+        F3 => "F3",
+        F4 => "F4",
+        F5 => "F5",
+        F6 => "F6",
+        F7 => "F7",
+        F8 => "F8",
+        F9 => "F9",
+        F10 => "F10",
+        F11 => "F11",
+        F12 => "F12",
+        F13 => "F13",
+        F14 => "F14",
+        F15 => "F15",
+        F16 => "F16",
+        F17 => "F17",
+        F18 => "F18",
+        F19 => "F19",
+        F20 => "F20",
+        F21 => "F21",
+        F22 => "F22",
+        F23 => "F23",
+        F24 => "F24",
+        Snapshot => "Print Screen",
+        Scroll => "Scroll Lock",
+        Pause => "Pause",
+        Insert => "Insert",
+        Home => "Home",
+        Delete => "Delete",
+        End => "End",
+        PageDown => "Page Down",
+        PageUp => "Page Up",
+        Left => "Left",
+        Up => "Up",
+        Right => "Right",
+        Down => "Down",
+        Back => "Backspace",
+        Return => "Enter",
+        Space => "Space",
+        Compose => "Compose",
+        Caret => "^",
+        Numlock => "Num Lock",
+        Numpad0 => "Numpad 0",
+        Numpad1 => "Numpad 1",
+        Numpad2 => "Numpad 2",
+        Numpad3 => "Numpad 3",
+        Numpad4 => "Numpad 4",
+        Numpad5 => "Numpad 5",
+        Numpad6 => "Numpad 6",
+        Numpad7 => "Numpad 7",
+        Numpad8 => "Numpad 8",
+        Numpad9 => "Numpad 9",
+        AbntC1 => "ABNT C1",
+        AbntC2 => "ABNT C2",
+        Add => "Numpad +",
+        Apostrophe => "'",
+        Apps => "Menu",
+        At => "@",
+        Ax => "AX",
+        Backslash => "\\",
+        Calculator => "Calculator",
+        Capital => "Caps Lock",
+        Colon => ":",
+        Comma => ",",
+        Convert => "Convert",
+        Decimal => "Numpad .",
+        Divide => "Numpad /",
+        Equals => "=",
+        Grave => "`",
+        Kana => "Kana",
+        Kanji => "Kanji",
+        LAlt => "Left Alt",
+        LBracket => "[",
+        LControl => "Left Ctrl",
+        LShift => "Left Shift",
+        LWin => "Left Logo",
+        Mail => "Mail",
+        MediaSelect => "Media Select",
+        MediaStop => "Media Stop",
+        Minus => "-",
+        Multiply => "Numpad *",
+        Mute => "Mute",
+        MyComputer => "My Computer",
+        NavigateForward => "Navigate Forward",
+        NavigateBackward => "Navigate Backward",
+        NextTrack => "Next Track",
+        NoConvert => "No Convert",
+        NumpadComma => "Numpad ,",
+        NumpadEnter => "Numpad Enter",
+        NumpadEquals => "Numpad =",
+        OEM102 => "OEM 102",
+        Period => ".",
+        PlayPause => "Play/Pause",
+        Power => "Power",
+        PrevTrack => "Previous Track",
+        RAlt => "Right Alt",
+        RBracket => "]",
+        RControl => "Right Ctrl",
+        RShift => "Right Shift",
+        RWin => "Right Logo",
+        Semicolon => ";",
+        Slash => "/",
+        Sleep => "Sleep",
+        Stop => "Stop",
+        Subtract => "Numpad -",
+        Sysrq => "SysRq",
+        Tab => "Tab",
+        Underline => "_",
+        Unlabeled => "Unlabeled",
+        VolumeDown => "Volume Down",
+        VolumeUp => "Volume Up",
+        Wake => "Wake",
+        WebBack => "Browser Back",
+        WebFavorites => "Browser Favorites",
+        WebForward => "Browser Forward",
+        WebHome => "Browser Home",
+        WebRefresh => "Browser Refresh",
+        WebSearch => "Browser Search",
+        WebStop => "Browser Stop",
+        Yen => "¥",
+        Copy => "Copy",
+        Paste => "Paste",
+        Cut => "Cut",
+    }
+}
+
+/// Tracks which virtual key each physical key produces under the active keyboard layout.
+///
+/// winit has no API for querying the layout directly, so the mapping is learned from the key events the platform
+/// sends us, falling back to US QWERTY for keys that haven't been pressed yet. Feed it every keyboard event.
+#[derive(Debug, Default, Clone)]
+pub struct KeyboardLayout {
+    observed: HashMap<PhysicalKey, VirtualKeyCode>,
+}
+
+impl KeyboardLayout {
+    /// Records the layout mapping reported by a key event
+    pub fn observe(&mut self, key_code: &KeyCode) {
+        if let (Some(physical), Some(mapped)) = (
+            PhysicalKey::from_key_code(key_code),
+            key_code.virtual_keycode(),
+        ) {
+            self.observed.insert(physical, mapped);
+        }
+    }
+
+    /// Records the layout mapping from any keyboard event, ignoring everything else
+    pub fn handle_event(&mut self, event: &ThermiteEvent) {
+        match event {
+            ThermiteEvent::Keyboard(KeyboardEvent::KeyPressed(key_code))
+            | ThermiteEvent::Keyboard(KeyboardEvent::KeyReleased(key_code)) => {
+                self.observe(key_code)
+            }
+            _ => (),
+        }
+    }
+
+    /// Forgets every learned mapping, e.g. after the user switches layouts
+    pub fn clear(&mut self) {
+        self.observed.clear();
+    }
+
+    /// The virtual key the given physical key produces under the active layout
+    pub fn virtual_keycode_for(&self, physical: PhysicalKey) -> VirtualKeyCode {
+        self.observed
+            .get(&physical)
+            .copied()
+            .unwrap_or_else(|| physical.us_virtual_keycode())
+    }
+
+    /// The physical key which produces the given virtual key under the active layout, if any
+    pub fn physical_key_for(&self, virtual_keycode: VirtualKeyCode) -> Option<PhysicalKey> {
+        self.observed
+            .iter()
+            .find(|(_, mapped)| **mapped == virtual_keycode)
+            .map(|(physical, _)| *physical)
+            .or_else(|| {
+                PhysicalKey::ALL
+                    .iter()
+                    .find(|physical| {
+                        !self.observed.contains_key(physical)
+                            && physical.us_virtual_keycode() == virtual_keycode
+                    })
+                    .copied()
+            })
+    }
+
+    /// The human-readable name of the given physical key under the active layout, for displaying bindings
+    pub fn display_name(&self, physical: PhysicalKey) -> &'static str {
+        match self.observed.get(&physical) {
+            Some(mapped) => key_name(*mapped),
+            None => physical.us_name(),
+        }
+    }
+}
@@ -1,3 +1,7 @@
 // TODO: Once this reaches maturity with gamepad and input handler / config, move it out to it's own crate. Doesn't really belong in core...
+pub mod axis;
+pub mod context;
+pub mod gesture;
 pub mod keyboard;
+pub mod layout;
 pub mod mouse;
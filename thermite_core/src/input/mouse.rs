@@ -1,4 +1,5 @@
 use crate::messaging::event::ThermiteEvent;
+use std::hash::{Hash, Hasher};
 use winit::dpi::PhysicalPosition;
 use winit::event::{MouseButton, MouseScrollDelta};
 
@@ -23,6 +24,64 @@ impl From<MouseScrollDelta> for ScrollDelta {
     }
 }
 
+impl ScrollDelta {
+    /// Horizontal scroll amount
+    pub fn x(&self) -> i64 {
+        self.x
+    }
+
+    /// Vertical scroll amount
+    pub fn y(&self) -> i64 {
+        self.y
+    }
+}
+
+/// Raw, unaccelerated mouse movement since the last motion event, as reported by the device.
+///
+/// Kept unrounded: slow or high-DPI motion moves less than one unit per event.
+#[derive(Debug, Clone, Copy)]
+pub struct MotionDelta {
+    x: f64,
+    y: f64,
+}
+
+impl From<(f64, f64)> for MotionDelta {
+    fn from(delta: (f64, f64)) -> Self {
+        Self {
+            x: delta.0,
+            y: delta.1,
+        }
+    }
+}
+
+// Compared and hashed bit for bit, so events stay usable as keys
+impl PartialEq for MotionDelta {
+    fn eq(&self, other: &Self) -> bool {
+        self.x.to_bits() == other.x.to_bits() && self.y.to_bits() == other.y.to_bits()
+    }
+}
+
+impl Eq for MotionDelta {}
+
+impl Hash for MotionDelta {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.x.to_bits().hash(state);
+        self.y.to_bits().hash(state);
+    }
+}
+
+impl MotionDelta {
+    /// Horizontal movement
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    /// Vertical movement
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct PixelCoordinates {
     x: u64,
@@ -43,6 +102,7 @@ pub enum MouseEvent {
     ButtonReleased(MouseButton),
     Scroll(ScrollDelta),
     Motion(PixelCoordinates),
+    RawMotion(MotionDelta),
     EnteredWindow,
     LeftWindow,
 }
@@ -0,0 +1,138 @@
+/*
+    ABSTRACT: Handles on scheduled jobs. A `JobHandle` waits for a job's result (running other jobs meanwhile),
+    and hands out `JobDependency`s, which other jobs can be scheduled after (see scheduler.rs).
+*/
+use crate::jobs::scheduler::{JobScheduler, PendingJob};
+use std::{
+    panic,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+struct CompletionState {
+    finished: bool,
+    /// Jobs waiting on this one, see `Completion::add_dependent`
+    dependents: Vec<Arc<PendingJob>>,
+}
+
+/// Whether a job has finished, and who's waiting for it
+pub(crate) struct Completion {
+    state: Mutex<CompletionState>,
+    finished: Condvar,
+}
+
+impl Completion {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(CompletionState {
+                finished: false,
+                dependents: vec![],
+            }),
+            finished: Condvar::new(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, CompletionState> {
+        // Jobs catch their own panics, so a poisoned state is still a valid state
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Registers a job to release once this one finishes. Returns `false` if this one has already finished.
+    pub(crate) fn add_dependent(&self, job: &Arc<PendingJob>) -> bool {
+        let mut state = self.state();
+        if !state.finished {
+            state.dependents.push(job.clone());
+        }
+        !state.finished
+    }
+
+    /// Marks the job as finished, wakes whoever waits for it, and returns the jobs which depended on it
+    pub(crate) fn finish(&self) -> Vec<Arc<PendingJob>> {
+        let dependents = {
+            let mut state = self.state();
+            state.finished = true;
+            std::mem::take(&mut state.dependents)
+        };
+        self.finished.notify_all();
+        dependents
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.state().finished
+    }
+
+    /// Blocks until the job finishes, or the timeout expires
+    pub(crate) fn wait_timeout(&self, timeout: Duration) {
+        let state = self.state();
+        if !state.finished {
+            let _ = self.finished.wait_timeout(state, timeout);
+        }
+    }
+}
+
+/// Something jobs can be scheduled after, see `JobHandle::dependency`
+#[derive(Clone)]
+pub struct JobDependency {
+    pub(crate) completion: Arc<Completion>,
+}
+
+impl JobDependency {
+    pub fn is_finished(&self) -> bool {
+        self.completion.is_finished()
+    }
+}
+
+/// The result of a job, once it has run. `Err` holds the job's panic.
+pub(crate) type JobResult<T> = Arc<Mutex<Option<thread::Result<T>>>>;
+
+/// A handle on a scheduled job, returning a `T`
+pub struct JobHandle<T> {
+    pub(crate) completion: Arc<Completion>,
+    pub(crate) result: JobResult<T>,
+    pub(crate) scheduler: JobScheduler,
+}
+
+impl<T> JobHandle<T> {
+    /// A dependency on this job, for scheduling other jobs after it
+    pub fn dependency(&self) -> JobDependency {
+        JobDependency {
+            completion: self.completion.clone(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.completion.is_finished()
+    }
+
+    /// Blocks until the job has run, running other jobs on this thread meanwhile, and returns its result.
+    ///
+    /// If the job panicked, the panic resumes on this thread.
+    pub fn wait(self) -> T {
+        match self.join() {
+            Ok(value) => value,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+
+    /// The job's result if it has run, or the handle back if it hasn't. Never blocks. The job's panic resumes, like `wait`.
+    pub fn try_wait(self) -> Result<T, Self> {
+        if self.is_finished() {
+            Ok(self.wait())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Like `wait`, but returns the job's panic rather than resuming it
+    pub(crate) fn join(self) -> thread::Result<T> {
+        self.scheduler.help_until(&self.completion);
+        self.result
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take()
+            .expect("A finished job has a result")
+    }
+}
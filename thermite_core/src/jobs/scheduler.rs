@@ -0,0 +1,463 @@
+/*
+    ABSTRACT: A work-stealing job system. Every worker thread owns a queue of jobs, and steals from the global
+    queue or from other workers once its own runs dry. Jobs can be scheduled after other jobs (dependencies), split
+    over slices or ranges (parallel-for), or kept for the main thread, which runs them once per frame (see `Systems::update`).
+
+    Every job runs within a profiler span named after it (see tools::profiler), on threads named `thermite-worker-<n>`.
+*/
+use crate::jobs::handle::{Completion, JobDependency, JobHandle};
+use crate::profile_scope;
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle, ThreadId},
+    time::Duration,
+};
+
+/// How long idle workers sleep before checking for work again, in case they missed being woken
+const IDLE_TIMEOUT: Duration = Duration::from_millis(10);
+/// How long a thread waiting for a job sleeps between looking for other jobs to run
+const WAIT_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// A job, ready to run
+type Task = Box<dyn FnOnce(&JobScheduler) + Send>;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum Target {
+    Workers,
+    MainThread,
+}
+
+/// A job waiting for its dependencies
+pub(crate) struct PendingJob {
+    /// Dependencies which haven't finished, plus one until the job is fully scheduled
+    remaining: AtomicUsize,
+    task: Mutex<Option<Task>>,
+    target: Target,
+}
+
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The local queue of a worker thread, and the id of the pool it belongs to
+    static LOCAL_QUEUE: RefCell<Option<(usize, Worker<Task>)>> = const { RefCell::new(None) };
+}
+
+struct Shared {
+    id: usize,
+    injector: Injector<Task>,
+    stealers: Vec<Stealer<Task>>,
+    main_queue: Mutex<VecDeque<Task>>,
+    main_thread: ThreadId,
+    sleep: Mutex<()>,
+    wake: Condvar,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn sleep(&self) -> MutexGuard<'_, ()> {
+        self.sleep
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn main_queue(&self) -> MutexGuard<'_, VecDeque<Task>> {
+        self.main_queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn has_queued_work(&self) -> bool {
+        !self.injector.is_empty() || self.stealers.iter().any(|stealer| !stealer.is_empty())
+    }
+}
+
+/// Schedules jobs on a `JobSystem`'s workers. Clones share the same workers, and can be sent to other threads (and jobs).
+#[derive(Clone)]
+pub struct JobScheduler {
+    shared: Arc<Shared>,
+}
+
+impl JobScheduler {
+    /// Schedules a job on the worker threads
+    pub fn spawn<T, F>(&self, name: &'static str, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.schedule(name, &[], Target::Workers, job)
+    }
+
+    /// Schedules a job on the worker threads, which runs once every dependency has finished (whether it succeeded or panicked)
+    pub fn spawn_after<T, F>(
+        &self,
+        name: &'static str,
+        dependencies: &[JobDependency],
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.schedule(name, dependencies, Target::Workers, job)
+    }
+
+    /// Schedules a job which runs on the main thread (e.g. for window or GPU work), once every dependency has finished.
+    ///
+    /// Main thread jobs run during `run_main_thread_jobs`, or while the main thread waits for a job.
+    pub fn spawn_on_main<T, F>(
+        &self,
+        name: &'static str,
+        dependencies: &[JobDependency],
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.schedule(name, dependencies, Target::MainThread, job)
+    }
+
+    /// Runs the main thread jobs which are ready, and returns how many ran. Jobs they schedule for the main thread wait for the next call.
+    ///
+    /// Does nothing when called from any other thread than the one which created the `JobSystem`.
+    pub fn run_main_thread_jobs(&self) -> usize {
+        if !self.is_main_thread() {
+            return 0;
+        }
+        let ready = self.shared.main_queue().len();
+        let mut ran = 0;
+        while ran < ready {
+            // Not locked while the job runs, since it may schedule other main thread jobs
+            let task = self.shared.main_queue().pop_front();
+            match task {
+                Some(task) => task(self),
+                None => break,
+            }
+            ran += 1;
+        }
+        ran
+    }
+
+    /// Calls `body` with every index in `0..len` on the worker threads, `chunk_size` indices per job, and waits for every call.
+    ///
+    /// The calling thread runs jobs too while it waits. If a call panics, the panic resumes once every other job has finished.
+    pub fn parallel_for<F>(&self, name: &'static str, len: usize, chunk_size: usize, body: F)
+    where
+        F: Fn(usize) + Sync,
+    {
+        self.run_chunks(
+            name,
+            chunk_ranges(len, chunk_size),
+            |range: Range<usize>| {
+                for idx in range {
+                    body(idx);
+                }
+            },
+        );
+    }
+
+    /// Calls `body` with every item of `items` on the worker threads, `chunk_size` items per job, and waits for every call.
+    ///
+    /// See `parallel_for`.
+    pub fn parallel_for_each_mut<T, F>(
+        &self,
+        name: &'static str,
+        items: &mut [T],
+        chunk_size: usize,
+        body: F,
+    ) where
+        T: Send,
+        F: Fn(&mut T) + Sync,
+    {
+        self.run_chunks(
+            name,
+            items.chunks_mut(chunk_size.max(1)).collect(),
+            |chunk: &mut [T]| {
+                for item in chunk {
+                    body(item);
+                }
+            },
+        );
+    }
+
+    /// How many worker threads run the jobs (main thread jobs aside)
+    pub fn worker_count(&self) -> usize {
+        self.shared.stealers.len()
+    }
+
+    /// Whether this is the thread which created the `JobSystem`
+    pub fn is_main_thread(&self) -> bool {
+        thread::current().id() == self.shared.main_thread
+    }
+
+    fn schedule<T, F>(
+        &self,
+        name: &'static str,
+        dependencies: &[JobDependency],
+        target: Target,
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let completion = Arc::new(Completion::new());
+        let result = Arc::new(Mutex::new(None));
+        let handle = JobHandle {
+            completion: completion.clone(),
+            result: result.clone(),
+            scheduler: self.clone(),
+        };
+        let task: Task = Box::new(move |scheduler| {
+            let outcome = {
+                profile_scope!(name);
+                panic::catch_unwind(AssertUnwindSafe(job))
+            };
+            *result
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(outcome);
+            for dependent in completion.finish() {
+                scheduler.release(dependent);
+            }
+        });
+        let pending = Arc::new(PendingJob {
+            remaining: AtomicUsize::new(dependencies.len() + 1),
+            task: Mutex::new(Some(task)),
+            target,
+        });
+        for dependency in dependencies {
+            if !dependency.completion.add_dependent(&pending) {
+                pending.remaining.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+        self.release(pending);
+        handle
+    }
+
+    /// Counts down one of the job's dependencies, and queues the job once none are left
+    fn release(&self, job: Arc<PendingJob>) {
+        if job.remaining.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        let task = job
+            .task
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        if let Some(task) = task {
+            self.push(task, job.target);
+        }
+    }
+
+    fn push(&self, task: Task, target: Target) {
+        match target {
+            Target::MainThread => self.shared.main_queue().push_back(task),
+            Target::Workers => {
+                // Jobs scheduled from a worker stay on that worker, unless another one steals them
+                let task = LOCAL_QUEUE.with(|local| match &*local.borrow() {
+                    Some((pool, queue)) if *pool == self.shared.id => {
+                        queue.push(task);
+                        None
+                    }
+                    _ => Some(task),
+                });
+                if let Some(task) = task {
+                    self.shared.injector.push(task);
+                }
+                let _sleep = self.shared.sleep();
+                self.shared.wake.notify_one();
+            }
+        }
+    }
+
+    /// The next job this thread should run: from its own queue if it's a worker, then the global queue, then other workers
+    fn find_task(&self) -> Option<Task> {
+        let local = LOCAL_QUEUE.with(|local| match &*local.borrow() {
+            Some((pool, queue)) if *pool == self.shared.id => Some(
+                queue
+                    .pop()
+                    .or_else(|| retry_steal(|| self.shared.injector.steal_batch_and_pop(queue))),
+            ),
+            _ => None,
+        });
+        match local {
+            Some(Some(task)) => Some(task),
+            Some(None) => self.steal_from_workers(),
+            None => {
+                retry_steal(|| self.shared.injector.steal()).or_else(|| self.steal_from_workers())
+            }
+        }
+    }
+
+    fn steal_from_workers(&self) -> Option<Task> {
+        self.shared
+            .stealers
+            .iter()
+            .find_map(|stealer| retry_steal(|| stealer.steal()))
+    }
+
+    /// Runs other jobs on this thread until the given job has finished
+    pub(crate) fn help_until(&self, completion: &Completion) {
+        while !completion.is_finished() {
+            let main_task = if self.is_main_thread() {
+                self.shared.main_queue().pop_front()
+            } else {
+                None
+            };
+            match main_task.or_else(|| self.find_task()) {
+                Some(task) => task(self),
+                None => completion.wait_timeout(WAIT_TIMEOUT),
+            }
+        }
+    }
+
+    /// Runs `body` on every chunk in parallel, and waits for every chunk even if one of them panics
+    fn run_chunks<C, F>(&self, name: &'static str, chunks: Vec<C>, body: F)
+    where
+        C: Send,
+        F: Fn(C) + Sync,
+    {
+        if chunks.len() <= 1 {
+            for chunk in chunks {
+                profile_scope!(name);
+                body(chunk);
+            }
+            return;
+        }
+        let body = &body;
+        let handles: Vec<JobHandle<()>> = chunks
+            .into_iter()
+            .map(|chunk| {
+                let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || body(chunk));
+                // SAFETY: the job borrows `body` and the chunk's data, which outlive it, since every job is
+                // waited for below (jobs catch their own panics, so waiting always completes) before returning
+                let job: Box<dyn FnOnce() + Send + 'static> = unsafe { std::mem::transmute(job) };
+                self.spawn(name, job)
+            })
+            .collect();
+        let mut first_panic = None;
+        for handle in handles {
+            if let Err(panic) = handle.join() {
+                first_panic.get_or_insert(panic);
+            }
+        }
+        if let Some(panic) = first_panic {
+            panic::resume_unwind(panic);
+        }
+    }
+}
+
+fn retry_steal<F: Fn() -> Steal<Task>>(steal: F) -> Option<Task> {
+    std::iter::repeat_with(steal)
+        .find(|steal| !steal.is_retry())
+        .and_then(|steal| steal.success())
+}
+
+fn chunk_ranges(len: usize, chunk_size: usize) -> Vec<Range<usize>> {
+    let chunk_size = chunk_size.max(1);
+    (0..len)
+        .step_by(chunk_size)
+        .map(|start| start..(start + chunk_size).min(len))
+        .collect()
+}
+
+/// Owns the worker threads jobs run on. Schedule jobs through its `JobScheduler`, see `scheduler`.
+///
+/// Dropping it runs the jobs which are already queued, then stops and joins every worker.
+pub struct JobSystem {
+    scheduler: JobScheduler,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Default for JobSystem {
+    /// A worker per logical core, except the one the main thread runs on
+    fn default() -> Self {
+        Self::new(Self::default_worker_count())
+    }
+}
+
+impl JobSystem {
+    /// Starts `worker_count` worker threads (at least one). The calling thread becomes the main thread, see `spawn_on_main`.
+    pub fn new(worker_count: usize) -> Self {
+        let queues: Vec<Worker<Task>> = (0..worker_count.max(1))
+            .map(|_| Worker::new_lifo())
+            .collect();
+        let scheduler = JobScheduler {
+            shared: Arc::new(Shared {
+                id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+                injector: Injector::new(),
+                stealers: queues.iter().map(|queue| queue.stealer()).collect(),
+                main_queue: Mutex::new(VecDeque::new()),
+                main_thread: thread::current().id(),
+                sleep: Mutex::new(()),
+                wake: Condvar::new(),
+                shutdown: AtomicBool::new(false),
+            }),
+        };
+        let workers = queues
+            .into_iter()
+            .enumerate()
+            .map(|(idx, queue)| {
+                let scheduler = scheduler.clone();
+                thread::Builder::new()
+                    .name(format!("thermite-worker-{}", idx))
+                    .spawn(move || run_worker(scheduler, queue))
+                    .expect("Couldn't spawn a worker thread")
+            })
+            .collect();
+        Self { scheduler, workers }
+    }
+
+    /// One less than the number of logical cores, and at least one
+    pub fn default_worker_count() -> usize {
+        num_cpus::get().saturating_sub(1).max(1)
+    }
+
+    pub fn scheduler(&self) -> &JobScheduler {
+        &self.scheduler
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        self.scheduler
+            .shared
+            .shutdown
+            .store(true, Ordering::Release);
+        {
+            let _sleep = self.scheduler.shared.sleep();
+            self.scheduler.shared.wake.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_worker(scheduler: JobScheduler, queue: Worker<Task>) {
+    let shared = scheduler.shared.clone();
+    LOCAL_QUEUE.with(|local| *local.borrow_mut() = Some((shared.id, queue)));
+    loop {
+        if let Some(task) = scheduler.find_task() {
+            task(&scheduler);
+            continue;
+        }
+        let sleep = shared.sleep();
+        if shared.shutdown.load(Ordering::Acquire) {
+            break;
+        }
+        // Jobs are queued before waking anyone, so checking under the lock means no wake up is missed
+        if !shared.has_queued_work() {
+            let _ = shared.wake.wait_timeout(sleep, IDLE_TIMEOUT);
+        }
+    }
+    LOCAL_QUEUE.with(|local| *local.borrow_mut() = None);
+}
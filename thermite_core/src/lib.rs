@@ -1,9 +1,49 @@
-// Re-export simple_logger as thermite_logging to be used by clients of thermite_core
-// TODO: Explore if simple_logger is actually performant enough for this project.
-pub use simple_logger as thermite_logging;
-
 // thermite_core native modules
+pub mod assets;
+pub mod console;
 pub mod input;
+pub mod jobs;
+pub mod logging;
 pub mod messaging;
 pub mod platform;
 pub mod tools;
+
+// Profiling macros live here rather than in tools::profiler, which only exists with the `profiler` feature
+/// Times the rest of the enclosing scope under the given name, nested in the spans already open on this thread.
+///
+/// Compiles to nothing without the `profiler` feature.
+///
+/// ```ignore
+/// profile_scope!("record_cmds_for_submission");
+/// ```
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::tools::profiler::ProfileScope::new($name);
+    };
+}
+
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! profile_scope {
+    // The name is still evaluated, so that names only used for profiling don't warn
+    ($name:expr) => {
+        let _ = $name;
+    };
+}
+
+/// Marks the end of a frame, see `tools::profiler::new_frame`. Compiles to nothing without the `profiler` feature.
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! profile_frame {
+    () => {
+        $crate::tools::profiler::new_frame()
+    };
+}
+
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! profile_frame {
+    () => {};
+}
@@ -0,0 +1,209 @@
+/*
+    ABSTRACT: The engine's `log` backend. Records are filtered per module, copied into the ring buffer,
+    then sent to a writer thread which hands them to every sink, so that logging never waits on I/O.
+*/
+use crate::logging::{
+    record::LogRecord,
+    sinks::{ConsoleSink, FileSink, LogRingBuffer, LogSink},
+};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::{
+    io,
+    path::Path,
+    sync::{
+        mpsc::{self, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+enum LogMessage {
+    Record(LogRecord),
+    /// Flushes every sink, then acknowledges on the given channel
+    Flush(Sender<()>),
+}
+
+/// Builds and installs the engine's logger
+pub struct LoggerBuilder {
+    level: LevelFilter,
+    module_levels: Vec<(String, LevelFilter)>,
+    sinks: Vec<Box<dyn LogSink>>,
+    ring_buffer_capacity: usize,
+}
+
+impl Default for LoggerBuilder {
+    /// Logs `Info` and above, with no sinks, and keeps the last 256 records in memory
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            module_levels: vec![],
+            sinks: vec![],
+            ring_buffer_capacity: 256,
+        }
+    }
+}
+
+impl LoggerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the level of every module without a level of its own
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the level of a module and its submodules (e.g. `thermite_gfx::hal`). The most specific module wins.
+    pub fn with_module_level<T: Into<String>>(mut self, module: T, level: LevelFilter) -> Self {
+        self.module_levels.push((module.into(), level));
+        self
+    }
+
+    pub fn with_sink<S: LogSink + 'static>(mut self, sink: S) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Adds a `ConsoleSink`
+    pub fn with_console(self) -> Self {
+        self.with_sink(ConsoleSink::default())
+    }
+
+    /// Adds a `FileSink` writing to the given file, rotating it past `max_bytes` and keeping `max_files` old files
+    pub fn with_file(self, path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        Ok(self.with_sink(FileSink::new(path, max_bytes, max_files)?))
+    }
+
+    /// Sets how many of the last records are kept in memory, see `LoggerHandle::ring_buffer`
+    pub fn with_ring_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.ring_buffer_capacity = capacity;
+        self
+    }
+
+    /// Starts the writer thread and installs the logger as the `log` backend. Can only succeed once per process.
+    pub fn init(self) -> Result<LoggerHandle, SetLoggerError> {
+        let (sender, receiver) = mpsc::channel();
+        let mut sinks = self.sinks;
+        let ring_buffer = LogRingBuffer::new(self.ring_buffer_capacity);
+        let mut module_levels = self.module_levels;
+        // Longest (most specific) modules first, so the first match wins
+        module_levels.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        let max_level = module_levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, std::cmp::max);
+        let logger = Logger {
+            level: self.level,
+            module_levels,
+            start: Instant::now(),
+            sender: Mutex::new(sender.clone()),
+            ring_buffer: ring_buffer.clone(),
+        };
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(max_level);
+        thread::Builder::new()
+            .name(String::from("thermite-logger"))
+            .spawn(move || {
+                for message in receiver {
+                    match message {
+                        LogMessage::Record(record) => {
+                            for sink in sinks.iter_mut() {
+                                sink.write(&record);
+                            }
+                        }
+                        LogMessage::Flush(ack) => {
+                            for sink in sinks.iter_mut() {
+                                sink.flush();
+                            }
+                            let _ = ack.send(());
+                        }
+                    }
+                }
+            })
+            .expect("Couldn't spawn the logger's writer thread");
+        Ok(LoggerHandle {
+            sender,
+            ring_buffer,
+        })
+    }
+}
+
+/// Logs to the console at `Info` and above, see `LoggerBuilder` for more control
+pub fn init() -> Result<LoggerHandle, SetLoggerError> {
+    LoggerBuilder::new().with_console().init()
+}
+
+struct Logger {
+    level: LevelFilter,
+    module_levels: Vec<(String, LevelFilter)>,
+    start: Instant,
+    sender: Mutex<Sender<LogMessage>>,
+    ring_buffer: LogRingBuffer,
+}
+
+impl Logger {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.module_levels
+            .iter()
+            .find(|(module, _)| {
+                target.starts_with(module.as_str())
+                    && matches!(target[module.len()..].chars().next(), None | Some(':'))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn send(&self, message: LogMessage) {
+        if let Ok(sender) = self.sender.lock() {
+            let _ = sender.send(message);
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let record = LogRecord::from_record(record, self.start.elapsed());
+        self.ring_buffer.push(record.clone());
+        self.send(LogMessage::Record(record));
+    }
+
+    fn flush(&self) {
+        let (ack_sender, ack) = mpsc::channel();
+        self.send(LogMessage::Flush(ack_sender));
+        let _ = ack.recv_timeout(Duration::from_secs(1));
+    }
+}
+
+/// A handle on the installed logger
+pub struct LoggerHandle {
+    sender: Sender<LogMessage>,
+    ring_buffer: LogRingBuffer,
+}
+
+impl LoggerHandle {
+    /// The last records logged, kept in memory
+    pub fn ring_buffer(&self) -> &LogRingBuffer {
+        &self.ring_buffer
+    }
+
+    /// Waits (up to `timeout`) until every record logged so far has been written by every sink
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let (ack_sender, ack) = mpsc::channel();
+        self.sender.send(LogMessage::Flush(ack_sender)).is_ok() && ack.recv_timeout(timeout).is_ok()
+    }
+}
+
+impl Drop for LoggerHandle {
+    /// Makes sure buffered records make it to the sinks before the application exits
+    fn drop(&mut self) {
+        self.flush(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,6 @@
+// Re-export log, so that `log_kv!` works in crates which don't depend on it directly
+pub use log;
+
+pub mod logger;
+pub mod record;
+pub mod sinks;
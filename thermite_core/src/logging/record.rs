@@ -0,0 +1,111 @@
+/*
+    ABSTRACT: An owned copy of a `log::Record`, including its key/value fields, so that it can be sent
+    to the logger's writer thread and kept in memory. Also provides `log_kv!` to log with fields.
+*/
+use log::{
+    kv::{self, Key, Source, Value, Visitor},
+    Level, Record,
+};
+use std::{fmt, thread, time::Duration};
+
+/// An owned log record
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// Key/value fields attached to the record, see `log_kv!`
+    pub fields: Vec<(String, String)>,
+    /// Time since the logger was installed
+    pub time: Duration,
+    pub thread: Option<String>,
+}
+
+impl LogRecord {
+    pub fn from_record(record: &Record, time: Duration) -> Self {
+        let mut fields = FieldCollector(vec![]);
+        // Collecting into a Vec can't fail
+        let _ = record.key_values().visit(&mut fields);
+        Self {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            fields: fields.0,
+            time,
+            thread: thread::current().name().map(|name| name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for LogRecord {
+    /// `[   12.345s] WARN  [main] thermite_gfx::hal: Message key=value key=value`
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "[{:>6}.{:03}s] {:<5} [{}] {}: {}",
+            self.time.as_secs(),
+            self.time.subsec_millis(),
+            self.level,
+            self.thread.as_deref().unwrap_or("<unnamed>"),
+            self.target,
+            self.message
+        )?;
+        for (key, value) in &self.fields {
+            write!(fmt, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+struct FieldCollector(Vec<(String, String)>);
+
+impl<'kvs> Visitor<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+/// Logs a record with key/value fields through the `log` facade. Used by `log_kv!`.
+pub fn log_with_fields(
+    level: Level,
+    target: &str,
+    location: (&'static str, &'static str, u32),
+    args: fmt::Arguments,
+    fields: &[(&str, &dyn kv::ToValue)],
+) {
+    let (module_path, file, line) = location;
+    let fields: &dyn Source = &fields;
+    log::logger().log(
+        &Record::builder()
+            .level(level)
+            .target(target)
+            .module_path_static(Some(module_path))
+            .file_static(Some(file))
+            .line(Some(line))
+            .args(args)
+            .key_values(fields)
+            .build(),
+    );
+}
+
+/// Logs a message along with key/value fields, which sinks keep separate from the message.
+///
+/// ```ignore
+/// log_kv!(Level::Warn, { "frame" => frame, "ms" => frame_ms }, "Frame took too long");
+/// ```
+#[macro_export]
+macro_rules! log_kv {
+    ($lvl:expr, { $($key:literal => $value:expr),* $(,)? }, $($arg:tt)+) => {{
+        let lvl = $lvl;
+        if lvl <= $crate::logging::log::STATIC_MAX_LEVEL && lvl <= $crate::logging::log::max_level() {
+            $crate::logging::record::log_with_fields(
+                lvl,
+                module_path!(),
+                (module_path!(), file!(), line!()),
+                format_args!($($arg)+),
+                &[$(($key, &$value as &dyn $crate::logging::log::kv::ToValue)),*],
+            );
+        }
+    }};
+}
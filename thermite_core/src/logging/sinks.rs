@@ -0,0 +1,169 @@
+/*
+    ABSTRACT: Destinations for log records. Sinks run on the logger's writer thread (see logger.rs),
+    except for the ring buffer, which is filled as records are logged so that it's always up to date.
+*/
+use crate::logging::record::LogRecord;
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// A destination for log records
+pub trait LogSink: Send {
+    fn write(&mut self, record: &LogRecord);
+    fn flush(&mut self);
+}
+
+/// Writes records to the standard output
+#[derive(Default)]
+pub struct ConsoleSink {}
+
+impl LogSink for ConsoleSink {
+    fn write(&mut self, record: &LogRecord) {
+        let _ = writeln!(io::stdout(), "{}", record);
+    }
+
+    fn flush(&mut self) {
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Writes records to a file through a buffer, rotating it when it grows past a maximum size.
+///
+/// When rotating, `log.txt` becomes `log.txt.1`, `log.txt.1` becomes `log.txt.2`, and so on, up to `max_files` old files.
+pub struct FileSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    written_bytes: u64,
+    writer: Option<BufWriter<File>>,
+}
+
+impl FileSink {
+    /// Opens (or creates) the given file, appending to it
+    pub fn new(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            written_bytes: file.metadata()?.len(),
+            writer: Some(BufWriter::new(file)),
+        })
+    }
+
+    fn rotated_path(&self, idx: usize) -> PathBuf {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(format!(".{}", idx));
+        PathBuf::from(rotated)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for idx in (1..self.max_files).rev() {
+                let from = self.rotated_path(idx);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(idx + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.writer = Some(BufWriter::new(File::create(&self.path)?));
+        self.written_bytes = 0;
+        Ok(())
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&mut self, record: &LogRecord) {
+        let line = format!("{}\n", record);
+        if self.written_bytes + line.len() as u64 > self.max_bytes && self.written_bytes > 0 {
+            if let Err(err) = self.rotate() {
+                eprintln!("Couldn't rotate log file {}: {}", self.path.display(), err);
+            }
+        }
+        if let Some(writer) = &mut self.writer {
+            if writer.write_all(line.as_bytes()).is_ok() {
+                self.written_bytes += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// The last records logged, kept in memory (e.g. for the console, or crash reports).
+///
+/// Clones share the same buffer.
+#[derive(Clone)]
+pub struct LogRingBuffer {
+    records: Arc<Mutex<VecDeque<LogRecord>>>,
+    capacity: usize,
+}
+
+impl LogRingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Adds a record, dropping the oldest one if the buffer is full
+    pub fn push(&self, record: LogRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        // A poisoned buffer is still a valid buffer, the records are all we're after
+        let mut records = self
+            .records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Copies of the buffered records, from oldest to newest
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Copies of the last `count` buffered records, from oldest to newest
+    pub fn last(&self, count: usize) -> Vec<LogRecord> {
+        let records = self
+            .records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        records
+            .iter()
+            .skip(records.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
@@ -7,6 +7,7 @@ use crate::messaging::{
     event::{Event, TSEvent},
     subscribe::{Subscriber, TSSubscriber},
 };
+use crate::profile_scope;
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::rc::{Rc, Weak};
@@ -25,8 +26,8 @@ unsafe impl Sync for BusRequest {}
 
 /// The end result of the `EventBus`'s `dispatch_event` method, which results in one of the following:
 ///
-///     1. `Stopped`: The event was handled by some subscribers in the list, but propagation was halted before the end of the list.
-///     2. `Finished`: The event was handled by every subscriber in the list.
+/// 1. `Stopped`: The event was handled by some subscribers in the list, but propagation was halted before the end of the list.
+/// 2. `Finished`: The event was handled by every subscriber in the list.
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum EventDispatchResult {
     Stopped,
@@ -54,14 +55,14 @@ where
                 BusRequest::NoActionNeeded => idx += 1,
                 // The rest are self explanatory
                 BusRequest::Unsubscribe => {
-                    // swap_remove for O(1) operation
-                    subscribers.swap_remove(idx); // TODO: If we move to layers, we can't arbitrarily alter the order here like this...
+                    // Preserve the order of the remaining subscribers, since things like a `LayerStack` rely on it
+                    subscribers.remove(idx);
                 }
                 BusRequest::DoNotPropagate => {
                     return EventDispatchResult::Stopped;
                 }
                 BusRequest::UnsubscribeAndDoNotPropagate => {
-                    subscribers.swap_remove(idx);
+                    subscribers.remove(idx);
                     return EventDispatchResult::Stopped;
                 }
             }
@@ -74,6 +75,20 @@ where
 
 //===================================================== NON THREAD SAFE =====================================================//
 
+/// Counters kept by an `EventBus`, for debugging tools (e.g. the console's `bus_stats` command)
+#[derive(Debug, Clone)]
+pub struct EventBusStats<T>
+where
+    T: Eq + PartialEq + Hash + Clone,
+{
+    /// How many events of each category were dispatched
+    pub dispatched: HashMap<T, u64>,
+    /// How many live subscribers each category has
+    pub subscribers: HashMap<T, usize>,
+    /// How many dispatches were stopped by a subscriber before reaching every subscriber
+    pub stopped: u64,
+}
+
 /// Single-thread datastructure responsible for dispatching events from `Publisher`s to `Subscriber`s
 ///
 /// This keeps the respective Pub/Sub systems decoupled from each other
@@ -87,6 +102,8 @@ where
     // We hold a std::rc::Weak (Rc which holds non-owning reference) to not prevent dropping and to avoid circular references to an Rc
     // We can deal with subscribers that get dropped by just removing them from our map if we find they did get dropped
     channels: HashMap<T, Vec<Weak<dyn Subscriber<T, E>>>>,
+    dispatched: HashMap<T, u64>,
+    stopped: u64,
 }
 
 impl<T, E> Default for EventBus<T, E>
@@ -97,6 +114,8 @@ where
     fn default() -> Self {
         Self {
             channels: HashMap::default(),
+            dispatched: HashMap::default(),
+            stopped: 0,
         }
     }
 }
@@ -135,12 +154,15 @@ where
 
     /// Dispatches the given event to all subscribers of that event's category
     pub fn dispatch_event(&mut self, event: &E) {
+        profile_scope!("EventBus::dispatch_event");
+        let category = event.category();
+        *self.dispatched.entry(category.clone()).or_insert(0) += 1;
         // Grab our list of subscribers for this event's category, if one exists
-        if let Some(subscriber_list) = self.channels.get_mut(&event.category()) {
+        if let Some(subscriber_list) = self.channels.get_mut(&category) {
             // For every subscriber in that list, handle the event after which that subscriber will
             // tell the bus whether or not it should propagate the event to other subscribers, among other actions
             // TODO: In order for this to make sense, our subscribers need to be ordered in a fashion that makes sense for event propagation (layers)
-            execute_bus_requests(subscriber_list, |weak_subscriber| {
+            let result = execute_bus_requests(subscriber_list, |weak_subscriber| {
                 // Upgrade our weak rc pointer to a full Arc, obtain a write lock and handle the event
                 if let Some(subscriber) = weak_subscriber.upgrade() {
                     subscriber.on_event(event)
@@ -150,6 +172,28 @@ where
                     // TODO: Clean up dropped subscriber
                 }
             });
+            if result == EventDispatchResult::Stopped {
+                self.stopped += 1;
+            }
+        }
+    }
+
+    /// A snapshot of this bus's counters
+    pub fn stats(&self) -> EventBusStats<T> {
+        EventBusStats {
+            dispatched: self.dispatched.clone(),
+            subscribers: self
+                .channels
+                .iter()
+                .map(|(category, subscribers)| {
+                    let live = subscribers
+                        .iter()
+                        .filter(|subscriber| subscriber.strong_count() > 0)
+                        .count();
+                    (category.clone(), live)
+                })
+                .collect(),
+            stopped: self.stopped,
         }
     }
 }
@@ -222,6 +266,7 @@ where
 
     /// Dispatches the given event to all subscribers of that event's category
     pub fn dispatch_event(&mut self, event: &E) {
+        profile_scope!("TSEventBus::dispatch_event");
         // Grab our list of subscribers for this event's category, if one exists
         if let Some(subscriber_list) = self.channels.get_mut(&event.category()) {
             // For every subscriber in that list, handle the event after which that subscriber will
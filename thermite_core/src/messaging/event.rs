@@ -2,7 +2,19 @@
     ABSTRACT: Definitions of single-thread and thread-safe generic events
     to be handled by their respective publishers, subscribers, and event buses.
 */
-use crate::input::{keyboard::KeyboardEvent, mouse::MouseEvent};
+use crate::assets::server::AssetEvent;
+use crate::console::system::ConsoleEvent;
+use crate::input::{
+    context::{ActionEvent, InputContextEvent},
+    gesture::GestureEvent,
+    keyboard::KeyboardEvent,
+    mouse::MouseEvent,
+};
+use crate::platform::{
+    app::AppEvent,
+    window::{WindowEvent, WindowId},
+};
+use crate::tools::{config::ConfigEvent, timer::TimerEvent};
 use std::hash::Hash;
 
 /// A generic, single-thread `Event`, categorized by an enum category `T`, meant to be implemented as an enum by the module consumer.
@@ -31,6 +43,11 @@ where
 pub enum ThermiteEventType {
     Input,
     Window,
+    Application,
+    Config,
+    Console,
+    Timer,
+    Asset,
 }
 // unsafe impl Send for ThermiteEventType {}
 // unsafe impl Sync for ThermiteEventType {}
@@ -40,6 +57,18 @@ pub enum ThermiteEventType {
 pub enum ThermiteEvent {
     Keyboard(KeyboardEvent),
     Mouse(MouseEvent),
+    Action(ActionEvent),
+    InputContext(InputContextEvent),
+    Gesture(GestureEvent),
+    Window {
+        window_id: WindowId,
+        event: WindowEvent,
+    },
+    App(AppEvent),
+    Config(ConfigEvent),
+    Console(ConsoleEvent),
+    Timer(TimerEvent),
+    Asset(AssetEvent),
 }
 // unsafe impl Send for ThermiteEvent {}
 // unsafe impl Sync for ThermiteEvent {}
@@ -48,6 +77,15 @@ impl Event<ThermiteEventType> for ThermiteEvent {
         match self {
             ThermiteEvent::Keyboard(_) => ThermiteEventType::Input,
             ThermiteEvent::Mouse(_) => ThermiteEventType::Input,
+            ThermiteEvent::Action(_) => ThermiteEventType::Input,
+            ThermiteEvent::InputContext(_) => ThermiteEventType::Input,
+            ThermiteEvent::Gesture(_) => ThermiteEventType::Input,
+            ThermiteEvent::Window { .. } => ThermiteEventType::Window,
+            ThermiteEvent::App(_) => ThermiteEventType::Application,
+            ThermiteEvent::Config(_) => ThermiteEventType::Config,
+            ThermiteEvent::Console(_) => ThermiteEventType::Console,
+            ThermiteEvent::Timer(_) => ThermiteEventType::Timer,
+            ThermiteEvent::Asset(_) => ThermiteEventType::Asset,
             // And more...
         }
     }
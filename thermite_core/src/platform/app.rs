@@ -0,0 +1,385 @@
+/*
+    ABSTRACT: The pieces of an application which don't depend on a window: user-provided systems and their
+    lifecycle hooks, the context they're given access to (bus, timing, timers, input contexts and gestures, configuration, jobs, files, assets),
+    and lifecycle events.
+    The loop which drives them lives with the window (see thermite_gfx's app.rs), or in headless.rs.
+*/
+use crate::assets::{server::AssetServer, vfs::Vfs};
+use crate::input::{context::InputContextStack, gesture::GestureRecognizer};
+use crate::jobs::scheduler::{JobScheduler, JobSystem};
+use crate::messaging::{
+    bus::{BusRequest, EventBus, EventDispatchResult},
+    event::{ThermiteEvent, ThermiteEventType},
+    publish::Publisher,
+    subscribe::Subscriber,
+};
+use crate::tools::{
+    config::ConfigRegistry,
+    timer::{FixedTimestep, Time, Timers},
+};
+use crate::{profile_frame, profile_scope};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The engine's single-threaded event bus, carrying `ThermiteEvent`s
+pub type ThermiteEventBus = EventBus<ThermiteEventType, ThermiteEvent>;
+
+/// Application lifecycle events, published on the bus under `ThermiteEventType::Application`
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum AppEvent {
+    /// Every system has been initialized, and the loop is about to start
+    Initialized,
+    /// The application has been suspended by the platform (e.g. sent to the background on mobile)
+    Suspended,
+    /// The application has been resumed by the platform after a suspension
+    Resumed,
+    /// The fixed-rate simulation couldn't keep up with real time, and dropped this many steps (see `FixedTimestep`)
+    FellBehind { dropped_steps: u32 },
+    /// Something requested that the application exits, it will shut down at the end of this iteration of the loop
+    ExitRequested,
+    /// The loop has ended, and every system is about to be shut down
+    Shutdown,
+}
+
+impl From<AppEvent> for ThermiteEvent {
+    fn from(app_evt: AppEvent) -> Self {
+        ThermiteEvent::App(app_evt)
+    }
+}
+
+/// Notices `AppEvent::ExitRequested` when it's published on the bus directly, rather than through `AppContext::request_exit`
+#[derive(Default)]
+struct ExitListener {
+    requested: Cell<bool>,
+}
+
+impl Subscriber<ThermiteEventType, ThermiteEvent> for ExitListener {
+    fn on_event(&self, event: &ThermiteEvent) -> BusRequest {
+        if let ThermiteEvent::App(AppEvent::ExitRequested) = event {
+            self.requested.set(true);
+        }
+        BusRequest::NoActionNeeded
+    }
+}
+
+/// Everything the application exposes to its `System`s
+pub struct AppContext {
+    event_bus: Rc<RefCell<ThermiteEventBus>>,
+    input_contexts: Rc<RefCell<InputContextStack>>,
+    gestures: Rc<RefCell<GestureRecognizer>>,
+    config: Rc<RefCell<ConfigRegistry>>,
+    time: Time,
+    fixed_timestep: FixedTimestep,
+    timers: Timers,
+    jobs: JobSystem,
+    vfs: Arc<Vfs>,
+    assets: AssetServer,
+    exit_requested: bool,
+    exit_listener: Rc<ExitListener>,
+}
+
+impl Default for AppContext {
+    fn default() -> Self {
+        Self::new(ConfigRegistry::default())
+    }
+}
+
+impl Publisher<ThermiteEventType, ThermiteEvent> for AppContext {}
+
+impl AppContext {
+    /// A context sharing the given configuration with the application's systems, with the default `JobSystem` and VFS mounts
+    pub fn new(config: ConfigRegistry) -> Self {
+        Self::with_jobs(config, JobSystem::default())
+    }
+
+    /// A context sharing the given configuration and job system with the application's systems
+    pub fn with_jobs(config: ConfigRegistry, jobs: JobSystem) -> Self {
+        let vfs = Arc::new(Vfs::with_default_mounts(None));
+        let assets = AssetServer::new(vfs.clone(), jobs.scheduler().clone());
+        // Subscribed first, so no other subscriber can stop an exit request before it's noticed
+        let exit_listener = Rc::new(ExitListener::default());
+        let mut event_bus = ThermiteEventBus::default();
+        event_bus.subscribe(&exit_listener, ThermiteEventType::Application);
+        Self {
+            event_bus: Rc::new(RefCell::new(event_bus)),
+            input_contexts: Rc::new(RefCell::new(InputContextStack::default())),
+            gestures: Rc::new(RefCell::new(GestureRecognizer::default())),
+            config: Rc::new(RefCell::new(config)),
+            time: Time::default(),
+            fixed_timestep: FixedTimestep::default(),
+            timers: Timers::default(),
+            jobs,
+            vfs,
+            assets,
+            exit_requested: false,
+            exit_listener,
+        }
+    }
+
+    /// The application's event bus, for subscribing
+    pub fn event_bus(&self) -> &Rc<RefCell<ThermiteEventBus>> {
+        &self.event_bus
+    }
+
+    /// The application's input context stack
+    pub fn input_contexts(&self) -> &Rc<RefCell<InputContextStack>> {
+        &self.input_contexts
+    }
+
+    /// The application's gesture recognizer, fed the input no context consumed (see `route_input`)
+    pub fn gestures(&self) -> &Rc<RefCell<GestureRecognizer>> {
+        &self.gestures
+    }
+
+    /// The application's configuration registry
+    pub fn config(&self) -> &Rc<RefCell<ConfigRegistry>> {
+        &self.config
+    }
+
+    /// The application's frame timing, ticked once per update
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    /// Mutable access to the application's frame timing, for the loop driving the application
+    pub fn time_mut(&mut self) -> &mut Time {
+        &mut self.time
+    }
+
+    /// The application's named timers, ticked with game time once per update
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    /// Mutable access to the application's named timers, e.g. to add one
+    pub fn timers_mut(&mut self) -> &mut Timers {
+        &mut self.timers
+    }
+
+    /// The application's fixed-rate simulation timing, advanced once per update
+    pub fn fixed_timestep(&self) -> &FixedTimestep {
+        &self.fixed_timestep
+    }
+
+    /// Mutable access to the application's fixed-rate simulation timing, e.g. to change its rate
+    pub fn fixed_timestep_mut(&mut self) -> &mut FixedTimestep {
+        &mut self.fixed_timestep
+    }
+
+    /// Schedules work on the application's worker threads, or on the main thread (see `Systems::update`)
+    pub fn jobs(&self) -> &JobScheduler {
+        self.jobs.scheduler()
+    }
+
+    /// The application's virtual file system, for loading assets (see `Resource::in_vfs`)
+    pub fn vfs(&self) -> &Arc<Vfs> {
+        &self.vfs
+    }
+
+    /// Replaces the application's virtual file system, e.g. after mounting mods. Assets already loaded are kept.
+    pub fn set_vfs(&mut self, vfs: Vfs) {
+        self.vfs = Arc::new(vfs);
+        self.assets.set_vfs(self.vfs.clone());
+    }
+
+    /// Loads assets through the application's VFS, on its worker threads
+    pub fn assets(&self) -> &AssetServer {
+        &self.assets
+    }
+
+    /// Mutable access to the application's asset server, e.g. to turn hot reloading on
+    pub fn assets_mut(&mut self) -> &mut AssetServer {
+        &mut self.assets
+    }
+
+    /// How far between the last two fixed simulation steps the current frame is, for interpolating what's rendered
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.fixed_timestep.alpha()
+    }
+
+    /// Publishes the given event on the application's event bus
+    pub fn publish(&self, event: ThermiteEvent) {
+        self.publish_event(
+            &event,
+            &mut self
+                .event_bus
+                .try_borrow_mut()
+                .expect("Couldn't borrow the event bus as mutable"),
+        );
+    }
+
+    /// Routes an input event through the input context stack, and publishes it on the bus if no context consumed it,
+    /// along with the `GestureEvent`s it completed (timed with the `Time`'s clock)
+    pub fn route_input(&self, event: ThermiteEvent) {
+        let mut bus = self
+            .event_bus
+            .try_borrow_mut()
+            .expect("Couldn't borrow the event bus as mutable");
+        let result = self
+            .input_contexts
+            .try_borrow()
+            .expect("Couldn't borrow the input context stack")
+            .route(&event, &mut bus);
+        if result == EventDispatchResult::Finished {
+            self.publish_event(&event, &mut bus);
+            let gestures = self
+                .gestures
+                .try_borrow_mut()
+                .expect("Couldn't borrow the gesture recognizer as mutable")
+                .handle_event(&event, self.time.clock().now());
+            for gesture in gestures {
+                self.publish_event(&gesture.into(), &mut bus);
+            }
+        }
+    }
+
+    /// Asks the application to exit at the end of the current iteration of its loop, and publishes `AppEvent::ExitRequested`
+    pub fn request_exit(&mut self) {
+        if !self.exit_requested() {
+            self.exit_requested = true;
+            self.publish(AppEvent::ExitRequested.into());
+        }
+    }
+
+    /// Whether something has asked the application to exit, through `request_exit` or by publishing `AppEvent::ExitRequested` on the bus
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested || self.exit_listener.requested.get()
+    }
+}
+
+/// A user-provided piece of the application, driven by the application's loop through these hooks.
+///
+/// Every hook has an empty default implementation, so systems only implement what they need.
+pub trait System {
+    /// Called once, before the loop starts
+    fn on_init(&mut self, _ctx: &mut AppContext) {}
+    /// Called zero or more times per iteration of the loop, at a fixed rate, with the fixed step length in seconds.
+    ///
+    /// Deterministic simulation (physics, networking) belongs here. See `AppContext::fixed_timestep`.
+    fn on_fixed_update(&mut self, _ctx: &mut AppContext, _step_sec: f32) {}
+    /// Called once per iteration of the loop, after any fixed updates, with the frame time in seconds
+    fn on_update(&mut self, _ctx: &mut AppContext, _delta_sec: f32) {}
+    /// Called whenever the application should draw a frame
+    fn on_render(&mut self, _ctx: &mut AppContext) {}
+    /// Called when the platform suspends the application
+    fn on_suspend(&mut self, _ctx: &mut AppContext) {}
+    /// Called when the platform resumes the application after a suspension
+    fn on_resume(&mut self, _ctx: &mut AppContext) {}
+    /// Called once, after the loop has ended
+    fn on_shutdown(&mut self, _ctx: &mut AppContext) {}
+}
+
+/// An ordered collection of `System`s, which runs each lifecycle hook on every system and publishes the matching `AppEvent`s.
+///
+/// Systems are initialized and updated in the order they were added, and shut down in reverse order.
+#[derive(Default)]
+pub struct Systems {
+    systems: Vec<Box<dyn System>>,
+}
+
+impl Systems {
+    /// Adds a system after every existing one
+    pub fn add(&mut self, system: Box<dyn System>) {
+        self.systems.push(system);
+    }
+
+    /// The number of systems in this collection
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    /// Whether this collection has no systems
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    pub fn init(&mut self, ctx: &mut AppContext) {
+        for system in self.systems.iter_mut() {
+            system.on_init(ctx);
+        }
+        ctx.publish(AppEvent::Initialized.into());
+    }
+
+    /// Ticks the context's `Time`, runs as many fixed updates as the (game) frame time allows, then updates every system with the new frame time.
+    ///
+    /// Publishes `AppEvent::FellBehind` if fixed steps had to be dropped.
+    /// Watched configuration files are reloaded first, if they changed, and their `ConfigEvent`s published.
+    /// Then the main thread jobs which are ready run (see `JobScheduler::spawn_on_main`), the `AssetEvent`s since the last update
+    /// are published, held inputs are checked for gestures, and the context's `Timers` are ticked.
+    pub fn update(&mut self, ctx: &mut AppContext) {
+        profile_frame!();
+        ctx.time_mut().tick();
+        self.run_updates(ctx);
+    }
+
+    /// Same as `update`, without ticking the context's `Time`, for loops which drive time themselves
+    pub fn run_updates(&mut self, ctx: &mut AppContext) {
+        profile_scope!("Systems::run_updates");
+        let now = ctx.time().clock().now();
+        let config_events = ctx
+            .config()
+            .try_borrow_mut()
+            .expect("Couldn't borrow the configuration registry as mutable")
+            .poll_watched_files(now);
+        for evt in config_events {
+            ctx.publish(evt.into());
+        }
+        ctx.jobs().run_main_thread_jobs();
+        AssetServer::update(ctx);
+        let holds = ctx
+            .gestures()
+            .try_borrow_mut()
+            .expect("Couldn't borrow the gesture recognizer as mutable")
+            .update(now);
+        for hold in holds {
+            ctx.publish(hold.into());
+        }
+        Timers::update(ctx);
+        let delta = ctx.time().delta();
+        let steps = ctx.fixed_timestep_mut().advance(delta);
+        if ctx.fixed_timestep().is_behind() {
+            let dropped_steps = ctx.fixed_timestep().dropped_steps();
+            ctx.publish(AppEvent::FellBehind { dropped_steps }.into());
+        }
+        let step_sec = ctx.fixed_timestep().step_sec();
+        for _ in 0..steps {
+            profile_scope!("Systems::fixed_update");
+            for system in self.systems.iter_mut() {
+                system.on_fixed_update(ctx, step_sec);
+            }
+        }
+        let delta_sec = ctx.time().delta_sec();
+        for system in self.systems.iter_mut() {
+            system.on_update(ctx, delta_sec);
+        }
+    }
+
+    pub fn render(&mut self, ctx: &mut AppContext) {
+        profile_scope!("Systems::render");
+        for system in self.systems.iter_mut() {
+            system.on_render(ctx);
+        }
+    }
+
+    pub fn suspend(&mut self, ctx: &mut AppContext) {
+        ctx.publish(AppEvent::Suspended.into());
+        for system in self.systems.iter_mut() {
+            system.on_suspend(ctx);
+        }
+    }
+
+    pub fn resume(&mut self, ctx: &mut AppContext) {
+        for system in self.systems.iter_mut() {
+            system.on_resume(ctx);
+        }
+        ctx.publish(AppEvent::Resumed.into());
+    }
+
+    pub fn shutdown(&mut self, ctx: &mut AppContext) {
+        ctx.publish(AppEvent::Shutdown.into());
+        for system in self.systems.iter_mut().rev() {
+            system.on_shutdown(ctx);
+        }
+    }
+}
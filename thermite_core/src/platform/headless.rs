@@ -0,0 +1,201 @@
+/*
+    ABSTRACT: An application loop without a window or renderer, for dedicated servers, CI tests and batch tools.
+    It drives the same `System`s and publishes the same lifecycle events as the windowed loop (see thermite_gfx's app.rs).
+*/
+use crate::assets::{integrity::AssetManifest, vfs::Vfs};
+use crate::jobs::scheduler::JobSystem;
+use crate::platform::app::{AppContext, System, Systems};
+use crate::profile_frame;
+use crate::tools::{
+    clock::Clock,
+    config::ConfigRegistry,
+    timer::{FixedTimestep, Time},
+};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Where a headless application gets its frame times from
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum HeadlessClock {
+    /// Frames take as long as they really take, according to the application's `Clock` (see `HeadlessAppBuilder::with_clock_source`)
+    Real,
+    /// Every frame advances time by exactly this much, however long it really took. Makes runs reproducible.
+    Fixed(Duration),
+}
+
+/// Builds a `HeadlessApp` from a clock, an optional frame limit and a set of `System`s
+pub struct HeadlessAppBuilder {
+    clock: HeadlessClock,
+    clock_source: Option<Arc<dyn Clock>>,
+    max_frames: Option<u64>,
+    fixed_timestep: FixedTimestep,
+    config: ConfigRegistry,
+    worker_threads: Option<usize>,
+    vfs: Option<Vfs>,
+    hot_reload: bool,
+    manifest: Option<AssetManifest>,
+    systems: Systems,
+}
+
+impl Default for HeadlessAppBuilder {
+    /// A real clock, no frame limit, the default `FixedTimestep`, an empty configuration, the default `JobSystem` and VFS mounts, and no systems
+    fn default() -> Self {
+        Self {
+            clock: HeadlessClock::Real,
+            clock_source: None,
+            max_frames: None,
+            fixed_timestep: FixedTimestep::default(),
+            config: ConfigRegistry::default(),
+            worker_threads: None,
+            vfs: None,
+            hot_reload: false,
+            manifest: None,
+            systems: Systems::default(),
+        }
+    }
+}
+
+impl HeadlessAppBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clock(mut self, clock: HeadlessClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Reads real time from the given clock rather than the platform's, e.g. a `ManualClock` advanced by a test
+    pub fn with_clock_source(mut self, clock_source: Arc<dyn Clock>) -> Self {
+        self.clock_source = Some(clock_source);
+        self
+    }
+
+    /// Stops the application after this many frames, if nothing requested an exit before
+    pub fn with_max_frames(mut self, max_frames: u64) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    /// Sets the rate at which the systems' fixed updates run
+    pub fn with_fixed_timestep(mut self, fixed_timestep: FixedTimestep) -> Self {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    /// Shares the given configuration with the application's systems
+    pub fn with_config(mut self, config: ConfigRegistry) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets how many worker threads the application's `JobSystem` starts, rather than one per core
+    pub fn with_worker_threads(mut self, worker_threads: usize) -> Self {
+        self.worker_threads = Some(worker_threads);
+        self
+    }
+
+    /// Loads the application's files through the given VFS, rather than one with the default mounts (see `Vfs::with_default_mounts`)
+    pub fn with_vfs(mut self, vfs: Vfs) -> Self {
+        self.vfs = Some(vfs);
+        self
+    }
+
+    /// Reloads assets when their file changes on disk (see `AssetServer::set_hot_reload`)
+    pub fn with_hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    /// Verifies the assets the manifest lists against it as they load (see `AssetServer::set_manifest`)
+    pub fn with_manifest(mut self, manifest: AssetManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Adds a `System` to the application. Systems run in the order they're added.
+    pub fn with_system<S: System + 'static>(mut self, system: S) -> Self {
+        self.systems.add(Box::new(system));
+        self
+    }
+
+    pub fn build(self) -> HeadlessApp {
+        let jobs = self
+            .worker_threads
+            .map_or_else(JobSystem::default, JobSystem::new);
+        let mut context = AppContext::with_jobs(self.config, jobs);
+        *context.fixed_timestep_mut() = self.fixed_timestep;
+        if let Some(clock_source) = self.clock_source {
+            *context.time_mut() = Time::with_clock(clock_source);
+        }
+        if let Some(vfs) = self.vfs {
+            context.set_vfs(vfs);
+        }
+        context.assets_mut().set_hot_reload(self.hot_reload);
+        context.assets_mut().set_manifest(self.manifest);
+        HeadlessApp {
+            clock: self.clock,
+            max_frames: self.max_frames,
+            context,
+            systems: self.systems,
+        }
+    }
+}
+
+/// A Thermite application without a window
+pub struct HeadlessApp {
+    clock: HeadlessClock,
+    max_frames: Option<u64>,
+    context: AppContext,
+    systems: Systems,
+}
+
+impl HeadlessApp {
+    pub fn builder() -> HeadlessAppBuilder {
+        HeadlessAppBuilder::new()
+    }
+
+    /// The context shared with the application's systems
+    pub fn context(&mut self) -> &mut AppContext {
+        &mut self.context
+    }
+
+    /// Runs the application's loop until something requests an exit (see `AppContext::exit_requested`) or the frame limit is reached,
+    /// then shuts every system down.
+    ///
+    /// Every frame updates then renders every system, like the windowed loop. Returns the number of frames that ran.
+    /// If a system panics, every system is still shut down before the panic resumes.
+    pub fn run(&mut self) -> u64 {
+        let HeadlessApp {
+            clock,
+            max_frames,
+            context,
+            systems,
+        } = self;
+        let mut frames = 0;
+        let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+            systems.init(context);
+            while !context.exit_requested() && !max_frames.is_some_and(|max| frames >= max) {
+                match clock {
+                    HeadlessClock::Real => systems.update(context),
+                    HeadlessClock::Fixed(delta) => {
+                        profile_frame!();
+                        context.time_mut().advance_by(*delta);
+                        systems.run_updates(context);
+                    }
+                }
+                systems.render(context);
+                frames += 1;
+            }
+        }));
+        // Systems still shut down after a panic (already reported by the panic hook, see tools::crash), then the panic resumes
+        systems.shutdown(context);
+        if let Err(panic) = ran {
+            panic::resume_unwind(panic);
+        }
+        frames
+    }
+}
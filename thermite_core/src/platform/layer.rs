@@ -1,22 +1,70 @@
-use crate::platform::event::{Publisher, Subscriber};
+/*
+    ABSTRACT: Definitions of application layers and the layer stack which orders them,
+    so that events travel from overlays (UI, debug tools) down to regular layers (gameplay, world)
+    and can be stopped along the way by any layer (see bus.rs for `BusRequest`).
+*/
+use crate::messaging::{
+    bus::{BusRequest, EventDispatchResult},
+    event::Event,
+    publish::Publisher,
+    subscribe::Subscriber,
+};
 use std::boxed::Box;
+use std::cell::RefCell;
+use std::hash::Hash;
 use std::ops::Deref;
 
-pub trait Layer<E: Event>: Publisher<E> + Subscriber<E> {
-    fn on_attach(&self);
-    fn on_detach(&self);
-    fn on_update(&self);
-    // TODO: Enable/Disable layer
+/// A single layer of the application, receiving events as a `Subscriber` and able to publish its own as a `Publisher`.
+///
+/// - `T` is meant to be implemented by the module consumer as an enum, depicting the various categories an event can belong to.
+///
+/// - `E` is meant to be implemented by the module consumer as an enum, depicting the individual events which exist in the system. See `Event`.
+pub trait Layer<T, E>: Publisher<T, E> + Subscriber<T, E>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
+    /// Called when this layer is pushed onto a `LayerStack`
+    fn on_attach(&mut self);
+    /// Called when this layer is removed from a `LayerStack`
+    fn on_detach(&mut self);
+    /// Called once per frame while this layer is enabled, with the frame time in seconds
+    fn on_update(&mut self, delta_sec: f32);
+    /// A unique identifier for this layer within its `LayerStack`
     fn id(&self) -> u32;
     fn debug_name(&self) -> &str;
 }
 
-pub struct LayerStack<E: Event> {
-    layers: Vec<Box<dyn Layer<E>>>,
+/// A `Layer` as stored in a `LayerStack`, along with whether it's currently enabled
+pub struct LayerEntry<T, E>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
+    pub layer: Box<dyn Layer<T, E>>,
+    pub enabled: bool,
+}
+
+/// An ordered stack of `Layer`s, split in two sections: regular layers at the bottom and overlays on top.
+///
+/// Events are dispatched from the top of the stack down, and updates are run from the bottom of the stack up.
+/// Disabled layers receive neither.
+///
+/// A `LayerStack` can be subscribed to an `EventBus` when wrapped in an `Rc<RefCell<LayerStack>>`.
+pub struct LayerStack<T, E>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
+    layers: Vec<LayerEntry<T, E>>,
     layer_boundary_idx: usize,
 }
 
-impl<E: Event> Default for LayerStack<E> {
+impl<T, E> Default for LayerStack<T, E>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
     fn default() -> Self {
         LayerStack {
             layers: vec![],
@@ -25,43 +73,194 @@ impl<E: Event> Default for LayerStack<E> {
     }
 }
 
-impl<E: Event> LayerStack<E> {
-    pub fn push_layer(&mut self, layer: Box<dyn Layer<E>>) {
+impl<T, E> LayerStack<T, E>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
+    /// Pushes a layer on top of the other layers, but below every overlay
+    pub fn push_layer(&mut self, mut layer: Box<dyn Layer<T, E>>) {
         layer.on_attach();
-        self.layers.insert(self.layer_boundary_idx, layer);
+        self.layers.insert(
+            self.layer_boundary_idx,
+            LayerEntry {
+                layer,
+                enabled: true,
+            },
+        );
         self.layer_boundary_idx += 1;
     }
 
-    pub fn pop_layer(&mut self, layer: Box<dyn Layer<E>>) -> Option<Box<dyn Layer<E>>> {
-        if let Some(idx) = self.layers.iter().position(|l| l.id() == layer.id()) {
-            let removed = self.layers.remove(idx);
-            removed.on_detach();
-            self.layer_boundary_idx -= 1;
-            Some(removed)
-        } else {
-            None
+    /// Pushes an overlay on top of everything else in the stack
+    pub fn push_overlay(&mut self, mut overlay: Box<dyn Layer<T, E>>) {
+        overlay.on_attach();
+        self.layers.push(LayerEntry {
+            layer: overlay,
+            enabled: true,
+        });
+    }
+
+    /// Removes the layer or overlay with the given ID from the stack and returns it
+    pub fn remove(&mut self, id: u32) -> Option<Box<dyn Layer<T, E>>> {
+        let idx = self.position(id)?;
+        Some(self.remove_at(idx))
+    }
+
+    /// Removes the topmost regular layer (not overlay) from the stack and returns it
+    pub fn pop_layer(&mut self) -> Option<Box<dyn Layer<T, E>>> {
+        if self.layer_boundary_idx == 0 {
+            return None;
         }
+        Some(self.remove_at(self.layer_boundary_idx - 1))
     }
 
-    pub fn push_overlay(&mut self, overlay: Box<dyn Layer<E>>) {
-        overlay.on_attach();
-        self.layers.push(overlay);
+    /// Removes the topmost overlay from the stack and returns it
+    pub fn pop_overlay(&mut self) -> Option<Box<dyn Layer<T, E>>> {
+        if self.layers.len() == self.layer_boundary_idx {
+            return None;
+        }
+        Some(self.remove_at(self.layers.len() - 1))
     }
 
-    pub fn pop_overlay(&mut self) -> Option<Box<dyn Layer<E>>> {
-        if let Some(overlay) = self.layers.pop() {
-            overlay.on_detach();
-            Some(overlay)
+    /// Enables the layer with the given ID, returning whether it was found
+    pub fn enable(&mut self, id: u32) -> bool {
+        self.set_enabled(id, true)
+    }
+
+    /// Disables the layer with the given ID, returning whether it was found. Disabled layers receive no events or updates.
+    pub fn disable(&mut self, id: u32) -> bool {
+        self.set_enabled(id, false)
+    }
+
+    /// Whether the layer with the given ID is in this stack and enabled
+    pub fn is_enabled(&self, id: u32) -> bool {
+        self.position(id)
+            .is_some_and(|idx| self.layers[idx].enabled)
+    }
+
+    /// Moves the layer with the given ID to `position` within its own section (layers or overlays), where `0` is the bottom.
+    ///
+    /// Positions past the top of the section move the layer to the top. Returns whether the layer was found.
+    pub fn move_to(&mut self, id: u32, position: usize) -> bool {
+        let idx = match self.position(id) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let (section_start, section_end) = if idx < self.layer_boundary_idx {
+            (0, self.layer_boundary_idx)
         } else {
-            None
+            (self.layer_boundary_idx, self.layers.len())
+        };
+        let entry = self.layers.remove(idx);
+        let new_idx = (section_start + position).min(section_end - 1);
+        self.layers.insert(new_idx, entry);
+        true
+    }
+
+    /// Moves the layer with the given ID one step up within its own section. Returns whether it moved.
+    pub fn raise(&mut self, id: u32) -> bool {
+        match self.position(id) {
+            Some(idx) if idx + 1 != self.layer_boundary_idx && idx + 1 < self.layers.len() => {
+                self.layers.swap(idx, idx + 1);
+                true
+            }
+            _ => false,
         }
     }
+
+    /// Moves the layer with the given ID one step down within its own section. Returns whether it moved.
+    pub fn lower(&mut self, id: u32) -> bool {
+        match self.position(id) {
+            Some(idx) if idx > 0 && idx != self.layer_boundary_idx => {
+                self.layers.swap(idx, idx - 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs `on_update` on every enabled layer, from the bottom of the stack up
+    pub fn update(&mut self, delta_sec: f32) {
+        for entry in self.layers.iter_mut().filter(|entry| entry.enabled) {
+            entry.layer.on_update(delta_sec);
+        }
+    }
+
+    /// Dispatches the given event from the top of the stack down, until a layer requests that it stops propagating.
+    ///
+    /// Layers which request to unsubscribe are detached and removed from the stack.
+    pub fn dispatch_event(&mut self, event: &E) -> EventDispatchResult {
+        let mut idx = self.layers.len();
+        while idx > 0 {
+            idx -= 1;
+            if !self.layers[idx].enabled {
+                continue;
+            }
+            match self.layers[idx].layer.on_event(event) {
+                BusRequest::NoActionNeeded => (),
+                BusRequest::Unsubscribe => {
+                    self.remove_at(idx);
+                }
+                BusRequest::DoNotPropagate => return EventDispatchResult::Stopped,
+                BusRequest::UnsubscribeAndDoNotPropagate => {
+                    self.remove_at(idx);
+                    return EventDispatchResult::Stopped;
+                }
+            }
+        }
+        EventDispatchResult::Finished
+    }
+
+    fn position(&self, id: u32) -> Option<usize> {
+        self.layers.iter().position(|entry| entry.layer.id() == id)
+    }
+
+    fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.position(id) {
+            Some(idx) => {
+                self.layers[idx].enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_at(&mut self, idx: usize) -> Box<dyn Layer<T, E>> {
+        let mut removed = self.layers.remove(idx).layer;
+        if idx < self.layer_boundary_idx {
+            self.layer_boundary_idx -= 1;
+        }
+        removed.on_detach();
+        removed
+    }
 }
 
-impl<E: Event> Deref for LayerStack<E> {
-    type Target = [Box<dyn Layer<E>>];
+impl<T, E> Deref for LayerStack<T, E>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
+    type Target = [LayerEntry<T, E>];
 
     fn deref(&self) -> &Self::Target {
         &self.layers
     }
 }
+
+// Lets an Rc<RefCell<LayerStack>> be subscribed to an `EventBus`, so that it halts propagation on the bus when a layer halts it in the stack
+impl<T, E> Subscriber<T, E> for RefCell<LayerStack<T, E>>
+where
+    T: Eq + PartialEq + Hash + Clone,
+    E: Event<T> + Eq + PartialEq + Hash + Clone,
+{
+    fn on_event(&self, event: &E) -> BusRequest {
+        match self
+            .try_borrow_mut()
+            .expect("Couldn't borrow the layer stack as mutable")
+            .dispatch_event(event)
+        {
+            EventDispatchResult::Stopped => BusRequest::DoNotPropagate,
+            EventDispatchResult::Finished => BusRequest::NoActionNeeded,
+        }
+    }
+}
@@ -1 +1,5 @@
-// pub mod layer;
+pub mod app;
+pub mod headless;
+pub mod layer;
+pub mod translate;
+pub mod window;
@@ -0,0 +1,76 @@
+/*
+    ABSTRACT: Translation of winit's window and device events into `ThermiteEvent`s,
+    so that application loops don't each have to hand-roll it.
+*/
+use crate::input::{keyboard::KeyboardEvent, mouse::MouseEvent};
+use crate::messaging::event::ThermiteEvent;
+use crate::platform::window::{WindowEvent, WindowId};
+use winit::event::{DeviceEvent, ElementState, WindowEvent as WinitWindowEvent};
+
+/// Translates a winit `WindowEvent` which happened to the given window into a `ThermiteEvent`, if it has a Thermite equivalent.
+///
+/// Only window events are tagged with the window's ID, input events are implied to go to the focused window.
+pub fn translate_window_event(
+    window_id: WindowId,
+    event: &WinitWindowEvent,
+) -> Option<ThermiteEvent> {
+    use WinitWindowEvent as WindowEvent;
+    match event {
+        WindowEvent::KeyboardInput { input, .. } => match input.state {
+            ElementState::Pressed => Some(KeyboardEvent::KeyPressed((*input).into()).into()),
+            ElementState::Released => Some(KeyboardEvent::KeyReleased((*input).into()).into()),
+        },
+        WindowEvent::ModifiersChanged(modifiers_state) => {
+            Some(KeyboardEvent::ModifiersChanged((*modifiers_state).into()).into())
+        }
+        WindowEvent::ReceivedCharacter(character) => {
+            Some(KeyboardEvent::Character(*character).into())
+        }
+        WindowEvent::MouseInput { state, button, .. } => match state {
+            ElementState::Pressed => Some(MouseEvent::ButtonPressed(*button).into()),
+            ElementState::Released => Some(MouseEvent::ButtonReleased(*button).into()),
+        },
+        WindowEvent::MouseWheel { delta, .. } => Some(MouseEvent::Scroll((*delta).into()).into()),
+        WindowEvent::CursorMoved { position, .. } => {
+            Some(MouseEvent::Motion((*position).into()).into())
+        }
+        WindowEvent::CursorEntered { .. } => Some(MouseEvent::EnteredWindow.into()),
+        WindowEvent::CursorLeft { .. } => Some(MouseEvent::LeftWindow.into()),
+        _ => translate_window_state_event(event).map(|w_evt| w_evt.for_window(window_id)),
+    }
+}
+
+/// Translates the winit `WindowEvent`s which describe the state of the window itself, rather than input
+fn translate_window_state_event(event: &WinitWindowEvent) -> Option<WindowEvent> {
+    match event {
+        WinitWindowEvent::Resized(size) if size.width == 0 && size.height == 0 => {
+            Some(WindowEvent::Minimized)
+        }
+        WinitWindowEvent::Resized(size) => Some(WindowEvent::Resized((*size).into())),
+        WinitWindowEvent::Moved(position) => Some(WindowEvent::Moved((*position).into())),
+        WinitWindowEvent::Focused(true) => Some(WindowEvent::FocusGained),
+        WinitWindowEvent::Focused(false) => Some(WindowEvent::FocusLost),
+        WinitWindowEvent::ScaleFactorChanged {
+            scale_factor,
+            new_inner_size,
+        } => Some(WindowEvent::ScaleFactorChanged {
+            scale_factor: (*scale_factor).into(),
+            new_size: (**new_inner_size).into(),
+        }),
+        WinitWindowEvent::CloseRequested => Some(WindowEvent::CloseRequested),
+        WinitWindowEvent::Destroyed => Some(WindowEvent::Destroyed),
+        WinitWindowEvent::DroppedFile(path) => Some(WindowEvent::DroppedFile(path.clone())),
+        WinitWindowEvent::HoveredFile(path) => Some(WindowEvent::HoveredFile(path.clone())),
+        WinitWindowEvent::HoveredFileCancelled => Some(WindowEvent::HoveredFileCancelled),
+        WinitWindowEvent::ThemeChanged(theme) => Some(WindowEvent::ThemeChanged(theme.into())),
+        _ => None,
+    }
+}
+
+/// Translates a winit `DeviceEvent` into a `ThermiteEvent`, if it has a Thermite equivalent
+pub fn translate_device_event(event: &DeviceEvent) -> Option<ThermiteEvent> {
+    match event {
+        DeviceEvent::MouseMotion { delta } => Some(MouseEvent::RawMotion((*delta).into()).into()),
+        _ => None,
+    }
+}
@@ -0,0 +1,109 @@
+/*
+    ABSTRACT: Window events, translated from the platform (see translate.rs) and published
+    on the bus under `ThermiteEventType::Window`, so that renderers and UI can react to them.
+    Every window event is tagged with the ID of the window it happened to, since an application can have several windows.
+*/
+use crate::messaging::event::ThermiteEvent;
+use std::path::PathBuf;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+pub use winit::window::WindowId;
+
+/// The size of a window's client area, in physical pixels
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<PhysicalSize<u32>> for WindowSize {
+    fn from(ps: PhysicalSize<u32>) -> Self {
+        Self {
+            width: ps.width,
+            height: ps.height,
+        }
+    }
+}
+
+/// The position of a window's top-left corner on the desktop, in physical pixels
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub struct WindowPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<PhysicalPosition<i32>> for WindowPosition {
+    fn from(pp: PhysicalPosition<i32>) -> Self {
+        Self { x: pp.x, y: pp.y }
+    }
+}
+
+/// The ratio between physical and logical pixels of a window.
+///
+/// Stored as the bits of an `f64` so that events carrying it can be hashed and compared.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub struct ScaleFactor(u64);
+
+impl From<f64> for ScaleFactor {
+    fn from(scale_factor: f64) -> Self {
+        Self(scale_factor.to_bits())
+    }
+}
+
+impl ScaleFactor {
+    pub fn value(&self) -> f64 {
+        f64::from_bits(self.0)
+    }
+}
+
+/// The system color theme
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum WindowTheme {
+    Light,
+    Dark,
+}
+
+impl From<&winit::window::Theme> for WindowTheme {
+    fn from(theme: &winit::window::Theme) -> Self {
+        match theme {
+            winit::window::Theme::Light => WindowTheme::Light,
+            winit::window::Theme::Dark => WindowTheme::Dark,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum WindowEvent {
+    /// The client area was resized. Not published for minimization, see `Minimized`.
+    Resized(WindowSize),
+    /// The window was minimized. Platforms report this as a resize to 0x0, and report restoring as a regular resize.
+    Minimized,
+    Moved(WindowPosition),
+    FocusGained,
+    FocusLost,
+    /// The window moved to a monitor with a different DPI, or the DPI settings changed. Includes the new client area size.
+    ScaleFactorChanged {
+        scale_factor: ScaleFactor,
+        new_size: WindowSize,
+    },
+    /// The user asked to close the window (e.g. clicked the close button)
+    CloseRequested,
+    Destroyed,
+    /// A file was dropped onto the window
+    DroppedFile(PathBuf),
+    /// A file is being dragged over the window
+    HoveredFile(PathBuf),
+    /// A file that was being dragged over the window left it, or the drag was cancelled
+    HoveredFileCancelled,
+    ThemeChanged(WindowTheme),
+}
+
+impl WindowEvent {
+    /// Tags this event with the window it happened to, making it publishable
+    pub fn for_window(self, window_id: WindowId) -> ThermiteEvent {
+        ThermiteEvent::Window {
+            window_id,
+            event: self,
+        }
+    }
+}
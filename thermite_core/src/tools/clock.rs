@@ -0,0 +1,61 @@
+/*
+    ABSTRACT: Where `Time` (and so the loop, `Timers` and everything driven by frame times) reads the current instant from.
+    `RealClock` reads the platform's monotonic clock, `ManualClock` only moves when told to, for deterministic tests and replays.
+*/
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A source of the current instant
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The platform's monotonic clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves when advanced. Clones share the same time, so a test can keep one and hand another to `Time`.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl Default for ManualClock {
+    /// A clock stopped at the instant it was created
+    fn default() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self
+            .now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self
+            .now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
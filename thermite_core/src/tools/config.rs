@@ -0,0 +1,903 @@
+/*
+    ABSTRACT: A typed registry of engine configuration values, layered from registered defaults,
+    then user configuration files (TOML or RON), then command line overrides.
+    Watched files are reloaded when they change on disk, and every change is reported as a `ConfigEvent`.
+*/
+use crate::messaging::event::ThermiteEvent;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Errors relating to `ConfigRegistry`
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    TomlDeserialization(toml::de::Error),
+    TomlSerialization(toml::ser::Error),
+    Ron(ron::Error),
+    /// Configuration files must have a `.toml` or `.ron` extension
+    UnsupportedFormat(PathBuf),
+    UnknownKey(String),
+    AlreadyRegistered(String),
+    TypeMismatch {
+        key: String,
+        expected: &'static str,
+        found: String,
+    },
+    /// The value was rejected by the key's validator
+    InvalidValue {
+        key: String,
+        reason: String,
+    },
+    /// A command line override wasn't of the form `key=value`
+    MalformedArgument(String),
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(error: toml::de::Error) -> Self {
+        ConfigError::TomlDeserialization(error)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(error: toml::ser::Error) -> Self {
+        ConfigError::TomlSerialization(error)
+    }
+}
+
+impl From<ron::Error> for ConfigError {
+    fn from(error: ron::Error) -> Self {
+        ConfigError::Ron(error)
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(fmt, "{:?}: {}", self, err),
+            ConfigError::TomlDeserialization(err) => write!(fmt, "{:?}: {}", self, err),
+            ConfigError::TomlSerialization(err) => write!(fmt, "{:?}: {}", self, err),
+            ConfigError::Ron(err) => write!(fmt, "{:?}: {}", self, err),
+            ConfigError::UnsupportedFormat(path) => write!(fmt, "{:?}: {}", self, path.display()),
+            ConfigError::UnknownKey(key) => write!(fmt, "{:?}: {}", self, key),
+            ConfigError::AlreadyRegistered(key) => write!(fmt, "{:?}: {}", self, key),
+            ConfigError::TypeMismatch {
+                key,
+                expected,
+                found,
+            } => write!(fmt, "{}: expected {}, found {}", key, expected, found),
+            ConfigError::InvalidValue { key, reason } => write!(fmt, "{}: {}", key, reason),
+            ConfigError::MalformedArgument(arg) => write!(fmt, "{:?}: {}", self, arg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(err) => Some(err),
+            ConfigError::TomlDeserialization(err) => Some(err),
+            ConfigError::TomlSerialization(err) => Some(err),
+            ConfigError::Ron(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Configuration events, published on the bus under `ThermiteEventType::Config`
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum ConfigEvent {
+    /// The effective value of this key changed
+    Changed(String),
+    /// A watched file changed on disk, but couldn't be loaded. The previous values are kept.
+    ReloadFailed(PathBuf),
+}
+
+impl From<ConfigEvent> for ThermiteEvent {
+    fn from(cfg_evt: ConfigEvent) -> Self {
+        ThermiteEvent::Config(cfg_evt)
+    }
+}
+
+/// A single configuration value. The type of a key is the type of its default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl ConfigValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ConfigValue::Bool(_) => "bool",
+            ConfigValue::Int(_) => "int",
+            ConfigValue::Float(_) => "float",
+            ConfigValue::String(_) => "string",
+        }
+    }
+
+    /// Converts this value to the type of `like`, if it's the same type or an int going into a float
+    fn coerce_like(self, like: &ConfigValue) -> Option<ConfigValue> {
+        match (self, like) {
+            (ConfigValue::Int(i), ConfigValue::Float(_)) => Some(ConfigValue::Float(i as f64)),
+            (value, like) if value.type_name() == like.type_name() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Parses text (e.g. from the command line) as a value of the same type as `like`
+    pub fn parse_like(text: &str, like: &ConfigValue) -> Option<ConfigValue> {
+        match like {
+            ConfigValue::Bool(_) => text.parse().ok().map(ConfigValue::Bool),
+            ConfigValue::Int(_) => text.parse().ok().map(ConfigValue::Int),
+            ConfigValue::Float(_) => text.parse().ok().map(ConfigValue::Float),
+            ConfigValue::String(_) => Some(ConfigValue::String(text.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigValue {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigValue::Bool(b) => write!(fmt, "{}", b),
+            ConfigValue::Int(i) => write!(fmt, "{}", i),
+            ConfigValue::Float(f) => write!(fmt, "{}", f),
+            ConfigValue::String(s) => write!(fmt, "{}", s),
+        }
+    }
+}
+
+impl From<bool> for ConfigValue {
+    fn from(b: bool) -> Self {
+        ConfigValue::Bool(b)
+    }
+}
+
+impl From<i64> for ConfigValue {
+    fn from(i: i64) -> Self {
+        ConfigValue::Int(i)
+    }
+}
+
+impl From<f64> for ConfigValue {
+    fn from(f: f64) -> Self {
+        ConfigValue::Float(f)
+    }
+}
+
+impl From<&str> for ConfigValue {
+    fn from(s: &str) -> Self {
+        ConfigValue::String(s.to_string())
+    }
+}
+
+impl From<String> for ConfigValue {
+    fn from(s: String) -> Self {
+        ConfigValue::String(s)
+    }
+}
+
+/// Types which can be read out of a `ConfigValue`, see `ConfigRegistry::get_as`
+pub trait FromConfigValue: Sized {
+    fn from_config_value(value: &ConfigValue) -> Option<Self>;
+}
+
+impl FromConfigValue for bool {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for i64 {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for u32 {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Int(i) => std::convert::TryFrom::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for u64 {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Int(i) => std::convert::TryFrom::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for f64 {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+impl FromConfigValue for f32 {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        f64::from_config_value(value).map(|f| f as f32)
+    }
+}
+
+impl FromConfigValue for String {
+    fn from_config_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The layers values can be set in, on top of the registered defaults. Later layers take precedence.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum ConfigLayer {
+    /// Values from the user's configuration files, and changes made at runtime (e.g. from the console)
+    User,
+    /// Values given on the command line, for this run only
+    CommandLine,
+}
+
+/// Checks a value before it's accepted, returning why it's invalid if it is
+pub type ConfigValidator = Box<dyn Fn(&ConfigValue) -> Result<(), String>>;
+
+struct ConfigEntry {
+    default: ConfigValue,
+    description: String,
+    validator: Option<ConfigValidator>,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    layer: ConfigLayer,
+    modified: Option<SystemTime>,
+}
+
+/// Files are a tree of tables, whose leaves are values. Keys in the registry are the dotted paths to the leaves.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ConfigNode {
+    Value(ConfigValue),
+    Table(BTreeMap<String, ConfigNode>),
+}
+
+/// A registry of typed configuration values, identified by dotted keys (e.g. `render.wireframe`).
+///
+/// Every key must be registered with a default value (which sets its type) before it can be set in a layer, but values
+/// loaded from files for keys which aren't registered yet are held until they are.
+/// Reading a key gives its command line value if there's one, else its user value if there's one, else its default.
+pub struct ConfigRegistry {
+    entries: BTreeMap<String, ConfigEntry>,
+    user: HashMap<String, ConfigValue>,
+    command_line: HashMap<String, ConfigValue>,
+    /// Values loaded from files for keys which aren't registered (yet), validated when they're registered
+    unregistered: HashMap<(ConfigLayer, String), ConfigValue>,
+    watched: Vec<WatchedFile>,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+    revision: u64,
+}
+
+impl Default for ConfigRegistry {
+    /// An empty registry, which checks watched files for changes every second
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            user: HashMap::new(),
+            command_line: HashMap::new(),
+            unregistered: HashMap::new(),
+            watched: vec![],
+            poll_interval: Duration::from_secs(1),
+            last_poll: None,
+            revision: 0,
+        }
+    }
+}
+
+impl ConfigRegistry {
+    /// Registers a key with its default value and a description of what it does
+    pub fn register<V: Into<ConfigValue>>(
+        &mut self,
+        key: &str,
+        default: V,
+        description: &str,
+    ) -> Result<(), ConfigError> {
+        self.insert_entry(key, default.into(), description, None)
+    }
+
+    /// Registers a key like `register`, along with a validator which every value set for it must pass
+    pub fn register_validated<V, F>(
+        &mut self,
+        key: &str,
+        default: V,
+        description: &str,
+        validator: F,
+    ) -> Result<(), ConfigError>
+    where
+        V: Into<ConfigValue>,
+        F: Fn(&ConfigValue) -> Result<(), String> + 'static,
+    {
+        self.insert_entry(key, default.into(), description, Some(Box::new(validator)))
+    }
+
+    pub fn is_registered(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Every registered key, in alphabetical order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|key| key.as_str())
+    }
+
+    pub fn description(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .map(|entry| entry.description.as_str())
+    }
+
+    pub fn default_value(&self, key: &str) -> Option<&ConfigValue> {
+        self.entries.get(key).map(|entry| &entry.default)
+    }
+
+    /// The effective value of the given key
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        self.command_line
+            .get(key)
+            .or_else(|| self.user.get(key))
+            .or_else(|| self.default_value(key))
+    }
+
+    /// The effective value of the given key, as a Rust type. `None` if the key isn't registered or doesn't fit in `T`.
+    pub fn get_as<T: FromConfigValue>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(T::from_config_value)
+    }
+
+    /// The value of the given key in the given layer only
+    pub fn layer_value(&self, layer: ConfigLayer, key: &str) -> Option<&ConfigValue> {
+        self.layer(layer).get(key)
+    }
+
+    /// Every value set in the given layer, in no particular order
+    pub fn layer_values(&self, layer: ConfigLayer) -> impl Iterator<Item = (&str, &ConfigValue)> {
+        self.layer(layer)
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Validates and sets a value in the given layer, returning whether the effective value changed
+    pub fn set<V: Into<ConfigValue>>(
+        &mut self,
+        layer: ConfigLayer,
+        key: &str,
+        value: V,
+    ) -> Result<bool, ConfigError> {
+        let value = self.validate(key, value.into())?;
+        let previous = self.get(key).cloned();
+        self.layer_mut(layer).insert(key.to_string(), value);
+        Ok(self.get(key) != previous.as_ref())
+    }
+
+    /// Parses text as a value of the key's type, then sets it like `set`
+    pub fn set_from_str(
+        &mut self,
+        layer: ConfigLayer,
+        key: &str,
+        text: &str,
+    ) -> Result<bool, ConfigError> {
+        let default = self
+            .default_value(key)
+            .ok_or_else(|| ConfigError::UnknownKey(key.to_string()))?;
+        let value =
+            ConfigValue::parse_like(text, default).ok_or_else(|| ConfigError::TypeMismatch {
+                key: key.to_string(),
+                expected: default.type_name(),
+                found: text.to_string(),
+            })?;
+        self.set(layer, key, value)
+    }
+
+    /// Removes the key's value from the given layer, returning whether the effective value changed
+    pub fn unset(&mut self, layer: ConfigLayer, key: &str) -> bool {
+        let previous = self.get(key).cloned();
+        self.layer_mut(layer).remove(key);
+        self.get(key) != previous.as_ref()
+    }
+
+    /// Replaces every value in the given layer with the values in the given TOML or RON file, and returns the keys whose effective value changed.
+    ///
+    /// Every value in the file is validated first, so nothing changes if any of them is invalid. Values for keys which aren't
+    /// registered are held (with a warning) until they're registered, rather than rejected: they may be typos, or belong to
+    /// systems which register their keys later, or to features this build doesn't have.
+    pub fn load_file(
+        &mut self,
+        path: &Path,
+        layer: ConfigLayer,
+    ) -> Result<Vec<String>, ConfigError> {
+        let root = read_file(path)?;
+        let mut flattened = HashMap::new();
+        flatten(root, String::new(), &mut flattened);
+        let mut values = HashMap::new();
+        let mut unregistered = vec![];
+        for (key, value) in flattened {
+            if self.is_registered(&key) {
+                let value = self.validate(&key, value)?;
+                values.insert(key, value);
+            } else {
+                unregistered.push((key, value));
+            }
+        }
+        self.unregistered
+            .retain(|(held_layer, _), _| *held_layer != layer);
+        for (key, value) in unregistered {
+            warn!(
+                "{} sets {}, which isn't registered (yet)",
+                path.display(),
+                key
+            );
+            self.unregistered.insert((layer, key), value);
+        }
+        Ok(self.replace_layer(layer, values))
+    }
+
+    /// Writes every value in the given layer to a TOML or RON file, along with the values held for keys which aren't registered
+    pub fn save_file(&self, path: &Path, layer: ConfigLayer) -> Result<(), ConfigError> {
+        let mut root = BTreeMap::new();
+        let unregistered = self
+            .unregistered
+            .iter()
+            .filter(|((held_layer, _), _)| *held_layer == layer)
+            .map(|((_, key), value)| (key, value));
+        for (key, value) in self.layer(layer).iter().chain(unregistered) {
+            insert_nested(&mut root, key, value.clone());
+        }
+        write_file(path, &ConfigNode::Table(root))
+    }
+
+    /// Writes the given keys' values in the given layer to a TOML or RON file, leaving the rest of the file as it is
+    /// (e.g. to persist changes made at runtime). Keys without a value in the layer are removed from the file.
+    ///
+    /// A watched file isn't reloaded because of this, unless it had already changed on disk since it was last loaded.
+    pub fn save_keys(
+        &mut self,
+        path: &Path,
+        layer: ConfigLayer,
+        keys: &[String],
+    ) -> Result<(), ConfigError> {
+        let modified = modified_time(path);
+        let mut root = match modified {
+            Some(_) => match read_file(path)? {
+                ConfigNode::Table(root) => root,
+                ConfigNode::Value(_) => BTreeMap::new(),
+            },
+            None => BTreeMap::new(),
+        };
+        for key in keys {
+            match self.layer(layer).get(key) {
+                Some(value) => insert_nested(&mut root, key, value.clone()),
+                None => remove_nested(&mut root, key),
+            }
+        }
+        write_file(path, &ConfigNode::Table(root))?;
+        for watched in &mut self.watched {
+            if watched.path == path && watched.modified == modified {
+                watched.modified = modified_time(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the given file into the given layer like `load_file`, then keeps reloading it when it changes on disk (see `poll_watched_files`).
+    ///
+    /// A file which doesn't exist yet is watched all the same, and loaded once it's created.
+    pub fn watch_file(
+        &mut self,
+        path: &Path,
+        layer: ConfigLayer,
+    ) -> Result<Vec<String>, ConfigError> {
+        let changed = if path.exists() {
+            self.load_file(path, layer)?
+        } else {
+            vec![]
+        };
+        self.watched.push(WatchedFile {
+            path: path.to_path_buf(),
+            layer,
+            modified: modified_time(path),
+        });
+        Ok(changed)
+    }
+
+    /// A counter which increases whenever a layer is modified, to tell whether anything changed since it was last read (e.g. before saving)
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Sets how often `poll_watched_files` actually checks the files
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    /// Reloads every watched file which changed on disk since it was last loaded, and returns the resulting events.
+    ///
+    /// Meant to be called every frame with the current instant (the application's `Clock`'s, see `Time::clock`):
+    /// files are only checked once per poll interval.
+    pub fn poll_watched_files(&mut self, now: Instant) -> Vec<ConfigEvent> {
+        if self
+            .last_poll
+            .is_some_and(|last_poll| now - last_poll < self.poll_interval)
+        {
+            return vec![];
+        }
+        self.last_poll = Some(now);
+        let mut events = vec![];
+        for idx in 0..self.watched.len() {
+            let modified = modified_time(&self.watched[idx].path);
+            if modified == self.watched[idx].modified {
+                continue;
+            }
+            self.watched[idx].modified = modified;
+            let (path, layer) = (self.watched[idx].path.clone(), self.watched[idx].layer);
+            match self.load_file(&path, layer) {
+                Ok(changed) => events.extend(changed.into_iter().map(ConfigEvent::Changed)),
+                Err(_) => events.push(ConfigEvent::ReloadFailed(path)),
+            }
+        }
+        events
+    }
+
+    /// Applies `--config key=value` (or `-c key=value`) pairs from command line arguments to the command line layer, ignoring every other argument.
+    ///
+    /// Returns the keys whose effective value changed.
+    pub fn apply_args<I, S>(&mut self, args: I) -> Result<Vec<String>, ConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut changed = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !matches!(arg.as_ref(), "--config" | "-c") {
+                continue;
+            }
+            let pair = args
+                .next()
+                .ok_or_else(|| ConfigError::MalformedArgument(arg.as_ref().to_string()))?;
+            let (key, text) = pair
+                .as_ref()
+                .split_once('=')
+                .ok_or_else(|| ConfigError::MalformedArgument(pair.as_ref().to_string()))?;
+            if self.set_from_str(ConfigLayer::CommandLine, key, text)? {
+                changed.push(key.to_string());
+            }
+        }
+        Ok(changed)
+    }
+
+    fn insert_entry(
+        &mut self,
+        key: &str,
+        default: ConfigValue,
+        description: &str,
+        validator: Option<ConfigValidator>,
+    ) -> Result<(), ConfigError> {
+        if self.is_registered(key) {
+            return Err(ConfigError::AlreadyRegistered(key.to_string()));
+        }
+        self.entries.insert(
+            key.to_string(),
+            ConfigEntry {
+                default,
+                description: description.to_string(),
+                validator,
+            },
+        );
+        for layer in [ConfigLayer::User, ConfigLayer::CommandLine].iter() {
+            let value = match self.unregistered.remove(&(*layer, key.to_string())) {
+                Some(value) => value,
+                None => continue,
+            };
+            match self.validate(key, value) {
+                Ok(value) => {
+                    self.layer_mut(*layer).insert(key.to_string(), value);
+                }
+                Err(err) => warn!("Ignoring the value loaded for {}: {}", key, err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the key is registered, and that the value has the key's type and passes its validator
+    fn validate(&self, key: &str, value: ConfigValue) -> Result<ConfigValue, ConfigError> {
+        let entry = self
+            .entries
+            .get(key)
+            .ok_or_else(|| ConfigError::UnknownKey(key.to_string()))?;
+        let found = value.to_string();
+        let value = value
+            .coerce_like(&entry.default)
+            .ok_or_else(|| ConfigError::TypeMismatch {
+                key: key.to_string(),
+                expected: entry.default.type_name(),
+                found,
+            })?;
+        if let Some(validator) = &entry.validator {
+            validator(&value).map_err(|reason| ConfigError::InvalidValue {
+                key: key.to_string(),
+                reason,
+            })?;
+        }
+        Ok(value)
+    }
+
+    fn replace_layer(
+        &mut self,
+        layer: ConfigLayer,
+        values: HashMap<String, ConfigValue>,
+    ) -> Vec<String> {
+        let previous: HashMap<String, ConfigValue> = self
+            .entries
+            .keys()
+            .filter_map(|key| self.get(key).map(|value| (key.clone(), value.clone())))
+            .collect();
+        *self.layer_mut(layer) = values;
+        previous
+            .into_iter()
+            .filter(|(key, value)| self.get(key) != Some(value))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    fn layer(&self, layer: ConfigLayer) -> &HashMap<String, ConfigValue> {
+        match layer {
+            ConfigLayer::User => &self.user,
+            ConfigLayer::CommandLine => &self.command_line,
+        }
+    }
+
+    fn layer_mut(&mut self, layer: ConfigLayer) -> &mut HashMap<String, ConfigValue> {
+        self.revision += 1;
+        match layer {
+            ConfigLayer::User => &mut self.user,
+            ConfigLayer::CommandLine => &mut self.command_line,
+        }
+    }
+}
+
+enum Format {
+    Toml,
+    Ron,
+}
+
+fn extension(path: &Path) -> Result<Format, ConfigError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => Ok(Format::Toml),
+        Some("ron") => Ok(Format::Ron),
+        _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+fn read_file(path: &Path) -> Result<ConfigNode, ConfigError> {
+    let text = fs::read_to_string(path)?;
+    Ok(match extension(path)? {
+        Format::Toml => toml::from_str(&text)?,
+        Format::Ron => ron::de::from_str(&text)?,
+    })
+}
+
+fn write_file(path: &Path, root: &ConfigNode) -> Result<(), ConfigError> {
+    let text = match extension(path)? {
+        // Through toml's own `Value`, which orders plain values before tables as TOML requires
+        Format::Toml => toml::to_string_pretty(&toml::Value::try_from(root)?)?,
+        Format::Ron => ron::ser::to_string_pretty(root, ron::ser::PrettyConfig::default())?,
+    };
+    Ok(fs::write(path, text)?)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn flatten(node: ConfigNode, prefix: String, into: &mut HashMap<String, ConfigValue>) {
+    match node {
+        ConfigNode::Value(value) => {
+            into.insert(prefix, value);
+        }
+        ConfigNode::Table(table) => {
+            for (key, child) in table {
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(child, path, into);
+            }
+        }
+    }
+}
+
+fn insert_nested(table: &mut BTreeMap<String, ConfigNode>, key: &str, value: ConfigValue) {
+    match key.find('.') {
+        None => {
+            table.insert(key.to_string(), ConfigNode::Value(value));
+        }
+        Some(idx) => {
+            let child = table
+                .entry(key[..idx].to_string())
+                .or_insert_with(|| ConfigNode::Table(BTreeMap::new()));
+            if let ConfigNode::Table(child) = child {
+                insert_nested(child, &key[idx + 1..], value);
+            }
+        }
+    }
+}
+
+/// Removes a value from a tree of tables, along with the tables it leaves empty
+fn remove_nested(table: &mut BTreeMap<String, ConfigNode>, key: &str) {
+    match key.find('.') {
+        None => {
+            table.remove(key);
+        }
+        Some(idx) => {
+            let emptied = match table.get_mut(&key[..idx]) {
+                Some(ConfigNode::Table(child)) => {
+                    remove_nested(child, &key[idx + 1..]);
+                    child.is_empty()
+                }
+                _ => false,
+            };
+            if emptied {
+                table.remove(&key[..idx]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file in the temporary directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+            fs::write(&path, contents).expect("Couldn't write the temporary file");
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn registry() -> ConfigRegistry {
+        let mut config = ConfigRegistry::default();
+        config
+            .register("render.wireframe", false, "")
+            .expect("Key isn't registered yet");
+        config
+    }
+
+    #[test]
+    fn unknown_keys_are_held_until_registered() {
+        let file = TempFile::new(
+            "unknown_keys.toml",
+            "[render]\nwireframe = true\nwirefram = true\n\n[profiler]\ntrace_file = \"trace.json\"\n",
+        );
+        let mut config = registry();
+        let changed = config
+            .load_file(&file.0, ConfigLayer::User)
+            .expect("Unknown keys don't fail the load");
+        assert_eq!(changed, vec![String::from("render.wireframe")]);
+        assert_eq!(config.get_as::<bool>("render.wireframe"), Some(true));
+        assert_eq!(config.get("profiler.trace_file"), None);
+
+        config
+            .register("profiler.trace_file", "", "")
+            .expect("Key isn't registered yet");
+        assert_eq!(
+            config.get_as::<String>("profiler.trace_file"),
+            Some(String::from("trace.json"))
+        );
+
+        // Saving keeps the keys which still aren't registered
+        let saved = TempFile::new("unknown_keys_saved.toml", "");
+        config
+            .save_file(&saved.0, ConfigLayer::User)
+            .expect("Couldn't save the user layer");
+        let mut reloaded = registry();
+        reloaded
+            .load_file(&saved.0, ConfigLayer::User)
+            .expect("Couldn't load the saved file");
+        reloaded
+            .register("render.wirefram", false, "")
+            .expect("Key isn't registered yet");
+        assert_eq!(reloaded.get_as::<bool>("render.wirefram"), Some(true));
+    }
+
+    #[test]
+    fn saving_keys_leaves_the_rest_of_the_file() {
+        let file = TempFile::new(
+            "save_keys.toml",
+            "[render]\nwireframe = true\nvsync = false\n\n[game]\nfov = 90\n",
+        );
+        let mut config = registry();
+        config
+            .register("game.fov", 70i64, "")
+            .expect("Key isn't registered yet");
+        config
+            .watch_file(&file.0, ConfigLayer::User)
+            .expect("Couldn't load the file");
+        config
+            .set(ConfigLayer::User, "game.fov", 100i64)
+            .expect("Value is valid");
+        config.unset(ConfigLayer::User, "render.wireframe");
+        config
+            .save_keys(
+                &file.0,
+                ConfigLayer::User,
+                &[String::from("game.fov"), String::from("render.wireframe")],
+            )
+            .expect("Couldn't save the keys");
+        // The registry wrote the file itself, so it isn't reloaded
+        assert!(config.poll_watched_files(Instant::now()).is_empty());
+
+        let mut reloaded = registry();
+        reloaded
+            .load_file(&file.0, ConfigLayer::User)
+            .expect("Couldn't load the saved file");
+        assert_eq!(
+            reloaded.layer_value(ConfigLayer::User, "render.wireframe"),
+            None
+        );
+        reloaded
+            .register("render.vsync", true, "")
+            .expect("Key isn't registered yet");
+        reloaded
+            .register("game.fov", 70i64, "")
+            .expect("Key isn't registered yet");
+        assert_eq!(reloaded.get_as::<bool>("render.vsync"), Some(false));
+        assert_eq!(reloaded.get_as::<i64>("game.fov"), Some(100));
+    }
+
+    #[test]
+    fn invalid_values_still_fail_the_load() {
+        let file = TempFile::new("invalid_values.toml", "[render]\nwireframe = \"yes\"\n");
+        let mut config = registry();
+        assert!(matches!(
+            config.load_file(&file.0, ConfigLayer::User),
+            Err(ConfigError::TypeMismatch { .. })
+        ));
+        assert_eq!(config.get_as::<bool>("render.wireframe"), Some(false));
+    }
+}
@@ -0,0 +1,424 @@
+/*
+    ABSTRACT: Crash reporting. `CrashReporter` installs a panic hook which writes a report file containing the panic,
+    a backtrace, the last lines logged, and every registered report section: text sections set once (e.g. the graphics
+    adapter, see thermite_gfx's hal_state.rs), and providers called when the report is written.
+    `CrashReportSystem` keeps the engine's own sections up to date: recent bus events, configuration, and frame timing.
+
+    The application loops catch panics from their systems, so that everything can still shut down before the panic resumes.
+*/
+use crate::input::mouse::MouseEvent;
+use crate::logging::sinks::LogRingBuffer;
+use crate::messaging::{
+    bus::BusRequest,
+    event::{ThermiteEvent, ThermiteEventType},
+    subscribe::Subscriber,
+};
+use crate::platform::app::{AppContext, System};
+use crate::tools::config::ConfigRegistry;
+use backtrace::Backtrace;
+use lazy_static::lazy_static;
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, Write},
+    panic,
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Bus events kept for the report by `CrashReportSystem`
+pub const RECENT_EVENTS: usize = 64;
+/// Frames averaged in the report's frame timing
+pub const TIMED_FRAMES: usize = 120;
+
+type SectionProvider = Box<dyn Fn() -> String + Send + Sync>;
+
+enum Section {
+    Text(String),
+    Provider(SectionProvider),
+}
+
+lazy_static! {
+    /// Named report sections, in the order they were first set
+    static ref SECTIONS: Mutex<Vec<(String, Section)>> = Mutex::new(vec![]);
+}
+
+thread_local! {
+    /// Set while this thread writes a report, so that a panic in a section provider doesn't report itself forever
+    static REPORTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn sections() -> MutexGuard<'static, Vec<(String, Section)>> {
+    // Sections are plain data, so poisoned sections are still valid sections
+    SECTIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn insert_section(name: &str, section: Section) {
+    let mut sections = sections();
+    match sections.iter_mut().find(|(existing, _)| existing == name) {
+        Some((_, existing)) => *existing = section,
+        None => sections.push((name.to_string(), section)),
+    }
+}
+
+/// Sets the text of a named section of any crash report written from now on
+pub fn set_section<T: Into<String>>(name: &str, text: T) {
+    insert_section(name, Section::Text(text.into()));
+}
+
+/// Sets a named section of crash reports, whose text is only built when a report is written. Must not panic.
+pub fn set_section_provider<F>(name: &str, provider: F)
+where
+    F: Fn() -> String + Send + Sync + 'static,
+{
+    insert_section(name, Section::Provider(Box::new(provider)));
+}
+
+/// Removes a named section from crash reports written from now on
+pub fn remove_section(name: &str) {
+    sections().retain(|(existing, _)| existing != name);
+}
+
+/// What panicked, where, and on which thread
+#[derive(Debug, Clone)]
+pub struct PanicDescription {
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: String,
+}
+
+/// Installs a panic hook which writes crash reports, see `install`
+pub struct CrashReporter {
+    report_dir: PathBuf,
+    log_buffer: Option<LogRingBuffer>,
+    log_lines: usize,
+}
+
+impl Default for CrashReporter {
+    /// Writes reports to `crashes/`, without log lines
+    fn default() -> Self {
+        Self {
+            report_dir: PathBuf::from("crashes"),
+            log_buffer: None,
+            log_lines: 0,
+        }
+    }
+}
+
+impl CrashReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the directory reports are written to, which is created when the first report is
+    pub fn with_report_dir<P: Into<PathBuf>>(mut self, report_dir: P) -> Self {
+        self.report_dir = report_dir.into();
+        self
+    }
+
+    /// Adds the last `lines` records of the given buffer (see `LoggerHandle::ring_buffer`) to reports
+    pub fn with_log_buffer(mut self, log_buffer: &LogRingBuffer, lines: usize) -> Self {
+        self.log_buffer = Some(log_buffer.clone());
+        self.log_lines = lines;
+        self
+    }
+
+    /// Installs the panic hook. The previous hook (by default, printing the panic to stderr) still runs first.
+    ///
+    /// Once the report is written, the panic is logged with the report's path and the logger is flushed.
+    pub fn install(self) {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if REPORTING.with(|reporting| reporting.replace(true)) {
+                return;
+            }
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match info.payload().downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => String::from("<non-string panic payload>"),
+                },
+            };
+            let description = PanicDescription {
+                message,
+                location: info.location().map(|location| location.to_string()),
+                thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+            };
+            match self.write_report(&description) {
+                Ok(path) => log::error!(
+                    "Thread '{}' panicked: {}, crash report written to {}",
+                    description.thread,
+                    description.message,
+                    path.display()
+                ),
+                Err(err) => log::error!(
+                    "Thread '{}' panicked: {}, couldn't write a crash report: {}",
+                    description.thread,
+                    description.message,
+                    err
+                ),
+            }
+            log::logger().flush();
+            REPORTING.with(|reporting| reporting.set(false));
+        }));
+    }
+
+    /// Writes a report for the given panic to a new file in the report directory, and returns its path
+    pub fn write_report(&self, description: &PanicDescription) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.report_dir)?;
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let path = self.report_dir.join(format!(
+            "crash-{}-{:03}.txt",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis()
+        ));
+        let mut file = File::create(&path)?;
+        self.write_report_to(&mut file, description, since_epoch)?;
+        file.flush()?;
+        Ok(path)
+    }
+
+    fn write_report_to<W: Write>(
+        &self,
+        writer: &mut W,
+        description: &PanicDescription,
+        since_epoch: Duration,
+    ) -> io::Result<()> {
+        writeln!(writer, "Thermite crash report")?;
+        writeln!(
+            writer,
+            "Time: {}s since the UNIX epoch",
+            since_epoch.as_secs()
+        )?;
+        writeln!(writer, "Thread: {}", description.thread)?;
+        writeln!(writer, "Panic: {}", description.message)?;
+        if let Some(location) = &description.location {
+            writeln!(writer, "Location: {}", location)?;
+        }
+        write_heading(writer, "Backtrace")?;
+        writeln!(writer, "{:?}", Backtrace::new())?;
+        // The panic may have happened while the sections were locked, in which case waiting would never end
+        match SECTIONS.try_lock() {
+            Ok(sections) => write_sections(writer, &sections)?,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => {
+                write_sections(writer, &poisoned.into_inner())?
+            }
+            Err(std::sync::TryLockError::WouldBlock) => {
+                write_heading(writer, "Sections")?;
+                writeln!(
+                    writer,
+                    "<unavailable, the panic happened while they were being updated>"
+                )?;
+            }
+        }
+        if let Some(log_buffer) = &self.log_buffer {
+            write_heading(writer, &format!("Log (last {} lines)", self.log_lines))?;
+            for record in log_buffer.last(self.log_lines) {
+                writeln!(writer, "{}", record)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_heading<W: Write>(writer: &mut W, heading: &str) -> io::Result<()> {
+    writeln!(writer)?;
+    writeln!(writer, "== {} ==", heading)
+}
+
+fn write_sections<W: Write>(writer: &mut W, sections: &[(String, Section)]) -> io::Result<()> {
+    for (name, section) in sections {
+        write_heading(writer, name)?;
+        match section {
+            Section::Text(text) => writeln!(writer, "{}", text)?,
+            Section::Provider(provider) => writeln!(writer, "{}", provider())?,
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the last bus events as text, for the "Recent events" section
+struct EventHistory {
+    events: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Subscriber<ThermiteEventType, ThermiteEvent> for EventHistory {
+    fn on_event(&self, event: &ThermiteEvent) -> BusRequest {
+        // Mouse motion would push everything else out of the history
+        if let ThermiteEvent::Mouse(MouseEvent::Motion(_))
+        | ThermiteEvent::Mouse(MouseEvent::RawMotion(_)) = event
+        {
+            return BusRequest::NoActionNeeded;
+        }
+        let mut events = self
+            .events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if events.len() == RECENT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(format!("{:?}", event));
+        BusRequest::NoActionNeeded
+    }
+}
+
+#[derive(Default)]
+struct FrameTiming {
+    frames: u64,
+    seconds_since_start: f64,
+    time_scale: f64,
+    paused: bool,
+    deltas: VecDeque<Duration>,
+    fixed_step: Duration,
+    total_fixed_steps: u64,
+    dropped_fixed_steps: u32,
+}
+
+impl FrameTiming {
+    fn describe(&self) -> String {
+        let last = self.deltas.back().copied().unwrap_or_default();
+        let worst = self.deltas.iter().max().copied().unwrap_or_default();
+        let average = if self.deltas.is_empty() {
+            Duration::from_secs(0)
+        } else {
+            self.deltas.iter().sum::<Duration>() / self.deltas.len() as u32
+        };
+        let fps = if average > Duration::from_secs(0) {
+            1.0 / average.as_secs_f64()
+        } else {
+            0.0
+        };
+        format!(
+            "Frames: {}\nTime since start: {:.3}s\nTime scale: {}{}\nLast frame: {:.3}ms\nLast {} frames: {:.3}ms average ({:.1} FPS), {:.3}ms worst\n\
+             Fixed step: {:.3}ms, {} steps run, {} dropped during the last frame",
+            self.frames,
+            self.seconds_since_start,
+            self.time_scale,
+            if self.paused { " (paused)" } else { "" },
+            last.as_secs_f64() * 1000.0,
+            self.deltas.len(),
+            average.as_secs_f64() * 1000.0,
+            fps,
+            worst.as_secs_f64() * 1000.0,
+            self.fixed_step.as_secs_f64() * 1000.0,
+            self.total_fixed_steps,
+            self.dropped_fixed_steps
+        )
+    }
+}
+
+/// Keeps the "Recent events", "Engine configuration" and "Frame timing" sections of crash reports up to date.
+///
+/// Add it before the other systems, so that it's initialized (and records events) first.
+pub struct CrashReportSystem {
+    history: Rc<EventHistory>,
+    timing: Arc<Mutex<FrameTiming>>,
+    config_revision: Option<u64>,
+}
+
+impl Default for CrashReportSystem {
+    fn default() -> Self {
+        Self {
+            history: Rc::new(EventHistory {
+                events: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_EVENTS))),
+            }),
+            timing: Arc::new(Mutex::new(FrameTiming::default())),
+            config_revision: None,
+        }
+    }
+}
+
+impl CrashReportSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update_config_section(&mut self, ctx: &AppContext) {
+        let config = ctx.config().borrow();
+        if self.config_revision != Some(config.revision()) {
+            self.config_revision = Some(config.revision());
+            set_section("Engine configuration", describe_config(&config));
+        }
+    }
+}
+
+impl System for CrashReportSystem {
+    fn on_init(&mut self, ctx: &mut AppContext) {
+        {
+            let mut bus = ctx
+                .event_bus()
+                .try_borrow_mut()
+                .expect("Couldn't borrow the event bus as mutable");
+            for category in [
+                ThermiteEventType::Input,
+                ThermiteEventType::Window,
+                ThermiteEventType::Application,
+                ThermiteEventType::Config,
+                ThermiteEventType::Console,
+                ThermiteEventType::Timer,
+                ThermiteEventType::Asset,
+            ]
+            .iter()
+            {
+                bus.subscribe(&self.history, category.clone());
+            }
+        }
+        let events = self.history.events.clone();
+        set_section_provider("Recent events", move || {
+            let events = events
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            events.iter().cloned().collect::<Vec<String>>().join("\n")
+        });
+        let timing = self.timing.clone();
+        set_section_provider("Frame timing", move || {
+            timing
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .describe()
+        });
+        self.update_config_section(ctx);
+    }
+
+    fn on_update(&mut self, ctx: &mut AppContext, _delta_sec: f32) {
+        {
+            let mut timing = self
+                .timing
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            timing.frames += 1;
+            timing.seconds_since_start = ctx.time().seconds_since_start();
+            timing.time_scale = ctx.time().time_scale();
+            timing.paused = ctx.time().is_paused();
+            if timing.deltas.len() == TIMED_FRAMES {
+                timing.deltas.pop_front();
+            }
+            timing.deltas.push_back(ctx.time().real_delta());
+            timing.fixed_step = ctx.fixed_timestep().step();
+            timing.total_fixed_steps = ctx.fixed_timestep().total_steps();
+            timing.dropped_fixed_steps = ctx.fixed_timestep().dropped_steps();
+        }
+        self.update_config_section(ctx);
+    }
+}
+
+/// Every registered key with its effective value, one `key = value` per line
+fn describe_config(config: &ConfigRegistry) -> String {
+    config
+        .keys()
+        .map(|key| match config.get(key) {
+            Some(value) => format!("{} = {}", key, value),
+            None => format!("{} = <unset>", key),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
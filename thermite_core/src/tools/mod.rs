@@ -1,2 +1,7 @@
+pub mod clock;
+pub mod config;
+pub mod crash;
+#[cfg(feature = "profiler")]
+pub mod profiler;
 pub mod resources;
 pub mod timer;
@@ -0,0 +1,305 @@
+/*
+    ABSTRACT: A CPU profiler recording nested, named timing spans on every thread. Spans are kept per thread
+    while they're nested, then moved into the global profiler, which summarizes each frame and can export
+    everything as Chrome Trace Event JSON (open it in chrome://tracing or https://ui.perfetto.dev).
+
+    Only compiled with the `profiler` feature. Use `profile_scope!` and `profile_frame!` (see lib.rs), which compile out without it.
+*/
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Spans kept for `write_chrome_trace`, the oldest are discarded past it so that a forgotten profiler doesn't eat all the memory.
+/// Frame summaries don't depend on it.
+pub const MAX_SPANS: usize = 1 << 20;
+/// Frame summaries kept, see `frame_summaries`
+pub const MAX_FRAME_SUMMARIES: usize = 300;
+
+/// A completed timing span
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub name: &'static str,
+    /// The profiler's id for the thread the span ran on
+    pub thread_id: u64,
+    /// How many spans were open on the thread when this one started
+    pub depth: u32,
+    /// Time between the profiler's start and the start of the span
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Time spent in spans with the same name during a frame
+#[derive(Debug, Clone)]
+pub struct SpanSummary {
+    pub name: &'static str,
+    pub calls: u32,
+    pub total: Duration,
+}
+
+/// Timings of a frame, see `profile_frame!`
+#[derive(Debug, Clone)]
+pub struct FrameSummary {
+    pub frame: u64,
+    pub duration: Duration,
+    /// From the most to the least time spent
+    pub spans: Vec<SpanSummary>,
+}
+
+struct ProfilerData {
+    /// The last `MAX_SPANS` spans, from oldest to newest
+    spans: VecDeque<Span>,
+    dropped_spans: u64,
+    thread_names: HashMap<u64, String>,
+    frame: u64,
+    frame_start: Duration,
+    /// Time spent in the spans completed during the current frame, by name
+    frame_totals: HashMap<&'static str, SpanSummary>,
+    frame_summaries: VecDeque<FrameSummary>,
+}
+
+impl ProfilerData {
+    fn record(&mut self, span: Span) {
+        let summary = self.frame_totals.entry(span.name).or_insert(SpanSummary {
+            name: span.name,
+            calls: 0,
+            total: Duration::from_secs(0),
+        });
+        summary.calls += 1;
+        summary.total += span.duration;
+        if self.spans.len() == MAX_SPANS {
+            self.spans.pop_front();
+            self.dropped_spans += 1;
+        }
+        self.spans.push_back(span);
+    }
+}
+
+lazy_static! {
+    /// Span start times are relative to this
+    static ref START: Instant = Instant::now();
+    static ref PROFILER: Mutex<ProfilerData> = Mutex::new(ProfilerData {
+        spans: VecDeque::new(),
+        dropped_spans: 0,
+        thread_names: HashMap::new(),
+        frame: 0,
+        frame_start: Duration::from_secs(0),
+        frame_totals: HashMap::new(),
+        frame_summaries: VecDeque::new(),
+    });
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+fn profiler() -> MutexGuard<'static, ProfilerData> {
+    // Spans are plain data, so a poisoned profiler is still a valid profiler
+    PROFILER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct ThreadSpans {
+    id: u64,
+    depth: u32,
+    completed: Vec<Span>,
+}
+
+impl ThreadSpans {
+    fn new() -> Self {
+        let id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+        let name = thread::current()
+            .name()
+            .map_or_else(|| format!("thread {}", id), |name| name.to_string());
+        profiler().thread_names.insert(id, name);
+        Self {
+            id,
+            depth: 0,
+            completed: vec![],
+        }
+    }
+
+    /// Moves the completed spans into the global profiler
+    fn flush(&mut self) {
+        if self.completed.is_empty() {
+            return;
+        }
+        let mut profiler = profiler();
+        for span in self.completed.drain(..) {
+            profiler.record(span);
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_SPANS: RefCell<ThreadSpans> = RefCell::new(ThreadSpans::new());
+}
+
+/// Times the scope it lives in, see `profile_scope!`
+pub struct ProfileScope {
+    name: &'static str,
+    start: Instant,
+    start_offset: Duration,
+}
+
+impl ProfileScope {
+    pub fn new(name: &'static str) -> Self {
+        THREAD_SPANS.with(|spans| spans.borrow_mut().depth += 1);
+        let start = Instant::now();
+        Self {
+            name,
+            start,
+            start_offset: start.saturating_duration_since(*START),
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        THREAD_SPANS.with(|spans| {
+            let mut spans = spans.borrow_mut();
+            spans.depth -= 1;
+            let span = Span {
+                name: self.name,
+                thread_id: spans.id,
+                depth: spans.depth,
+                start: self.start_offset,
+                duration,
+            };
+            spans.completed.push(span);
+            // Outermost spans hand everything over, so nothing is locked while spans are nested
+            if spans.depth == 0 {
+                spans.flush();
+            }
+        });
+    }
+}
+
+/// Ends the current frame, summarizing the spans completed on any thread during it, and starts the next one
+pub fn new_frame() {
+    THREAD_SPANS.with(|spans| spans.borrow_mut().flush());
+    let mut profiler = profiler();
+    let now = START.elapsed();
+    let mut spans: Vec<SpanSummary> = profiler
+        .frame_totals
+        .drain()
+        .map(|(_, summary)| summary)
+        .collect();
+    spans.sort_by_key(|summary| std::cmp::Reverse(summary.total));
+    let summary = FrameSummary {
+        frame: profiler.frame,
+        duration: now - profiler.frame_start,
+        spans,
+    };
+    if profiler.frame_summaries.len() == MAX_FRAME_SUMMARIES {
+        profiler.frame_summaries.pop_front();
+    }
+    profiler.frame_summaries.push_back(summary);
+    profiler.frame += 1;
+    profiler.frame_start = now;
+}
+
+/// Summaries of the last `MAX_FRAME_SUMMARIES` frames, from oldest to newest
+pub fn frame_summaries() -> Vec<FrameSummary> {
+    profiler().frame_summaries.iter().cloned().collect()
+}
+
+/// The summary of the last complete frame
+pub fn last_frame() -> Option<FrameSummary> {
+    profiler().frame_summaries.back().cloned()
+}
+
+/// How many of the oldest spans were discarded after reaching `MAX_SPANS`
+pub fn dropped_spans() -> u64 {
+    profiler().dropped_spans
+}
+
+/// Forgets every span and frame summary recorded so far
+pub fn clear() {
+    let mut profiler = profiler();
+    profiler.spans.clear();
+    profiler.dropped_spans = 0;
+    profiler.frame_totals.clear();
+    profiler.frame_summaries.clear();
+}
+
+#[derive(Serialize)]
+struct TraceEventArgs<'a> {
+    name: &'a str,
+}
+
+/// See the "Trace Event Format" document for Chrome's tracing
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cat: Option<&'a str>,
+    ph: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<TraceEventArgs<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: Vec<TraceEvent<'a>>,
+    display_time_unit: &'a str,
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+/// Writes the last `MAX_SPANS` spans recorded (on threads which closed their outermost span) as Chrome Trace Event JSON
+pub fn write_chrome_trace<W: io::Write>(writer: W) -> io::Result<()> {
+    THREAD_SPANS.with(|spans| spans.borrow_mut().flush());
+    let profiler = profiler();
+    let pid = std::process::id();
+    let thread_names = profiler.thread_names.iter().map(|(tid, name)| TraceEvent {
+        name: "thread_name",
+        cat: None,
+        ph: "M",
+        ts: None,
+        dur: None,
+        pid,
+        tid: *tid,
+        args: Some(TraceEventArgs { name }),
+    });
+    let spans = profiler.spans.iter().map(|span| TraceEvent {
+        name: span.name,
+        cat: Some("thermite"),
+        ph: "X",
+        ts: Some(micros(span.start)),
+        dur: Some(micros(span.duration)),
+        pid,
+        tid: span.thread_id,
+        args: None,
+    });
+    let trace = Trace {
+        trace_events: thread_names.chain(spans).collect(),
+        display_time_unit: "ms",
+    };
+    serde_json::to_writer(writer, &trace).map_err(io::Error::from)
+}
+
+/// Writes the spans recorded to the given file, see `write_chrome_trace`
+pub fn save_chrome_trace(path: &Path) -> io::Result<()> {
+    write_chrome_trace(BufWriter::new(File::create(path)?))
+}
@@ -1,8 +1,14 @@
+use crate::assets::{
+    integrity::IntegrityError,
+    vfs::{Vfs, VfsError, VirtualPath},
+};
+use crate::jobs::{handle::JobHandle, scheduler::JobScheduler};
+use crate::profile_scope;
 use std::{
     ffi::CString,
-    fs,
-    io::{self, Read},
+    io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 /// Errors relating to `Resource`
@@ -12,6 +18,13 @@ pub enum ResourceError {
     FileContainsNil(String),
     FailedToGetExePath(String),
     DeserializationFailure(String),
+    Vfs(VfsError),
+    /// No `AssetLoader` is registered for the asset's type and extension (or MIME type)
+    NoLoader(String),
+    /// The settings an asset was loaded with aren't those of its loader
+    WrongLoaderSettings(String),
+    /// The asset doesn't match its manifest entry, or its format (see `assets::integrity`)
+    Integrity(IntegrityError),
 }
 
 impl From<io::Error> for ResourceError {
@@ -20,6 +33,18 @@ impl From<io::Error> for ResourceError {
     }
 }
 
+impl From<VfsError> for ResourceError {
+    fn from(error: VfsError) -> Self {
+        ResourceError::Vfs(error)
+    }
+}
+
+impl From<IntegrityError> for ResourceError {
+    fn from(error: IntegrityError) -> Self {
+        ResourceError::Integrity(error)
+    }
+}
+
 impl std::fmt::Display for ResourceError {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -29,46 +54,62 @@ impl std::fmt::Display for ResourceError {
             ResourceError::DeserializationFailure(filename) => {
                 write!(fmt, "{:?}: {}", self, filename)
             }
+            ResourceError::Vfs(error) => write!(fmt, "{}", error),
+            ResourceError::NoLoader(asset) => write!(fmt, "{:?}: {}", self, asset),
+            ResourceError::WrongLoaderSettings(asset) => write!(fmt, "{:?}: {}", self, asset),
+            ResourceError::Integrity(error) => write!(fmt, "{}", error),
         }
     }
 }
 
 impl std::error::Error for ResourceError {}
 
-/// A `Resource` which points to and loads from a directory containing resources for the application
+/// A `Resource` which points to and loads from a directory of the virtual file system, containing resources for the application
+#[derive(Clone)]
 pub struct Resource {
-    root_path: PathBuf,
+    vfs: Arc<Vfs>,
+    root: VirtualPath,
 }
 
 impl Resource {
-    /// Create a new `Resource` to the given directory.
+    /// Create a new `Resource` to the given directory, in a VFS with the default mounts (see `Vfs::with_default_mounts`).
     ///
     /// ### Parameters
     ///
-    /// - `rel_path`: The relative path from the project executable to the resource directory.
+    /// - `rel_path`: The relative path from the project executable (or the working directory) to the resource directory.
     ///
     /// ### Returns
     ///
     /// A `Result` which is:
     ///
     /// - `Ok`: A `Resource` to use to access assets within the folder it points to.
-    /// - `Err`: A `ResourceError` describing the various IO errors that may have occurred during creation of the `Resource`.
+    /// - `Err`: A `ResourceError` if the path isn't valid UTF-8, is absolute, or escapes the VFS with `..`.
     pub fn new(rel_path: &Path) -> Result<Resource, ResourceError> {
-        // Grab the filename, or return if there's an error (? on Result)
-        let path_str: String = rel_path.to_str().unwrap_or("invalid_utf8_path").to_string();
-        let exe_filename = std::env::current_exe()
-            .map_err(|_| ResourceError::FailedToGetExePath(path_str.clone()))?;
-        // Grab the path to the executable via .parent(), checking for errors
-        let exe_path = exe_filename
-            .parent()
-            .ok_or(ResourceError::FailedToGetExePath(path_str))?;
-        // Return our resource
+        let path_str = rel_path
+            .to_str()
+            .ok_or_else(|| VfsError::InvalidPath(rel_path.to_string_lossy().to_string()))?;
+        Self::in_vfs(Arc::new(Vfs::with_default_mounts(None)), path_str)
+    }
+
+    /// Create a new `Resource` to the given directory of the given VFS, e.g. the application's (see `AppContext::vfs`)
+    pub fn in_vfs(vfs: Arc<Vfs>, dir: &str) -> Result<Resource, ResourceError> {
         Ok(Resource {
-            root_path: exe_path.join(rel_path),
+            vfs,
+            root: VirtualPath::new(dir)?,
         })
     }
 
-    /// Load the given file inside this `Resource`'s root path and return the data in a byte vector.
+    /// The VFS this `Resource` resolves its files through
+    pub fn vfs(&self) -> &Arc<Vfs> {
+        &self.vfs
+    }
+
+    /// The virtual path of the given resource
+    pub fn virtual_path(&self, resource_name: &str) -> Result<VirtualPath, ResourceError> {
+        Ok(self.root.join(resource_name)?)
+    }
+
+    /// Load the given file inside this `Resource`'s directory and return the data in a byte vector.
     ///
     /// ### Parameters
     ///
@@ -79,25 +120,39 @@ impl Resource {
     /// A `Result` which is:
     ///
     /// - `Ok`: A `Vec<u8>` containing the raw data bytes of the resource file in question.
-    /// - `Err`: A `ResourceError` describing the various IO errors that may have occurred during loading of the resource file.
+    /// - `Err`: A `ResourceError` describing the various VFS and IO errors that may have occurred during loading of the resource file.
     pub fn load_to_bytes(
         &self,
         resource_name: &str,
         check_for_interior_null: bool,
     ) -> Result<Vec<u8>, ResourceError> {
-        let mut file = fs::File::open(self.path_for(resource_name))?;
-        // File buffer of size +1 for null termination character
-        let mut buffer: Vec<u8> = Vec::with_capacity(file.metadata()?.len() as usize + 1);
-        file.read_to_end(&mut buffer)?;
+        profile_scope!("Resource::load_to_bytes");
+        let mut buffer = self.vfs.read(&self.virtual_path(resource_name)?)?;
         if check_for_interior_null {
             // Check the file for interior 0 (null) bytes
             if buffer.iter().find(|i| **i == 0).is_some() {
                 return Err(ResourceError::FileContainsNil(resource_name.to_string()));
             }
         }
+        // Room for a null termination character
+        buffer.reserve_exact(1);
         Ok(buffer)
     }
 
+    /// Same as `load_to_bytes`, but loads the file on one of the scheduler's worker threads.
+    pub fn load_to_bytes_async(
+        &self,
+        jobs: &JobScheduler,
+        resource_name: &str,
+        check_for_interior_null: bool,
+    ) -> JobHandle<Result<Vec<u8>, ResourceError>> {
+        let resource = self.clone();
+        let resource_name = resource_name.to_string();
+        jobs.spawn("Resource::load_to_bytes_async", move || {
+            resource.load_to_bytes(&resource_name, check_for_interior_null)
+        })
+    }
+
     /// Load the given file inside this `Resource`'s root path and return the data as a `CString`.
     ///
     /// ### Parameters
@@ -134,15 +189,14 @@ impl Resource {
     /// - `Ok`: A `String` containing the utf-8 data of the resource file in question.
     /// - `Err`: A `ResourceError` describing the various IO errors that may have occurred during loading of the resource file.
     pub fn load_to_string(&self, resource_name: &str) -> Result<String, ResourceError> {
-        Ok(fs::read_to_string(self.path_for(resource_name))?)
+        profile_scope!("Resource::load_to_string");
+        Ok(self
+            .vfs
+            .read_to_string(&self.virtual_path(resource_name)?)?)
     }
 
-    /// Returns a `PathBuf` representing the full path to the given resource.
-    pub fn path_for(&self, resource_name: &str) -> PathBuf {
-        let mut path = PathBuf::from(&self.root_path);
-        for path_component in resource_name.split("/") {
-            path = path.join(path_component);
-        }
-        path
+    /// Returns a `PathBuf` representing the full path on disk to the given resource, from the highest priority mount which has it.
+    pub fn path_for(&self, resource_name: &str) -> Result<PathBuf, ResourceError> {
+        Ok(self.vfs.real_path(&self.virtual_path(resource_name)?)?)
     }
 }
@@ -1,94 +1,800 @@
-use std::time::{Duration, Instant};
+/*
+    ABSTRACT: Timing. `Time` keeps the application's real and game clocks (the game clock can be scaled and paused),
+    reading real time from a `Clock` (see clock.rs),
+    `FixedTimestep` drives fixed-rate simulation, and `Timer`s count down game time, once or repeatedly.
+    `Timers` runs named timers for the application's systems, with callbacks and `TimerEvent`s (see `AppContext::timers_mut`),
+    and schedules events to be published after a delay.
+*/
+use crate::messaging::event::ThermiteEvent;
+use crate::platform::app::AppContext;
+use crate::tools::clock::{Clock, RealClock};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+/// Weight of the latest frame in the smoothed frame time, see `Time::fps`
+pub const FPS_SMOOTHING: f64 = 0.1;
+
+/// The application's clocks, ticked once per frame.
+///
+/// Real time is what the `Clock` measured (the platform's clock by default). Game time follows real time multiplied by the time scale,
+/// and stops while paused. Deltas (`delta`, `delta_sec`) are in game time, `real_delta` is in real time.
 pub struct Time {
+    clock: Arc<dyn Clock>,
     start: Instant,
     last_tick: Option<Instant>,
+    real_delta: Duration,
     delta: Duration,
     delta_sec: f32,
     delta_sec_f64: f64,
     seconds_since_start: f64,
+    game_time: Duration,
+    time_scale: f64,
+    paused: bool,
+    frame_count: u64,
+    smoothed_frame_sec: f64,
 }
 
 impl Default for Time {
+    /// Reads the platform's clock
     fn default() -> Self {
+        Self::with_clock(Arc::new(RealClock))
+    }
+}
+
+impl Time {
+    /// Reads real time from the given clock, e.g. a `ManualClock` in tests. Time starts at the clock's current instant.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
-            start: Instant::now(),
+            start: clock.now(),
+            clock,
             last_tick: None,
+            real_delta: Duration::from_secs(0),
             delta: Duration::from_secs(0),
             delta_sec: 0.0,
             delta_sec_f64: 0.0,
             seconds_since_start: 0.0,
+            game_time: Duration::from_secs(0),
+            time_scale: 1.0,
+            paused: false,
+            frame_count: 0,
+            smoothed_frame_sec: 0.0,
         }
     }
-}
 
-impl Time {
+    /// The clock real time is read from
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Ticks with the clock's current instant
     pub fn tick(&mut self) {
-        let tick = Instant::now();
+        self.tick_at(self.clock.now());
+    }
+
+    /// Ticks as if exactly `delta` of real time passed since the last tick (or since creation, for the first tick), regardless of real time.
+    ///
+    /// Used to drive time from a fixed clock, e.g. in headless applications.
+    pub fn advance_by(&mut self, delta: Duration) {
+        let last_tick = self.last_tick.unwrap_or(self.start);
+        self.last_tick = Some(last_tick);
+        self.tick_at(last_tick + delta);
+    }
+
+    fn tick_at(&mut self, tick: Instant) {
         if let Some(last_tick) = self.last_tick {
-            self.delta = tick - last_tick;
+            self.real_delta = tick.saturating_duration_since(last_tick);
+            self.delta = if self.paused {
+                Duration::from_secs(0)
+            } else {
+                self.real_delta.mul_f64(self.time_scale)
+            };
             self.delta_sec = self.delta.as_secs_f32();
             self.delta_sec_f64 = self.delta.as_secs_f64();
+            self.game_time += self.delta;
+            let frame_sec = self.real_delta.as_secs_f64();
+            self.smoothed_frame_sec = if self.smoothed_frame_sec > 0.0 {
+                self.smoothed_frame_sec + (frame_sec - self.smoothed_frame_sec) * FPS_SMOOTHING
+            } else {
+                frame_sec
+            };
         }
-        let duration_since_start = tick - self.start;
+        let duration_since_start = tick.saturating_duration_since(self.start);
         self.seconds_since_start = duration_since_start.as_secs_f64();
         self.last_tick = Some(tick);
+        self.frame_count += 1;
     }
 
+    /// Real time since the creation of this `Time`, according to its clock right now (rather than at the last tick)
     pub fn time_elapsed_since_start(&self) -> Duration {
-        Instant::now() - self.start
+        self.clock.now().saturating_duration_since(self.start)
+    }
+
+    /// Game time between the last two ticks
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Game time between the last two ticks, in seconds
+    pub fn delta_sec(&self) -> f32 {
+        self.delta_sec
+    }
+
+    /// Game time between the last two ticks, in seconds (double precision)
+    pub fn delta_sec_f64(&self) -> f64 {
+        self.delta_sec_f64
+    }
+
+    /// Real time between the last two ticks, unaffected by the time scale and pausing
+    pub fn real_delta(&self) -> Duration {
+        self.real_delta
+    }
+
+    /// Real time between the creation of this `Time` and its last tick, in seconds
+    pub fn seconds_since_start(&self) -> f64 {
+        self.seconds_since_start
+    }
+
+    /// Game time accumulated over every tick
+    pub fn game_time(&self) -> Duration {
+        self.game_time
+    }
+
+    /// How fast game time passes compared to real time, `1.0` by default
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets how fast game time passes compared to real time, e.g. `0.5` for slow motion. Negative scales are clamped to zero.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    /// Stops game time from the next tick, real time keeps going
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Number of ticks since the creation of this `Time`
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Frames per second, from the real frame time smoothed over the last frames (see `FPS_SMOOTHING`)
+    pub fn fps(&self) -> f64 {
+        if self.smoothed_frame_sec > 0.0 {
+            1.0 / self.smoothed_frame_sec
+        } else {
+            0.0
+        }
     }
 }
 
-pub enum TimerMagnitude {
-    Nanosecond,
-    Microsecond,
-    Millisecond,
-    Second,
+/// Drives a simulation at a fixed rate, independently of the frame rate.
+///
+/// Frame time is accumulated on every `advance`, and spent in steps of exactly `step` length.
+/// Whatever is left over is exposed as an interpolation factor (`alpha`) for rendering between the last two simulation states.
+/// To avoid a spiral of death when the simulation can't keep up, at most `max_steps_per_frame` steps are run per frame,
+/// and any whole steps beyond that are dropped and reported (see `is_behind`).
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps_per_frame: u32,
+    accumulator: Duration,
+    steps: u32,
+    dropped_steps: u32,
+    total_steps: u64,
 }
 
+impl Default for FixedTimestep {
+    /// 60 steps per second, with at most 5 steps per frame
+    fn default() -> Self {
+        Self::from_hz(60, 5)
+    }
+}
+
+impl FixedTimestep {
+    /// # Panics
+    /// If `step` is zero, or `max_steps_per_frame` is zero
+    pub fn new(step: Duration, max_steps_per_frame: u32) -> Self {
+        assert!(
+            step > Duration::from_secs(0),
+            "The fixed timestep must not be zero"
+        );
+        assert!(
+            max_steps_per_frame > 0,
+            "At least one step per frame must be allowed"
+        );
+        Self {
+            step,
+            max_steps_per_frame,
+            accumulator: Duration::from_secs(0),
+            steps: 0,
+            dropped_steps: 0,
+            total_steps: 0,
+        }
+    }
+
+    /// A fixed timestep running `steps_per_second` steps every second
+    ///
+    /// # Panics
+    /// If `steps_per_second` is zero, or `max_steps_per_frame` is zero
+    pub fn from_hz(steps_per_second: u32, max_steps_per_frame: u32) -> Self {
+        assert!(
+            steps_per_second > 0,
+            "The fixed timestep rate must not be zero"
+        );
+        Self::new(
+            Duration::from_secs(1) / steps_per_second,
+            max_steps_per_frame,
+        )
+    }
+
+    /// Accumulates the given frame time, and returns how many fixed steps should be simulated this frame
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulator += delta;
+        self.steps = 0;
+        while self.accumulator >= self.step && self.steps < self.max_steps_per_frame {
+            self.accumulator -= self.step;
+            self.steps += 1;
+        }
+        // Anything still owed past the cap is dropped, only the partial step is kept for interpolation
+        self.dropped_steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            self.dropped_steps += 1;
+        }
+        self.total_steps += u64::from(self.steps);
+        self.steps
+    }
+
+    /// Length of a single step
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Length of a single step, in seconds
+    pub fn step_sec(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    pub fn max_steps_per_frame(&self) -> u32 {
+        self.max_steps_per_frame
+    }
+
+    /// How far between the last simulated step and the next one the current frame is, from `0.0` to `1.0`
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32
+    }
+
+    /// Number of steps returned by the last `advance`
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Number of steps which the last `advance` couldn't fit under `max_steps_per_frame`, and dropped
+    pub fn dropped_steps(&self) -> u32 {
+        self.dropped_steps
+    }
+
+    /// Whether the simulation fell behind during the last `advance`, and had to drop steps
+    pub fn is_behind(&self) -> bool {
+        self.dropped_steps > 0
+    }
+
+    /// Number of steps simulated since this `FixedTimestep` was created
+    pub fn total_steps(&self) -> u64 {
+        self.total_steps
+    }
+}
+
+/// How many times a `Timer` runs before it's finished
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum TimerRepeat {
+    Once,
+    Times(u32),
+    Forever,
+}
+
+/// Counts down a duration, once or repeatedly. Ticked with game time by `Timers`, or by hand with `tick`.
+#[derive(Debug, Clone)]
 pub struct Timer {
-    pub magnitude: TimerMagnitude,
-    pub elapsed: f32,
-    pub duration: f32,
-    pub finished: bool,
+    duration: Duration,
+    elapsed: Duration,
+    repeat: TimerRepeat,
+    completions: u32,
+    just_completed: u32,
+    paused: bool,
 }
 
 impl Timer {
-    pub fn new(duration: f32, magnitude: TimerMagnitude) -> Self {
+    /// A timer which finishes once, after `duration`
+    pub fn new(duration: Duration) -> Self {
         Self {
-            magnitude: magnitude,
-            elapsed: 0.0,
-            duration: duration,
-            finished: false,
+            duration,
+            elapsed: Duration::from_secs(0),
+            repeat: TimerRepeat::Once,
+            completions: 0,
+            just_completed: 0,
+            paused: false,
+        }
+    }
+
+    /// A timer which completes every `duration`, forever
+    pub fn repeating(duration: Duration) -> Self {
+        Self::new(duration).with_repeat(TimerRepeat::Forever)
+    }
+
+    pub fn from_secs_f32(duration: f32) -> Self {
+        Self::new(Duration::from_secs_f32(duration))
+    }
+
+    pub fn with_repeat(mut self, repeat: TimerRepeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Advances the timer, and returns how many times it completed during this tick (more than once if `delta` spans several repeats)
+    pub fn tick(&mut self, delta: Duration) -> u32 {
+        self.just_completed = 0;
+        if self.paused || self.is_finished() {
+            return 0;
+        }
+        self.elapsed += delta;
+        while self.elapsed >= self.duration && !self.is_finished() {
+            self.completions += 1;
+            self.just_completed += 1;
+            if self.is_finished() || self.duration == Duration::from_secs(0) {
+                self.elapsed = self.duration;
+                break;
+            }
+            self.elapsed -= self.duration;
         }
+        self.just_completed
+    }
+
+    /// Starts counting down from the beginning again, forgetting past completions
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::from_secs(0);
+        self.completions = 0;
+        self.just_completed = 0;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
-    pub fn from_nanoseconds(duration: f32) -> Self {
-        Self::new(duration, TimerMagnitude::Nanosecond)
+    pub fn duration(&self) -> Duration {
+        self.duration
     }
 
-    pub fn from_microseconds(duration: f32) -> Self {
-        Self::new(duration, TimerMagnitude::Microsecond)
+    /// Changes the duration, keeping the time elapsed in the current run
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
     }
 
-    pub fn from_milliseconds(duration: f32) -> Self {
-        Self::new(duration, TimerMagnitude::Millisecond)
+    pub fn repeat(&self) -> TimerRepeat {
+        self.repeat
     }
 
-    pub fn from_seconds(duration: f32) -> Self {
-        Self::new(duration, TimerMagnitude::Second)
+    /// Time elapsed in the current run
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
     }
 
-    pub fn tick(&mut self, delta: f32) {
-        self.elapsed = (self.elapsed + delta).min(self.duration);
-        if self.elapsed >= self.duration {
-            self.finished = true;
+    /// Time left in the current run
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.elapsed)
+    }
+
+    /// How far through the current run the timer is, from `0.0` to `1.0`
+    pub fn progress(&self) -> f32 {
+        if self.duration == Duration::from_secs(0) {
+            1.0
+        } else {
+            (self.elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0) as f32
         }
     }
 
-    pub fn reset(&mut self) {
-        self.finished = false;
-        self.elapsed = 0.0;
+    /// How many times the timer completed since it was created or reset
+    pub fn completions(&self) -> u32 {
+        self.completions
+    }
+
+    /// Whether the timer completed during the last tick
+    pub fn just_completed(&self) -> bool {
+        self.just_completed > 0
+    }
+
+    /// Whether the timer has run as many times as it repeats. Never true for `TimerRepeat::Forever`.
+    pub fn is_finished(&self) -> bool {
+        match self.repeat {
+            TimerRepeat::Once => self.completions >= 1,
+            TimerRepeat::Times(times) => self.completions >= times,
+            TimerRepeat::Forever => false,
+        }
+    }
+}
+
+/// Timer events, published on the bus under `ThermiteEventType::Timer`
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum TimerEvent {
+    /// A named timer completed this many times during the frame
+    Completed { name: String, times: u32 },
+    /// A named timer finished its last repeat, and was removed
+    Finished { name: String },
+}
+
+impl From<TimerEvent> for ThermiteEvent {
+    fn from(timer_evt: TimerEvent) -> Self {
+        ThermiteEvent::Timer(timer_evt)
+    }
+}
+
+/// Called every time a timer completes
+pub type TimerCallback = Box<dyn FnMut(&mut AppContext)>;
+
+struct NamedTimer {
+    /// Tells the timer from one added later under the same name, see `Timers::update`
+    id: u64,
+    timer: Timer,
+    callback: Option<TimerCallback>,
+    /// Published every time the timer completes, see `Timers::schedule_event`
+    event: Option<ThermiteEvent>,
+}
+
+/// Named timers, ticked with game time once per frame before the systems update (see `Systems::update`).
+///
+/// Every completion runs the timer's callback and publishes its scheduled event, if any, then publishes `TimerEvent::Completed`.
+/// Timers which finished their last repeat are removed, and publish `TimerEvent::Finished`.
+#[derive(Default)]
+pub struct Timers {
+    timers: BTreeMap<String, NamedTimer>,
+    next_id: u64,
+}
+
+impl Timers {
+    /// Adds a timer, replacing any timer with the same name
+    pub fn add<N: Into<String>>(&mut self, name: N, timer: Timer) {
+        self.insert(name.into(), timer, None, None);
+    }
+
+    /// Adds a timer which calls `callback` every time it completes, replacing any timer with the same name
+    pub fn add_with_callback<N, F>(&mut self, name: N, timer: Timer, callback: F)
+    where
+        N: Into<String>,
+        F: FnMut(&mut AppContext) + 'static,
+    {
+        self.insert(name.into(), timer, Some(Box::new(callback)), None);
+    }
+
+    /// Adds a timer which publishes `event` every time it completes, replacing any timer with the same name
+    pub fn add_with_event<N: Into<String>>(&mut self, name: N, timer: Timer, event: ThermiteEvent) {
+        self.insert(name.into(), timer, None, Some(event));
+    }
+
+    /// Publishes `event` once `delay` of game time has passed, replacing any timer with the same name.
+    /// Removing the timer before then cancels the event.
+    pub fn schedule_event<N: Into<String>>(
+        &mut self,
+        name: N,
+        delay: Duration,
+        event: ThermiteEvent,
+    ) {
+        self.add_with_event(name, Timer::new(delay), event);
+    }
+
+    /// Removes a timer, returns it if it existed
+    pub fn remove(&mut self, name: &str) -> Option<Timer> {
+        self.timers.remove(name).map(|named| named.timer)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Timer> {
+        self.timers.get(name).map(|named| &named.timer)
+    }
+
+    /// Mutable access to a timer, e.g. to pause it
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Timer> {
+        self.timers.get_mut(name).map(|named| &mut named.timer)
+    }
+
+    /// Every timer's name, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.timers.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    /// Ticks every timer, runs the callbacks of those which completed and publishes their events
+    pub fn update(ctx: &mut AppContext) {
+        let delta = ctx.time().delta();
+        let completed: Vec<(String, u32)> = ctx
+            .timers_mut()
+            .timers
+            .iter_mut()
+            .map(|(name, named)| (name.clone(), named.timer.tick(delta)))
+            .filter(|(_, times)| *times > 0)
+            .collect();
+        for (name, times) in completed {
+            // Callbacks get the whole context, so they're taken out of their timer while they run. The timer itself stays,
+            // so that callbacks can remove or replace it.
+            let (id, mut callback, event) = match ctx.timers_mut().timers.get_mut(&name) {
+                Some(named) => (named.id, named.callback.take(), named.event.clone()),
+                None => continue,
+            };
+            if let Some(callback) = &mut callback {
+                for _ in 0..times {
+                    callback(ctx);
+                }
+            }
+            if let Some(event) = &event {
+                for _ in 0..times {
+                    ctx.publish(event.clone());
+                }
+            }
+            ctx.publish(
+                TimerEvent::Completed {
+                    name: name.clone(),
+                    times,
+                }
+                .into(),
+            );
+            let timers = &mut ctx.timers_mut().timers;
+            let finished = match timers.get_mut(&name) {
+                // Unless a callback removed or replaced the timer, which then stays as the callback left it
+                Some(named) if named.id == id => {
+                    named.callback = callback;
+                    named.timer.is_finished()
+                }
+                _ => false,
+            };
+            if finished {
+                timers.remove(&name);
+                ctx.publish(TimerEvent::Finished { name }.into());
+            }
+        }
+    }
+
+    fn insert(
+        &mut self,
+        name: String,
+        timer: Timer,
+        callback: Option<TimerCallback>,
+        event: Option<ThermiteEvent>,
+    ) {
+        self.next_id += 1;
+        self.timers.insert(
+            name,
+            NamedTimer {
+                id: self.next_id,
+                timer,
+                callback,
+                event,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::{bus::BusRequest, event::ThermiteEventType, subscribe::Subscriber};
+    use crate::platform::app::AppEvent;
+    use crate::tools::clock::ManualClock;
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn manual_time() -> (ManualClock, Time) {
+        let clock = ManualClock::new();
+        let time = Time::with_clock(Arc::new(clock.clone()));
+        (clock, time)
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: RefCell<Vec<ThermiteEvent>>,
+    }
+
+    impl Subscriber<ThermiteEventType, ThermiteEvent> for Recorder {
+        fn on_event(&self, event: &ThermiteEvent) -> BusRequest {
+            self.events.borrow_mut().push(event.clone());
+            BusRequest::NoActionNeeded
+        }
+    }
+
+    #[test]
+    fn time_follows_the_clock() {
+        let (clock, mut time) = manual_time();
+        time.tick();
+        assert_eq!(time.delta(), ms(0));
+        clock.advance(ms(100));
+        time.tick();
+        assert_eq!(time.delta(), ms(100));
+        assert_eq!(time.real_delta(), ms(100));
+        assert_eq!(time.game_time(), ms(100));
+        assert_eq!(time.frame_count(), 2);
+        assert!((time.fps() - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn time_scale_only_affects_game_time() {
+        let (clock, mut time) = manual_time();
+        time.tick();
+        time.set_time_scale(0.5);
+        clock.advance(ms(100));
+        time.tick();
+        assert_eq!(time.delta(), ms(50));
+        assert_eq!(time.real_delta(), ms(100));
+        time.set_time_scale(-1.0);
+        assert_eq!(time.time_scale(), 0.0);
+    }
+
+    #[test]
+    fn pausing_stops_game_time() {
+        let (clock, mut time) = manual_time();
+        time.tick();
+        clock.advance(ms(100));
+        time.tick();
+        time.pause();
+        clock.advance(ms(100));
+        time.tick();
+        assert_eq!(time.delta(), ms(0));
+        assert_eq!(time.real_delta(), ms(100));
+        assert_eq!(time.game_time(), ms(100));
+        assert!((time.seconds_since_start() - 0.2).abs() < 1e-9);
+        time.resume();
+        clock.advance(ms(100));
+        time.tick();
+        assert_eq!(time.game_time(), ms(200));
+    }
+
+    #[test]
+    fn fixed_timestep_drops_steps_past_the_cap() {
+        let (clock, mut time) = manual_time();
+        let mut fixed = FixedTimestep::from_hz(10, 2);
+        time.tick();
+        clock.advance(ms(150));
+        time.tick();
+        assert_eq!(fixed.advance(time.delta()), 1);
+        assert!((fixed.alpha() - 0.5).abs() < 1e-6);
+        // A long hitch: only 2 of the 5 steps owed (with the half step carried over) run, the rest are dropped
+        clock.advance(ms(470));
+        time.tick();
+        assert_eq!(fixed.advance(time.delta()), 2);
+        assert_eq!(fixed.dropped_steps(), 3);
+        assert!(fixed.is_behind());
+        assert_eq!(fixed.total_steps(), 3);
+        // Only the partial step is kept
+        assert!((fixed.alpha() - 0.2).abs() < 1e-6);
+        clock.advance(ms(80));
+        time.tick();
+        assert_eq!(fixed.advance(time.delta()), 1);
+        assert!(!fixed.is_behind());
+    }
+
+    #[test]
+    fn timers_expire_with_game_time() {
+        let clock = ManualClock::new();
+        let mut ctx = AppContext::default();
+        *ctx.time_mut() = Time::with_clock(Arc::new(clock.clone()));
+        let recorder = Rc::new(Recorder::default());
+        for category in [ThermiteEventType::Timer, ThermiteEventType::Application].iter() {
+            ctx.event_bus()
+                .borrow_mut()
+                .subscribe(&recorder, category.clone());
+        }
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        ctx.timers_mut().add_with_callback(
+            "repeat",
+            Timer::new(ms(100)).with_repeat(TimerRepeat::Times(3)),
+            move |_| counter.set(counter.get() + 1),
+        );
+        ctx.timers_mut()
+            .schedule_event("later", ms(250), AppEvent::Initialized.into());
+        let frame = |ctx: &mut AppContext, advance: Duration| {
+            clock.advance(advance);
+            ctx.time_mut().tick();
+            Timers::update(ctx);
+        };
+        frame(&mut ctx, ms(0));
+        frame(&mut ctx, ms(99));
+        assert_eq!(calls.get(), 0);
+        frame(&mut ctx, ms(1));
+        assert_eq!(calls.get(), 1);
+        // Paused game time doesn't count
+        ctx.time_mut().pause();
+        frame(&mut ctx, ms(1000));
+        assert_eq!(calls.get(), 1);
+        ctx.time_mut().resume();
+        // One frame spanning two repeats completes twice, and finishes the timer
+        frame(&mut ctx, ms(200));
+        assert_eq!(calls.get(), 3);
+        assert!(ctx.timers().get("repeat").is_none());
+        assert!(ctx.timers().get("later").is_none());
+        let events = recorder.events.borrow();
+        assert!(events.contains(&ThermiteEvent::App(AppEvent::Initialized)));
+        assert!(events.contains(
+            &TimerEvent::Finished {
+                name: "repeat".into()
+            }
+            .into()
+        ));
+        assert!(events.contains(
+            &TimerEvent::Finished {
+                name: "later".into()
+            }
+            .into()
+        ));
+    }
+
+    #[test]
+    fn cancelled_events_are_never_published() {
+        let clock = ManualClock::new();
+        let mut ctx = AppContext::default();
+        *ctx.time_mut() = Time::with_clock(Arc::new(clock.clone()));
+        let recorder = Rc::new(Recorder::default());
+        ctx.event_bus()
+            .borrow_mut()
+            .subscribe(&recorder, ThermiteEventType::Application);
+        ctx.timers_mut()
+            .schedule_event("later", ms(100), AppEvent::Initialized.into());
+        ctx.time_mut().tick();
+        ctx.timers_mut().remove("later");
+        clock.advance(ms(200));
+        ctx.time_mut().tick();
+        Timers::update(&mut ctx);
+        assert!(recorder.events.borrow().is_empty());
+    }
+
+    #[test]
+    fn callbacks_can_remove_or_replace_their_timer() {
+        let clock = ManualClock::new();
+        let mut ctx = AppContext::default();
+        *ctx.time_mut() = Time::with_clock(Arc::new(clock.clone()));
+        ctx.timers_mut()
+            .add_with_callback("removed", Timer::repeating(ms(100)), |ctx| {
+                ctx.timers_mut().remove("removed");
+            });
+        ctx.timers_mut()
+            .add_with_callback("replaced", Timer::repeating(ms(100)), |ctx| {
+                ctx.timers_mut().add("replaced", Timer::new(ms(500)));
+            });
+        ctx.time_mut().tick();
+        clock.advance(ms(100));
+        ctx.time_mut().tick();
+        Timers::update(&mut ctx);
+        assert!(ctx.timers().get("removed").is_none());
+        let replaced = ctx.timers().get("replaced").expect("Timer was replaced");
+        assert_eq!(replaced.duration(), ms(500));
     }
 }
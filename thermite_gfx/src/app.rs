@@ -0,0 +1,562 @@
+/*
+    ABSTRACT: A reusable, windowed application which owns the windows, event bus, timing and
+    winit -> Thermite event translation, and drives user-provided `System`s through their lifecycle hooks.
+*/
+use crate::hal::hal_state;
+use crate::renderer::{Renderer, RendererSystem};
+use crate::window::{WindowChange, WindowManager};
+use crate::window_config::{WindowConfig, WindowConfigError};
+use log::warn;
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    rc::Rc,
+    sync::Arc,
+};
+use thermite_core::{
+    assets::{cache::CacheBudget, integrity::AssetManifest, vfs::Vfs},
+    jobs::scheduler::JobSystem,
+    messaging::event::{Event, ThermiteEvent, ThermiteEventType},
+    platform::{
+        app::{AppContext, System, Systems},
+        translate::{translate_device_event, translate_window_event},
+    },
+    tools::{
+        clock::Clock,
+        config::{ConfigError, ConfigLayer, ConfigRegistry, ConfigValue},
+        timer::{FixedTimestep, Time},
+    },
+};
+use winit::{
+    event::{Event as WinitEvent, StartCause, WindowEvent},
+    event_loop::{ControlFlow, EventLoopWindowTarget},
+    window::WindowId,
+};
+
+/// Width of the primary window, in logical pixels
+pub const WINDOW_WIDTH_KEY: &str = "window.width";
+/// Height of the primary window, in logical pixels
+pub const WINDOW_HEIGHT_KEY: &str = "window.height";
+/// Worker threads started for the application's jobs, one per core (except the main thread's) if 0
+pub const WORKER_THREADS_KEY: &str = "jobs.worker_threads";
+/// Memory assets may take in RAM before unreferenced ones are evicted from the cache, in MiB, unlimited if 0
+pub const ASSET_CPU_BUDGET_KEY: &str = "assets.cpu_budget_mb";
+/// Memory assets may take on the GPU before unreferenced ones are evicted from the cache, in MiB, unlimited if 0.
+/// Both budgets at 0 turn the cache off.
+pub const ASSET_GPU_BUDGET_KEY: &str = "assets.gpu_budget_mb";
+/// File the profiler's Chrome trace is written to on exit, nothing is written if empty
+#[cfg(feature = "profiler")]
+pub const PROFILER_TRACE_FILE_KEY: &str = "profiler.trace_file";
+
+/// Errors which can occur while building an `App`
+#[derive(Debug)]
+pub enum AppError {
+    Window(WindowConfigError),
+    Config(ConfigError),
+}
+
+impl From<WindowConfigError> for AppError {
+    fn from(error: WindowConfigError) -> Self {
+        AppError::Window(error)
+    }
+}
+
+impl From<ConfigError> for AppError {
+    fn from(error: ConfigError) -> Self {
+        AppError::Config(error)
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Window(err) => write!(fmt, "{:?}: {}", self, err),
+            AppError::Config(err) => write!(fmt, "{:?}: {}", self, err),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Window(err) => Some(err),
+            AppError::Config(err) => Some(err),
+        }
+    }
+}
+
+/// A `System` which the `App` also gives its windows to, e.g. to render to them or open more
+pub trait WindowSystem: System {
+    /// Called once per iteration of the loop, before the systems update, with the running event loop to create windows on
+    /// (see `WindowManager::create_window_on`)
+    fn on_windows(
+        &mut self,
+        _ctx: &mut AppContext,
+        _windows: &mut WindowManager<ThermiteEvent>,
+        _target: &EventLoopWindowTarget<ThermiteEvent>,
+    ) {
+    }
+    /// Called for every window opened, after `on_init` for the windows the application starts with
+    fn on_window_opened(
+        &mut self,
+        _ctx: &mut AppContext,
+        _windows: &WindowManager<ThermiteEvent>,
+        _window_id: WindowId,
+    ) {
+    }
+    /// Called for every window closed, before it's destroyed
+    fn on_window_closed(&mut self, _ctx: &mut AppContext, _window_id: WindowId) {}
+}
+
+/// Runs a `WindowSystem`'s `System` hooks along with every other system, while the `App` keeps it for its window hooks
+struct SharedSystem(Rc<RefCell<dyn WindowSystem>>);
+
+impl System for SharedSystem {
+    fn on_init(&mut self, ctx: &mut AppContext) {
+        self.0.borrow_mut().on_init(ctx)
+    }
+
+    fn on_fixed_update(&mut self, ctx: &mut AppContext, step_sec: f32) {
+        self.0.borrow_mut().on_fixed_update(ctx, step_sec)
+    }
+
+    fn on_update(&mut self, ctx: &mut AppContext, delta_sec: f32) {
+        self.0.borrow_mut().on_update(ctx, delta_sec)
+    }
+
+    fn on_render(&mut self, ctx: &mut AppContext) {
+        self.0.borrow_mut().on_render(ctx)
+    }
+
+    fn on_suspend(&mut self, ctx: &mut AppContext) {
+        self.0.borrow_mut().on_suspend(ctx)
+    }
+
+    fn on_resume(&mut self, ctx: &mut AppContext) {
+        self.0.borrow_mut().on_resume(ctx)
+    }
+
+    fn on_shutdown(&mut self, ctx: &mut AppContext) {
+        self.0.borrow_mut().on_shutdown(ctx)
+    }
+}
+
+/// Builds an `App` from window configurations, engine configuration and a set of `System`s
+#[derive(Default)]
+pub struct AppBuilder {
+    window: WindowConfig,
+    window_config_file: Option<PathBuf>,
+    additional_windows: Vec<WindowConfig>,
+    config: ConfigRegistry,
+    user_config_file: Option<PathBuf>,
+    args: Vec<String>,
+    fixed_timestep: FixedTimestep,
+    clock_source: Option<Arc<dyn Clock>>,
+    vfs: Option<Vfs>,
+    hot_reload: bool,
+    manifest: Option<AssetManifest>,
+    systems: Systems,
+    window_systems: Vec<Rc<RefCell<dyn WindowSystem>>>,
+    renderer: Renderer,
+}
+
+impl AppBuilder {
+    /// The default `WindowConfig` for the primary window, an empty configuration, the default `FixedTimestep`, and no systems
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the title of the application's primary window
+    pub fn with_title<T: Into<String>>(mut self, title: T) -> Self {
+        self.window.title = title.into();
+        self
+    }
+
+    /// Sets the logical size of the application's primary window
+    pub fn with_size(mut self, size: [u32; 2]) -> Self {
+        self.window.size = size;
+        self
+    }
+
+    /// Sets the whole configuration of the application's primary window
+    pub fn with_window_config(mut self, config: WindowConfig) -> Self {
+        self.window = config;
+        self
+    }
+
+    /// Loads the primary window's configuration from this RON file when building, if it exists, instead of the one given to this builder.
+    ///
+    /// The configuration is saved back to the file on exit, with the window's last position and size (see `WindowConfig::remember`).
+    pub fn with_window_config_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.window_config_file = Some(path.into());
+        self
+    }
+
+    /// Adds a secondary window (e.g. a tool window), sharing the primary window's event loop
+    pub fn with_additional_window(mut self, config: WindowConfig) -> Self {
+        self.additional_windows.push(config);
+        self
+    }
+
+    /// Shares the given configuration with the application's systems. The engine's own keys are registered into it when building.
+    pub fn with_config(mut self, config: ConfigRegistry) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Loads the user configuration layer from this TOML or RON file when building, if it exists, and reloads it whenever it changes (or is created)
+    pub fn with_user_config_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.user_config_file = Some(path.into());
+        self
+    }
+
+    /// Applies the `--config key=value` overrides in these command line arguments when building, see `ConfigRegistry::apply_args`
+    pub fn with_command_line_args<I: IntoIterator<Item = String>>(mut self, args: I) -> Self {
+        self.args = args.into_iter().collect();
+        self
+    }
+
+    /// Sets the rate at which the systems' fixed updates run
+    pub fn with_fixed_timestep(mut self, fixed_timestep: FixedTimestep) -> Self {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    /// Loads the application's files through the given VFS, rather than one with the default mounts (see `Vfs::with_default_mounts`)
+    pub fn with_vfs(mut self, vfs: Vfs) -> Self {
+        self.vfs = Some(vfs);
+        self
+    }
+
+    /// Reloads assets when their file changes on disk (see `AssetServer::set_hot_reload`)
+    pub fn with_hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    /// Verifies the assets the manifest lists against it as they load (see `AssetServer::set_manifest`)
+    pub fn with_manifest(mut self, manifest: AssetManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Reads real time from the given clock rather than the platform's, e.g. a `ManualClock` for deterministic captures
+    pub fn with_clock_source(mut self, clock_source: Arc<dyn Clock>) -> Self {
+        self.clock_source = Some(clock_source);
+        self
+    }
+
+    /// Adds a `System` to the application. Systems run in the order they're added.
+    pub fn with_system<S: System + 'static>(mut self, system: S) -> Self {
+        self.systems.add(Box::new(system));
+        self
+    }
+
+    /// Adds a `WindowSystem` to the application, which runs in the order it's added like any other `System`
+    pub fn with_window_system<S: WindowSystem + 'static>(mut self, system: S) -> Self {
+        self.add_window_system(system);
+        self
+    }
+
+    /// The handle on the renderer of the `App` this builds, e.g. for its console commands (see `hal_state::register_console_commands`)
+    pub fn renderer(&self) -> Renderer {
+        self.renderer.clone()
+    }
+
+    fn add_window_system<S: WindowSystem + 'static>(&mut self, system: S) {
+        let system = Rc::new(RefCell::new(system));
+        self.window_systems.push(system.clone());
+        self.systems.add(Box::new(SharedSystem(system)));
+    }
+
+    /// Registers the engine's configuration keys, layers the configuration, creates the application's windows and builds the `App`,
+    /// whose renderer (see `RendererSystem`) runs after every system added to this builder.
+    ///
+    /// The primary window's size from the configuration (`window.width` and `window.height`) takes precedence over its `WindowConfig`.
+    ///
+    /// Fails if a configuration file can't be read or holds invalid values, or if a window can't be created from its configuration.
+    pub fn build(mut self) -> Result<App, AppError> {
+        self.add_window_system(RendererSystem::new(self.renderer.clone()));
+        let mut window_config = match &self.window_config_file {
+            Some(path) => WindowConfig::load_or(path, self.window)?,
+            None => self.window,
+        };
+        let mut config = self.config;
+        register_window_config(&mut config, &window_config)?;
+        hal_state::register_config(&mut config)?;
+        config.register_validated(
+            WORKER_THREADS_KEY,
+            0i64,
+            "Worker threads started for the application's jobs, one per core (except the main thread's) if 0",
+            |value| match value {
+                ConfigValue::Int(threads) if *threads >= 0 => Ok(()),
+                _ => Err(String::from("must be a number of threads, or 0")),
+            },
+        )?;
+        fn mebibytes(value: &ConfigValue) -> Result<(), String> {
+            match value {
+                ConfigValue::Int(size) if *size >= 0 => Ok(()),
+                _ => Err(String::from("must be a number of MiB, or 0")),
+            }
+        }
+        config.register_validated(
+            ASSET_CPU_BUDGET_KEY,
+            256i64,
+            "Memory assets may take in RAM before unreferenced ones are evicted from the cache, in MiB, unlimited if 0",
+            mebibytes,
+        )?;
+        config.register_validated(
+            ASSET_GPU_BUDGET_KEY,
+            512i64,
+            "Memory assets may take on the GPU before unreferenced ones are evicted from the cache, in MiB, unlimited if 0",
+            mebibytes,
+        )?;
+        #[cfg(feature = "profiler")]
+        config.register(
+            PROFILER_TRACE_FILE_KEY,
+            "profile.json",
+            "File the profiler's Chrome trace is written to on exit, nothing is written if empty",
+        )?;
+        if let Some(path) = &self.user_config_file {
+            config.watch_file(path, ConfigLayer::User)?;
+        }
+        config.apply_args(&self.args)?;
+        window_config.size = [
+            config
+                .get_as(WINDOW_WIDTH_KEY)
+                .unwrap_or(window_config.size[0]),
+            config
+                .get_as(WINDOW_HEIGHT_KEY)
+                .unwrap_or(window_config.size[1]),
+        ];
+        let mut windows = WindowManager::new();
+        windows.create_window_from_config(&window_config)?;
+        for config in &self.additional_windows {
+            windows.create_window_from_config(config)?;
+        }
+        let jobs = match config.get_as::<u32>(WORKER_THREADS_KEY) {
+            Some(threads) if threads > 0 => JobSystem::new(threads as usize),
+            _ => JobSystem::default(),
+        };
+        let budget = CacheBudget::from_mebibytes(
+            config.get_as::<u32>(ASSET_CPU_BUDGET_KEY).unwrap_or(0) as usize,
+            config.get_as::<u32>(ASSET_GPU_BUDGET_KEY).unwrap_or(0) as usize,
+        );
+        let mut context = AppContext::with_jobs(config, jobs);
+        *context.fixed_timestep_mut() = self.fixed_timestep;
+        if let Some(clock_source) = self.clock_source {
+            *context.time_mut() = Time::with_clock(clock_source);
+        }
+        if let Some(vfs) = self.vfs {
+            context.set_vfs(vfs);
+        }
+        context.assets_mut().set_hot_reload(self.hot_reload);
+        context.assets_mut().set_manifest(self.manifest);
+        context.assets_mut().set_budget(budget);
+        hal_state::register_loaders(context.assets_mut());
+        Ok(App {
+            windows,
+            window_config,
+            window_config_file: self.window_config_file,
+            context,
+            systems: self.systems,
+            window_systems: self.window_systems,
+        })
+    }
+}
+
+fn register_window_config(
+    config: &mut ConfigRegistry,
+    window_config: &WindowConfig,
+) -> Result<(), ConfigError> {
+    fn non_zero(value: &ConfigValue) -> Result<(), String> {
+        match value {
+            ConfigValue::Int(size) if *size > 0 && *size <= i64::from(u32::MAX) => Ok(()),
+            _ => Err(String::from("must be a positive number of pixels")),
+        }
+    }
+    config.register_validated(
+        WINDOW_WIDTH_KEY,
+        i64::from(window_config.size[0]),
+        "Width of the primary window, in logical pixels",
+        non_zero,
+    )?;
+    config.register_validated(
+        WINDOW_HEIGHT_KEY,
+        i64::from(window_config.size[1]),
+        "Height of the primary window, in logical pixels",
+        non_zero,
+    )
+}
+
+/// A windowed Thermite application.
+///
+/// Closing the primary window exits the application, closing any other window only closes it.
+pub struct App {
+    windows: WindowManager<ThermiteEvent>,
+    window_config: WindowConfig,
+    window_config_file: Option<PathBuf>,
+    context: AppContext,
+    systems: Systems,
+    window_systems: Vec<Rc<RefCell<dyn WindowSystem>>>,
+}
+
+impl App {
+    pub fn builder() -> AppBuilder {
+        AppBuilder::new()
+    }
+
+    /// The application's windows
+    pub fn windows(&self) -> &WindowManager<ThermiteEvent> {
+        &self.windows
+    }
+
+    /// The context shared with the application's systems
+    pub fn context(&mut self) -> &mut AppContext {
+        &mut self.context
+    }
+
+    /// Runs the application's loop until something requests an exit, then shuts every system down and exits the process.
+    ///
+    /// If a system panics, the loop ends and every system is still shut down, then the process exits with code 101.
+    pub fn run(self) -> ! {
+        let App {
+            mut windows,
+            mut window_config,
+            window_config_file,
+            mut context,
+            mut systems,
+            window_systems,
+        } = self;
+        let event_loop = windows.event_loop();
+        let mut panicked = false;
+        event_loop.run(move |event, target, control_flow| {
+            let destroyed = matches!(event, WinitEvent::LoopDestroyed);
+            // After a panic, systems may be left half updated, so nothing else runs before they're shut down
+            if panicked && !destroyed {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            // A panicking system ends the loop, but everything is still shut down (the panic hook already reported it, see tools::crash)
+            let handled = panic::catch_unwind(AssertUnwindSafe(|| match event {
+                // The very first event, before anything else happens
+                WinitEvent::NewEvents(StartCause::Init) => {
+                    // Games redraw continuously, rather than waiting on OS events
+                    *control_flow = ControlFlow::Poll;
+                    systems.init(&mut context);
+                    sync_windows(&mut windows, &window_systems, &mut context);
+                }
+                // Events emitted by the winit window
+                WinitEvent::WindowEvent { window_id, event } => {
+                    if let WindowEvent::Focused(focused) = event {
+                        windows.set_focused(window_id, focused);
+                    }
+                    if let Some(evt) = translate_window_event(window_id, &event) {
+                        // Only input goes through the input contexts, window events go straight to the bus
+                        match evt.category() {
+                            ThermiteEventType::Input => context.route_input(evt),
+                            _ => context.publish(evt),
+                        }
+                    }
+                    if let WindowEvent::CloseRequested = event {
+                        if windows.primary_id() == Some(window_id) {
+                            context.request_exit();
+                        } else {
+                            windows.close(window_id);
+                            sync_windows(&mut windows, &window_systems, &mut context);
+                        }
+                    }
+                }
+                // Events coming straight from hardware devices
+                WinitEvent::DeviceEvent { event, .. } => {
+                    if let Some(evt) = translate_device_event(&event) {
+                        context.route_input(evt);
+                    }
+                }
+                // Continuous dynamic graphics rendering (loop "main body")
+                WinitEvent::MainEventsCleared => {
+                    for system in &window_systems {
+                        system
+                            .borrow_mut()
+                            .on_windows(&mut context, &mut windows, target);
+                    }
+                    sync_windows(&mut windows, &window_systems, &mut context);
+                    systems.update(&mut context);
+                    if let Some(primary) = windows.primary() {
+                        primary.request_redraw();
+                    }
+                }
+                // Systems render once per frame, to whichever windows they own surfaces for
+                WinitEvent::RedrawRequested(window_id)
+                    if windows.primary_id() == Some(window_id) =>
+                {
+                    systems.render(&mut context)
+                }
+                WinitEvent::Suspended => systems.suspend(&mut context),
+                WinitEvent::Resumed => systems.resume(&mut context),
+                // Last event to be emitted, period.
+                WinitEvent::LoopDestroyed => {
+                    if let (Some(path), Some(primary)) = (&window_config_file, windows.primary()) {
+                        window_config.remember(primary);
+                        if let Err(err) = window_config.save(path) {
+                            warn!("Couldn't save the window configuration: {}", err);
+                        }
+                    }
+                    systems.shutdown(&mut context);
+                    #[cfg(feature = "profiler")]
+                    save_profile(&context);
+                    // The event loop never returns, so make sure buffered log records are written
+                    log::logger().flush();
+                }
+                _ => (),
+            }));
+            if handled.is_err() {
+                panicked = true;
+            }
+            if destroyed && panicked {
+                // Rust's exit code for a panicking process
+                std::process::exit(101);
+            }
+            if panicked || context.exit_requested() {
+                *control_flow = ControlFlow::Exit;
+            }
+        })
+    }
+}
+
+/// Tells the window systems about the windows opened and closed since the last call, then destroys the closed windows
+fn sync_windows(
+    windows: &mut WindowManager<ThermiteEvent>,
+    window_systems: &[Rc<RefCell<dyn WindowSystem>>],
+    context: &mut AppContext,
+) {
+    for change in windows.take_changes() {
+        for system in window_systems {
+            let mut system = system.borrow_mut();
+            match change {
+                WindowChange::Opened(window_id) => {
+                    system.on_window_opened(context, windows, window_id)
+                }
+                WindowChange::Closed(window_id) => system.on_window_closed(context, window_id),
+            }
+        }
+    }
+    windows.destroy_closed();
+}
+
+#[cfg(feature = "profiler")]
+fn save_profile(context: &AppContext) {
+    let path: String = context
+        .config()
+        .borrow()
+        .get_as(PROFILER_TRACE_FILE_KEY)
+        .unwrap_or_default();
+    if path.is_empty() {
+        return;
+    }
+    match thermite_core::tools::profiler::save_chrome_trace(std::path::Path::new(&path)) {
+        Ok(()) => log::info!("Wrote the profiler's trace to {}", path),
+        Err(err) => warn!("Couldn't write the profiler's trace to {}: {}", path, err),
+    }
+}
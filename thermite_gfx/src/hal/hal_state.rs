@@ -1,6 +1,7 @@
-use crate::primitives::buffer::VertexBuffer;
-use crate::resources::mesh::Mesh;
-use crate::shaders::shader::{PushConstants, ShaderSet};
+use crate::primitives::buffer::{BufferError, VertexBuffer};
+use crate::renderer::Renderer;
+use crate::resources::mesh::{Mesh, MeshLoader};
+use crate::shaders::shader::{PushConstants, ShaderCode, ShaderLoader, ShaderSet};
 use backend::{Backend as ThermiteBackend, Device as ThermiteDevice, Instance as ThermiteInstance};
 use gfx_hal::{
     self,
@@ -14,7 +15,22 @@ use gfx_hal::{
 };
 use raw_window_handle::HasRawWindowHandle;
 use std::mem::ManuallyDrop;
-use thermite_core::tools::resources;
+use std::sync::Arc;
+use thermite_core::assets::{
+    handle::{Asset, Handle, LoadState},
+    server::AssetServer,
+};
+use thermite_core::console::{
+    command::{ArgKind, ConsoleCommand, ConsoleError},
+    shell::Console,
+};
+use thermite_core::profile_scope;
+use thermite_core::tools::{
+    config::{ConfigError, ConfigEvent, ConfigLayer, ConfigRegistry, FromConfigValue},
+    crash,
+    resources::ResourceError,
+};
+use winit::window::WindowId;
 
 type ThermiteRenderPass = <ThermiteBackend as Backend>::RenderPass;
 type ThermitePipelineLayout = <ThermiteBackend as Backend>::PipelineLayout;
@@ -23,6 +39,166 @@ type ThermiteSwapchainImage =
     <<ThermiteBackend as Backend>::Surface as PresentationSurface<ThermiteBackend>>::SwapchainImage;
 type ThermiteFramebuffer = <ThermiteBackend as Backend>::Framebuffer;
 
+/// Draw polygons as lines instead of filling them
+pub const WIREFRAME_KEY: &str = "render.wireframe";
+/// How long to wait for the previous submission to complete before recording new commands, in nanoseconds
+pub const RENDER_TIMEOUT_KEY: &str = "render.render_timeout_ns";
+/// How long to wait for a swapchain image to be available, in nanoseconds
+pub const ACQUIRE_TIMEOUT_KEY: &str = "render.acquire_timeout_ns";
+/// Directory containing the meshes, relative to the executable
+pub const MESH_DIR_KEY: &str = "assets.mesh_dir";
+/// Directory containing the compiled SPIR-V shaders, relative to the executable
+pub const SHADER_DIR_KEY: &str = "assets.shader_dir";
+/// Directory containing the GLSL shader sources, compiled at runtime instead of loading the SPIR-V shaders. Empty to load the SPIR-V shaders.
+pub const SHADER_SOURCE_DIR_KEY: &str = "assets.shader_source_dir";
+/// The name of the shader set the pipeline is made of
+const SHADER_SET: &str = "test";
+/// The console command reloading the renderer's shaders, see `register_console_commands`
+pub const RELOAD_SHADERS_COMMAND: &str = "reload_shaders";
+
+/// Registers the configuration keys read by the `HALState`, with their defaults
+pub fn register_config(config: &mut ConfigRegistry) -> Result<(), ConfigError> {
+    fn positive(value: &thermite_core::tools::config::ConfigValue) -> Result<(), String> {
+        match i64::from_config_value(value) {
+            Some(ns) if ns > 0 => Ok(()),
+            _ => Err(String::from("must be a positive number of nanoseconds")),
+        }
+    }
+    config.register(
+        WIREFRAME_KEY,
+        false,
+        "Draw polygons as lines instead of filling them",
+    )?;
+    config.register_validated(
+        RENDER_TIMEOUT_KEY,
+        1_000_000_000i64,
+        "How long to wait for the previous frame's submission, in nanoseconds",
+        positive,
+    )?;
+    config.register_validated(
+        ACQUIRE_TIMEOUT_KEY,
+        1_000_000_000i64,
+        "How long to wait for a swapchain image, in nanoseconds",
+        positive,
+    )?;
+    config.register(
+        MESH_DIR_KEY,
+        "assets/meshes/",
+        "Directory containing the meshes",
+    )?;
+    config.register(
+        SHADER_DIR_KEY,
+        "assets/shaders/spirv",
+        "Directory containing the compiled SPIR-V shaders",
+    )?;
+    config.register(
+        SHADER_SOURCE_DIR_KEY,
+        "",
+        "Directory containing the GLSL shader sources, compiled at runtime instead of loading the SPIR-V shaders \
+         (e.g. thermite_gfx/assets/shaders/glsl from the repository, to iterate on them with hot reloading). \
+         Empty to load the SPIR-V shaders",
+    )?;
+    Ok(())
+}
+
+/// Registers the renderer's console commands, acting on the given renderer: `wireframe [enabled]` and `reload_shaders`.
+///
+/// `wireframe` rebuilds the pipeline and sets `render.wireframe` (publishing `ConfigEvent::Changed`), `reload_shaders` reloads
+/// the shaders, which are swapped in between frames. Both fail while the renderer isn't running.
+pub fn register_console_commands(
+    console: &mut Console,
+    renderer: &Renderer,
+) -> Result<(), ConsoleError> {
+    let wireframe_renderer = renderer.clone();
+    console.register(
+        ConsoleCommand::new(
+            "wireframe",
+            "Toggles drawing polygons as lines, or turns it on or off",
+            move |ctx, args| {
+                let enabled = match args.bool(0) {
+                    Some(enabled) => enabled,
+                    None => !ctx
+                        .config()
+                        .borrow()
+                        .get_as::<bool>(WIREFRAME_KEY)
+                        .unwrap_or(false),
+                };
+                wireframe_renderer
+                    .set_wireframe(enabled)
+                    .map_err(|err| ConsoleError::Failed(err.to_string()))?;
+                let changed =
+                    ctx.config()
+                        .borrow_mut()
+                        .set(ConfigLayer::User, WIREFRAME_KEY, enabled)?;
+                if changed {
+                    ctx.publish(ConfigEvent::Changed(WIREFRAME_KEY.to_string()).into());
+                }
+                Ok(format!("Wireframe {}", if enabled { "on" } else { "off" }))
+            },
+        )
+        .with_optional_arg("enabled", ArgKind::Bool),
+    )?;
+    let reload_renderer = renderer.clone();
+    console.register(ConsoleCommand::new(
+        RELOAD_SHADERS_COMMAND,
+        "Recompiles the shaders from the shader directory and rebuilds the pipelines",
+        move |ctx, _| {
+            reload_renderer
+                .reload_shaders(ctx.assets())
+                .map_err(|err| ConsoleError::Failed(err.to_string()))?;
+            Ok(String::from("Reloading shaders"))
+        },
+    ))
+}
+
+/// Registers the loaders of the renderer's assets (meshes and shaders) with the given `AssetServer`, see `AppContext::assets_mut`
+pub fn register_loaders(assets: &mut AssetServer) {
+    assets.register_loader(MeshLoader);
+    assets.register_loader(ShaderLoader);
+}
+
+/// The virtual paths of the pipeline's shaders, by stage: GLSL sources if `SHADER_SOURCE_DIR_KEY` is set, SPIR-V otherwise
+fn shader_paths(config: &ConfigRegistry) -> Result<Vec<(ShaderStageFlags, String)>, HALError> {
+    let source_dir: String = config_value(config, SHADER_SOURCE_DIR_KEY)?;
+    let (dir, suffix) = if source_dir.is_empty() {
+        (config_value(config, SHADER_DIR_KEY)?, ".spv")
+    } else {
+        (source_dir, "")
+    };
+    Ok([
+        (ShaderStageFlags::VERTEX, "vert"),
+        (ShaderStageFlags::FRAGMENT, "frag"),
+    ]
+    .iter()
+    .map(|(stage, extension)| {
+        (
+            *stage,
+            format!("{}/{}.{}{}", dir, SHADER_SET, extension, suffix),
+        )
+    })
+    .collect())
+}
+
+/// Waits for an asset the renderer needs, see `AssetServer::wait`
+fn wait_for<T: Asset>(assets: &AssetServer, handle: &Handle<T>) -> Result<Arc<T>, HALError> {
+    if let LoadState::Failed(inner) = assets.wait(handle) {
+        return Err(HALError::AssetError {
+            path: handle.path().to_string(),
+            inner,
+        });
+    }
+    Ok(handle
+        .get()
+        .expect("An asset which didn't fail has loaded once waited for"))
+}
+
+/// Reads a registered configuration value, see `register_config`
+fn config_value<T: FromConfigValue>(config: &ConfigRegistry, key: &str) -> Result<T, HALError> {
+    config
+        .get_as(key)
+        .ok_or_else(|| HALError::ConfigError(ConfigError::UnknownKey(key.to_string())))
+}
+
 /// The error type reported by this module, regarding Hardware Abstraction Layer operation errors/failures
 #[derive(Debug)]
 pub enum HALError {
@@ -37,7 +213,22 @@ pub enum HALError {
     ShaderError(crate::shaders::shader::ShaderError),
     PipelineError(gfx_hal::pso::CreationError),
     ResourceError(thermite_core::tools::resources::ResourceError),
+    /// An asset the renderer needs is still loading
+    AssetPending(String),
+    /// An asset the renderer needs failed to load
+    AssetError {
+        path: String,
+        inner: Arc<ResourceError>,
+    },
+    BufferError(BufferError),
+    ConfigError(ConfigError),
     AcquireError(gfx_hal::window::AcquireError),
+    /// No surface was created for the window with this ID
+    UnknownWindow(WindowId),
+    /// The window's surface can't be presented to by the queue family the device was opened with
+    UnsupportedSurface(WindowId),
+    /// The renderer hasn't started (or couldn't), or has shut down, see `Renderer`
+    NotRunning,
 }
 
 impl From<gfx_hal::window::InitError> for HALError {
@@ -82,6 +273,18 @@ impl From<thermite_core::tools::resources::ResourceError> for HALError {
     }
 }
 
+impl From<BufferError> for HALError {
+    fn from(error: BufferError) -> Self {
+        HALError::BufferError(error)
+    }
+}
+
+impl From<ConfigError> for HALError {
+    fn from(error: ConfigError) -> Self {
+        HALError::ConfigError(error)
+    }
+}
+
 impl From<gfx_hal::window::AcquireError> for HALError {
     fn from(error: gfx_hal::window::AcquireError) -> Self {
         HALError::AcquireError(error)
@@ -92,6 +295,10 @@ impl std::fmt::Display for HALError {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             HALError::UnsupportedBackend => write!(fmt, "{:?}", self),
+            HALError::UnknownWindow(_) => write!(fmt, "{:?}", self),
+            HALError::UnsupportedSurface(_) => write!(fmt, "{:?}", self),
+            HALError::NotRunning => write!(fmt, "{:?}", self),
+            HALError::AssetPending(_) => write!(fmt, "{:?}", self),
             HALError::InitializationError(err) => write!(fmt, "{:?}: {}", self, err),
             HALError::CreationError(err) => write!(fmt, "{:?}: {}", self, err),
             HALError::AdapterError { message, inner } => {
@@ -101,6 +308,11 @@ impl std::fmt::Display for HALError {
             HALError::ShaderError(err) => write!(fmt, "{:?}: {}", self, err),
             HALError::PipelineError(err) => write!(fmt, "{:?}: {}", self, err),
             HALError::ResourceError(err) => write!(fmt, "{:?}: {}", self, err),
+            HALError::AssetError { path, inner } => {
+                write!(fmt, "AssetError ({}): {}", path, inner)
+            }
+            HALError::BufferError(err) => write!(fmt, "{:?}: {}", self, err),
+            HALError::ConfigError(err) => write!(fmt, "{:?}: {}", self, err),
             HALError::AcquireError(err) => write!(fmt, "{:?}: {}", self, err),
         }
     }
@@ -115,16 +327,40 @@ impl std::error::Error for HALError {
             HALError::ShaderError(err) => Some(err),
             HALError::PipelineError(err) => Some(err),
             HALError::ResourceError(err) => Some(err),
+            HALError::AssetError { inner, .. } => Some(inner.as_ref()),
+            HALError::BufferError(err) => Some(err),
+            HALError::ConfigError(err) => Some(err),
             HALError::AcquireError(err) => Some(err),
             _ => None,
         }
     }
 }
 
+/// A window's presentation surface and its swapchain
+pub struct WindowSurface<B: Backend> {
+    window_id: WindowId,
+    surface: B::Surface,
+}
+
+/// Finds the surface of the given window, in a way which only borrows the surfaces and not the rest of `HALResources`
+fn find_surface<B: Backend>(
+    surfaces: &mut [WindowSurface<B>],
+    window_id: WindowId,
+) -> Result<&mut B::Surface, HALError> {
+    surfaces
+        .iter_mut()
+        .find(|ws| ws.window_id == window_id)
+        .map(|ws| &mut ws.surface)
+        .ok_or(HALError::UnknownWindow(window_id))
+}
+
 /// The resources associated with the HALState (requires manual memory management)
+///
+/// Every window rendered to has its own surface and swapchain, but they all share the device, render passes and pipelines.
+/// Render passes are created for the format of the first window's surface, which every other surface must support.
 pub struct HALResources<B: Backend> {
     instance: B::Instance,
-    surface: B::Surface,
+    surfaces: Vec<WindowSurface<B>>,
     adapter: Adapter<B>,
     logical_device: B::Device,
     queue_group: QueueGroup<ThermiteBackend>,
@@ -136,14 +372,57 @@ pub struct HALResources<B: Backend> {
     format: Format,
     submission_complete_fence: B::Fence,
     rendering_complete_semaphore: B::Semaphore,
+    render_timeout_ns: u64,
+    acquire_timeout_ns: u64,
     vertex_buffer: VertexBuffer<B>, // This will be one big buffer containing everything in the Scene, and we will have multiple descriptors which point to this buffer but with different sizes and offsets
                                     //vb_descriptors: Vec<Descriptor> // <- like this
 }
 
 impl HALResources<ThermiteBackend> {
-    /// Queries the capabilities of the window Surface and recreates the swapchain from those capabilities, and returns the resulting `Extent2D`
-    pub fn recreate_swapchain(&mut self, extent: Extent2D) -> Result<Extent2D, HALError> {
-        let capabilities = self.surface.capabilities(&self.adapter.physical_device);
+    /// Creates a surface (and later, its swapchain) for another window, to render to it
+    pub fn add_window(
+        &mut self,
+        window_id: WindowId,
+        window: &impl HasRawWindowHandle,
+    ) -> Result<(), HALError> {
+        let surface = unsafe { self.instance.create_surface(window)? };
+        let supported = self
+            .adapter
+            .queue_families
+            .iter()
+            .any(|qf| qf.id() == self.queue_group.family && surface.supports_queue_family(qf));
+        if !supported {
+            unsafe { self.instance.destroy_surface(surface) };
+            return Err(HALError::UnsupportedSurface(window_id));
+        }
+        self.surfaces.push(WindowSurface { window_id, surface });
+        Ok(())
+    }
+
+    /// Destroys the surface and swapchain of the given window, which must be done before the window itself is closed
+    pub fn remove_window(&mut self, window_id: WindowId) -> Result<(), HALError> {
+        let idx = self
+            .surfaces
+            .iter()
+            .position(|ws| ws.window_id == window_id)
+            .ok_or(HALError::UnknownWindow(window_id))?;
+        let WindowSurface { mut surface, .. } = self.surfaces.remove(idx);
+        unsafe {
+            let _ = self.logical_device.wait_idle();
+            surface.unconfigure_swapchain(&self.logical_device);
+            self.instance.destroy_surface(surface);
+        }
+        Ok(())
+    }
+
+    /// Queries the capabilities of the given window's Surface and recreates its swapchain from those capabilities, and returns the resulting `Extent2D`
+    pub fn recreate_swapchain(
+        &mut self,
+        window_id: WindowId,
+        extent: Extent2D,
+    ) -> Result<Extent2D, HALError> {
+        let surface = find_surface(&mut self.surfaces, window_id)?;
+        let capabilities = surface.capabilities(&self.adapter.physical_device);
         let mut swapchain_config = SwapchainConfig::from_caps(&capabilities, self.format, extent);
         // *NOTE: This seems to fix some fullscreen slowdown on macOS.
         if capabilities.image_count.contains(&3) {
@@ -153,30 +432,31 @@ impl HALResources<ThermiteBackend> {
         let extent = swapchain_config.extent;
 
         unsafe {
-            self.surface
-                .configure_swapchain(&self.logical_device, swapchain_config)?;
+            surface.configure_swapchain(&self.logical_device, swapchain_config)?;
         };
         Ok(extent)
     }
 
-    /// Waits for the command pool to finish submission via fences, and resets it
-    pub unsafe fn reset_command_pool(&mut self, render_timeout_ns: u64) -> Result<(), HALError> {
+    /// Waits (up to the configured render timeout) for the command pool to finish submission via fences, and resets it
+    pub unsafe fn reset_command_pool(&mut self) -> Result<(), HALError> {
         use gfx_hal::pool::CommandPool;
+        profile_scope!("HALResources::reset_command_pool");
         self.logical_device
-            .wait_for_fence(&self.submission_complete_fence, render_timeout_ns)?;
+            .wait_for_fence(&self.submission_complete_fence, self.render_timeout_ns)?;
         self.logical_device
             .reset_fence(&self.submission_complete_fence)?;
         self.command_pool.reset(false);
         Ok(())
     }
 
-    /// Acquires a new image from the swapchain for rendering
+    /// Acquires a new image from the given window's swapchain for rendering, waiting up to the configured acquire timeout
     pub unsafe fn acquire_image(
         &mut self,
-        acquire_timeout_ns: u64,
+        window_id: WindowId,
     ) -> Result<ThermiteSwapchainImage, HALError> {
+        profile_scope!("HALResources::acquire_image");
         // Map the result tuple to just the swapchain image, because that's what we want
-        match self.surface.acquire_image(acquire_timeout_ns) {
+        match find_surface(&mut self.surfaces, window_id)?.acquire_image(self.acquire_timeout_ns) {
             Ok(img_tuple) => Ok(img_tuple.0),
             Err(err) => Err(HALError::AcquireError(err)),
         }
@@ -227,6 +507,7 @@ impl HALResources<ThermiteBackend> {
         use gfx_hal::command::{
             ClearColor, ClearValue, CommandBuffer, CommandBufferFlags, SubpassContents,
         };
+        profile_scope!("HALResources::record_cmds_for_submission");
         self.command_buffer
             .begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
         self.command_buffer.set_viewports(0, &[viewport.clone()]);
@@ -265,9 +546,15 @@ impl HALResources<ThermiteBackend> {
         self.command_buffer.finish()
     }
 
-    /// Submits all commands in the command buffer and presents the surface, and returns whether or not the operation was successful
-    pub unsafe fn submit_cmds(&mut self, surface_image: ThermiteSwapchainImage) -> bool {
+    /// Submits all commands in the command buffer and presents the given window's surface, and returns whether or not the operation was successful
+    pub unsafe fn submit_cmds(
+        &mut self,
+        window_id: WindowId,
+        surface_image: ThermiteSwapchainImage,
+    ) -> Result<bool, HALError> {
         use gfx_hal::queue::{CommandQueue, Submission};
+        profile_scope!("HALResources::submit_cmds");
+        let surface = find_surface(&mut self.surfaces, window_id)?;
         let submission = Submission {
             command_buffers: vec![&self.command_buffer],
             wait_semaphores: None,
@@ -275,11 +562,11 @@ impl HALResources<ThermiteBackend> {
         };
         self.queue_group.queues[0].submit(submission, Some(&self.submission_complete_fence));
         let result = self.queue_group.queues[0].present_surface(
-            &mut self.surface,
+            surface,
             surface_image,
             Some(&self.rendering_complete_semaphore),
         );
-        result.is_err()
+        Ok(result.is_err())
     }
 
     /// Destroys the given framebuffer
@@ -299,11 +586,35 @@ unsafe fn push_constant_bytes<T>(push_constants: &T) -> &[u32] {
 /// The Hardware Abstraction Layer state, manages all low-level graphics resources and provides mid-level API
 pub struct HALState {
     pub resources: ManuallyDrop<HALResources<ThermiteBackend>>,
+    mesh: Handle<Mesh>,
+    shaders: Vec<(ShaderStageFlags, Handle<ShaderCode>)>,
+    /// The versions of the assets the GPU resources were made from, see `swap_reloaded_assets`
+    mesh_version: u64,
+    shader_versions: Vec<u64>,
+    wireframe: bool,
 }
 
 impl HALState {
-    /// Create a new Hardware Abstraction Layer State for the given window
-    pub fn new(window: &impl HasRawWindowHandle) -> Result<Self, HALError> {
+    /// Create a new Hardware Abstraction Layer State for the given window. More windows can be added with `HALResources::add_window`.
+    ///
+    /// Reads the keys registered by `register_config` from the given configuration.
+    /// Meshes and shaders are loaded by the given `AssetServer` (see `AppContext::assets`, and `register_loaders`) while the device is created,
+    /// and swapped when they're reloaded (see `swap_reloaded_assets`).
+    pub fn new(
+        window_id: WindowId,
+        window: &impl HasRawWindowHandle,
+        config: &ConfigRegistry,
+        assets: &AssetServer,
+    ) -> Result<Self, HALError> {
+        let wireframe: bool = config_value(config, WIREFRAME_KEY)?;
+        let render_timeout_ns: u64 = config_value(config, RENDER_TIMEOUT_KEY)?;
+        let acquire_timeout_ns: u64 = config_value(config, ACQUIRE_TIMEOUT_KEY)?;
+        let mesh_dir: String = config_value(config, MESH_DIR_KEY)?;
+        let mesh = assets.load::<Mesh>(&format!("{}/teapot_mesh.bin", mesh_dir))?;
+        let shaders = shader_paths(config)?
+            .into_iter()
+            .map(|(stage, path)| Ok((stage, assets.load::<ShaderCode>(&path)?)))
+            .collect::<Result<Vec<_>, HALError>>()?;
         let (instance, surface, adapter) = {
             let instance = ThermiteInstance::create("Thermite GFX", 1)
                 .map_err(|_| HALError::UnsupportedBackend)?;
@@ -320,6 +631,8 @@ impl HALState {
                     message: String::from("Couldn't find a suitable graphical adapter!"),
                     inner: None,
                 })?;
+            // Whatever the renderer panics on later, the report should say which GPU and driver it ran on
+            crash::set_section("Graphics adapter", format!("{:#?}", adapter.info));
             (instance, surface, adapter)
         };
         let (logical_device, queue_group) = {
@@ -398,22 +711,29 @@ impl HALState {
                 &[(ShaderStageFlags::VERTEX, 0..push_constant_bytes)],
             )?
         };
+        let shader_code = shaders
+            .iter()
+            .map(|(stage, handle)| Ok((*stage, wait_for(assets, handle)?)))
+            .collect::<Result<Vec<_>, HALError>>()?;
         let pipeline = unsafe {
-            make_pipeline::<ThermiteBackend>(&logical_device, &render_pass, &pipeline_layout)?
+            make_pipeline::<ThermiteBackend>(
+                &logical_device,
+                &render_pass,
+                &pipeline_layout,
+                &shader_code,
+                wireframe,
+            )?
         };
         let submission_complete_fence = logical_device.create_fence(true)?;
         let rendering_complete_semaphore = logical_device.create_semaphore()?;
-        let mesh_res = resources::Resource::new(std::path::Path::new("assets/meshes/"))
-            .expect("Couldn't get mesh resource");
-        let teapot_mesh =
-            Mesh::new(&mesh_res, "teapot_mesh.bin").expect("Couldn't load teapot mesh!");
+        let teapot_mesh = wait_for(assets, &mesh)?;
         let vertex_buffer =
-            VertexBuffer::from_mesh(teapot_mesh, &logical_device, &adapter.physical_device)
-                .expect("Couldn't create vbo for teapot mesh");
+            VertexBuffer::from_mesh(&teapot_mesh, &logical_device, &adapter.physical_device)?;
+        assets.track_gpu_memory(&mesh, vertex_buffer.size() as usize);
         let hal_state = HALState {
             resources: ManuallyDrop::new(HALResources::<ThermiteBackend> {
                 instance: instance,
-                surface: surface,
+                surfaces: vec![WindowSurface { window_id, surface }],
                 adapter: adapter,
                 logical_device: logical_device,
                 queue_group: queue_group,
@@ -425,11 +745,123 @@ impl HALState {
                 format: surface_color_format,
                 submission_complete_fence: submission_complete_fence,
                 rendering_complete_semaphore: rendering_complete_semaphore,
+                render_timeout_ns,
+                acquire_timeout_ns,
                 vertex_buffer: vertex_buffer,
             }),
+            mesh_version: mesh.version(),
+            mesh,
+            shader_versions: shaders.iter().map(|(_, handle)| handle.version()).collect(),
+            shaders,
+            wireframe,
         };
         Ok(hal_state)
     }
+
+    /// Rebuilds the graphics pipeline from the current shaders, drawing polygons as lines or not (see `WIREFRAME_KEY`).
+    ///
+    /// Waits for the GPU to be idle, so it belongs between frames. The previous pipeline is kept if the new one can't be created,
+    /// e.g. while one of the shaders is still loading, or if it failed to.
+    pub fn rebuild_pipeline(&mut self, wireframe: bool) -> Result<(), HALError> {
+        profile_scope!("HALState::rebuild_pipeline");
+        let shader_code = self
+            .shaders
+            .iter()
+            .map(|(stage, handle)| match (handle.get(), handle.state()) {
+                (Some(code), _) => Ok((*stage, code)),
+                (None, LoadState::Failed(inner)) => Err(HALError::AssetError {
+                    path: handle.path().to_string(),
+                    inner,
+                }),
+                (None, _) => Err(HALError::AssetPending(handle.path().to_string())),
+            })
+            .collect::<Result<Vec<_>, HALError>>()?;
+        let resources = &mut *self.resources;
+        unsafe {
+            resources.logical_device.wait_idle()?;
+            let pipeline = make_pipeline::<ThermiteBackend>(
+                &resources.logical_device,
+                &resources.render_passes[0],
+                &resources.pipeline_layouts[0],
+                &shader_code,
+                wireframe,
+            )?;
+            let previous = std::mem::replace(&mut resources.pipelines[0], pipeline);
+            resources.logical_device.destroy_graphics_pipeline(previous);
+        }
+        self.wireframe = wireframe;
+        Ok(())
+    }
+
+    /// Whether the pipeline draws polygons as lines
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Loads the shaders again (recompiling GLSL sources), they're swapped in by `swap_reloaded_assets` once they've loaded
+    pub fn reload_shaders(&self, assets: &AssetServer) {
+        for (_, handle) in &self.shaders {
+            assets.reload(handle);
+        }
+    }
+
+    /// Whether the mesh or shaders were reloaded since they were last swapped in, see `swap_reloaded_assets`
+    pub fn has_unswapped_assets(&self) -> bool {
+        self.mesh.version() != self.mesh_version
+            || self
+                .shaders
+                .iter()
+                .map(|(_, handle)| handle.version())
+                .ne(self.shader_versions.iter().copied())
+    }
+
+    /// Swaps in the mesh and shaders which have been reloaded since the last call (see `AssetServer::set_hot_reload`):
+    /// the vertex buffer is recreated, and the pipeline rebuilt with the new shaders.
+    ///
+    /// Waits for the GPU to be idle when there's something to swap, so it belongs between frames.
+    /// Returns whether anything was swapped. If a swap fails, the previous resources are kept and it's tried again on the next call.
+    pub fn swap_reloaded_assets(&mut self, assets: &AssetServer) -> Result<bool, HALError> {
+        profile_scope!("HALState::swap_reloaded_assets");
+        let mut swapped = false;
+        let mesh_version = self.mesh.version();
+        if mesh_version != self.mesh_version {
+            // A mesh which isn't available yet is swapped in by a later call
+            if let Some(mesh) = self.mesh.get() {
+                let resources = &mut *self.resources;
+                unsafe {
+                    resources.logical_device.wait_idle()?;
+                }
+                let vertex_buffer = VertexBuffer::from_mesh(
+                    &mesh,
+                    &resources.logical_device,
+                    &resources.adapter.physical_device,
+                )?;
+                assets.track_gpu_memory(&self.mesh, vertex_buffer.size() as usize);
+                let previous = std::mem::replace(&mut resources.vertex_buffer, vertex_buffer);
+                unsafe {
+                    resources.logical_device.free_memory(previous.data.memory);
+                    resources
+                        .logical_device
+                        .destroy_buffer(previous.data.buffer);
+                }
+                self.mesh_version = mesh_version;
+                swapped = true;
+            }
+        }
+        let shader_versions: Vec<u64> = self
+            .shaders
+            .iter()
+            .map(|(_, handle)| handle.version())
+            .collect();
+        // Shaders which are still loading are swapped in by a later call, like the mesh
+        let shaders_done = self.shaders.iter().all(|(_, handle)| handle.is_done());
+        if shader_versions != self.shader_versions && shaders_done {
+            self.rebuild_pipeline(self.wireframe)?;
+            self.shader_versions = shader_versions;
+            swapped = true;
+        }
+        Ok(swapped)
+    }
 }
 
 // TODO: Ensure everything that needs to be dropped here is properly, and in the correct order
@@ -438,7 +870,7 @@ impl Drop for HALState {
         unsafe {
             let HALResources {
                 instance,
-                mut surface,
+                surfaces,
                 adapter: _,
                 logical_device,
                 queue_group: _,
@@ -450,6 +882,8 @@ impl Drop for HALState {
                 pipelines,
                 submission_complete_fence,
                 rendering_complete_semaphore,
+                render_timeout_ns: _,
+                acquire_timeout_ns: _,
                 vertex_buffer,
             } = ManuallyDrop::take(&mut self.resources);
             let _ = logical_device.wait_idle();
@@ -467,8 +901,10 @@ impl Drop for HALState {
                 logical_device.destroy_render_pass(render_pass);
             }
             logical_device.destroy_command_pool(command_pool);
-            surface.unconfigure_swapchain(&logical_device);
-            instance.destroy_surface(surface);
+            for WindowSurface { mut surface, .. } in surfaces {
+                surface.unconfigure_swapchain(&logical_device);
+                instance.destroy_surface(surface);
+            }
         }
     }
 }
@@ -478,25 +914,28 @@ unsafe fn make_pipeline<ThermiteBackend>(
     logical_device: &ThermiteDevice,
     render_pass: &ThermiteRenderPass,
     pipeline_layout: &ThermitePipelineLayout,
+    shaders: &[(ShaderStageFlags, Arc<ShaderCode>)],
+    wireframe: bool,
 ) -> Result<ThermiteGraphicsPipeline, HALError> {
     use gfx_hal::pass::Subpass;
     use gfx_hal::pso::{
         BlendState, ColorBlendDesc, ColorMask, Face, GraphicsPipelineDesc, PolygonMode, Primitive,
         Rasterizer,
     };
-    let shader_res = resources::Resource::new(std::path::Path::new("assets/shaders/spirv"))?;
-    let mut shader_set = ShaderSet::new(
-        "test",
-        &shader_res,
-        ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
-        "main",
-        logical_device,
-    )?;
+    let stages: Vec<(ShaderStageFlags, &ShaderCode)> = shaders
+        .iter()
+        .map(|(stage, code)| (*stage, code.as_ref()))
+        .collect();
+    let mut shader_set = ShaderSet::from_code(SHADER_SET, &stages, "main", logical_device)?;
     let mut pipeline_desc = GraphicsPipelineDesc::new(
         shader_set.inner()?,
         Primitive::TriangleList,
         Rasterizer {
-            polygon_mode: PolygonMode::Line, // Uncomment this for wireframe polygons
+            polygon_mode: if wireframe {
+                PolygonMode::Line
+            } else {
+                PolygonMode::Fill
+            },
             cull_face: Face::NONE,
             ..Rasterizer::FILL
         },
@@ -534,7 +973,8 @@ unsafe fn make_pipeline<ThermiteBackend>(
             offset: 12,
         },
     });
-    let pipeline = logical_device.create_graphics_pipeline(&pipeline_desc, None)?;
+    // The shader modules must be destroyed whether or not the pipeline could be created
+    let pipeline = logical_device.create_graphics_pipeline(&pipeline_desc, None);
     shader_set.destroy(logical_device);
-    Ok(pipeline)
+    Ok(pipeline?)
 }
@@ -5,8 +5,11 @@ pub use gfx_hal;
 pub use winit;
 
 // thermite_gfx native modules
+pub mod app;
 pub mod hal;
 pub mod primitives;
+pub mod renderer;
 pub mod resources;
 pub mod shaders;
 pub mod window;
+pub mod window_config;
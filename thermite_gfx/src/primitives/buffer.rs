@@ -86,6 +86,8 @@ impl std::error::Error for BufferError {
 pub struct Buffer<B: Backend> {
     pub(crate) memory: B::Memory,
     pub(crate) buffer: B::Buffer,
+    /// The size of the memory allocated for the buffer, in bytes
+    pub(crate) size: u64,
 }
 
 impl<B: Backend> Buffer<B> {
@@ -118,8 +120,14 @@ impl<B: Backend> Buffer<B> {
         Ok(Buffer {
             memory: buffer_memory,
             buffer: buffer,
+            size: req.size,
         })
     }
+
+    /// The size of the memory allocated for the buffer (which may be more than was asked for), in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
 }
 
 pub struct VertexBuffer<B: Backend> {
@@ -129,7 +137,7 @@ pub struct VertexBuffer<B: Backend> {
 
 impl<B: Backend> VertexBuffer<B> {
     pub fn new(
-        vertices: Vec<Vertex>,
+        vertices: &[Vertex],
         logical_device: &B::Device,
         physical_device: &B::PhysicalDevice,
     ) -> Result<Self, BufferError> {
@@ -169,11 +177,16 @@ impl<B: Backend> VertexBuffer<B> {
     }
 
     pub fn from_mesh(
-        mesh: Mesh,
+        mesh: &Mesh,
         logical_device: &B::Device,
         physical_device: &B::PhysicalDevice,
     ) -> Result<Self, BufferError> {
-        VertexBuffer::new(mesh.vertex_data, logical_device, physical_device)
+        VertexBuffer::new(&mesh.vertex_data, logical_device, physical_device)
+    }
+
+    /// The size of the GPU memory allocated for the vertices, in bytes
+    pub fn size(&self) -> u64 {
+        self.data.size()
     }
 }
 
@@ -222,4 +235,9 @@ impl<B: Backend> IndexBuffer<B> {
             data: memory_buffer,
         })
     }
+
+    /// The size of the GPU memory allocated for the indices, in bytes
+    pub fn size(&self) -> u64 {
+        self.data.size()
+    }
 }
@@ -0,0 +1,360 @@
+/*
+    ABSTRACT: The renderer, as a `WindowSystem` the `App` registers. It owns the `HALState` (see hal::hal_state), which it
+    starts on the primary window, and keeps a surface for every other window while it's open. It draws a frame to each
+    window whenever the application renders, and swaps in the meshes and shaders reloaded since the last frame
+    (on `AssetEvent::Reloaded`) during its update, between frames.
+
+    The `HALState` is shared through a `Renderer` handle, which is how console commands reach it
+    (see `hal_state::register_console_commands`).
+*/
+use crate::app::WindowSystem;
+use crate::hal::hal_state::{HALError, HALState, WIREFRAME_KEY};
+use crate::shaders::shader::{make_transform, PushConstants};
+use crate::window::WindowManager;
+use gfx_hal::window::Extent2D;
+use log::{error, warn};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+use thermite_core::{
+    assets::server::{AssetEvent, AssetServer},
+    messaging::{
+        bus::BusRequest,
+        event::{ThermiteEvent, ThermiteEventType},
+        subscribe::Subscriber,
+    },
+    platform::app::{AppContext, System},
+    profile_scope,
+    tools::config::ConfigEvent,
+};
+use winit::{dpi::PhysicalSize, event_loop::EventLoopWindowTarget, window::WindowId};
+
+/// A handle on the renderer's `HALState`, shared with its `RendererSystem` (see `AppBuilder::renderer`)
+#[derive(Clone, Default)]
+pub struct Renderer {
+    state: Rc<RefCell<Option<HALState>>>,
+}
+
+impl Renderer {
+    /// Whether the `HALState` was created, and hasn't been dropped on shutdown
+    pub fn is_running(&self) -> bool {
+        self.state.borrow().is_some()
+    }
+
+    /// Loads the shaders again, they're swapped in between frames once they've loaded (see `HALState::reload_shaders`)
+    pub fn reload_shaders(&self, assets: &AssetServer) -> Result<(), HALError> {
+        self.with_state(|state| {
+            state.reload_shaders(assets);
+            Ok(())
+        })
+    }
+
+    /// Rebuilds the pipeline, unless it already draws polygons as lines or not as asked (see `HALState::rebuild_pipeline`)
+    pub fn set_wireframe(&self, wireframe: bool) -> Result<(), HALError> {
+        self.with_state(|state| {
+            if state.wireframe() == wireframe {
+                return Ok(());
+            }
+            state.rebuild_pipeline(wireframe)
+        })
+    }
+
+    fn with_state<T, F>(&self, f: F) -> Result<T, HALError>
+    where
+        F: FnOnce(&mut HALState) -> Result<T, HALError>,
+    {
+        match self.state.borrow_mut().as_mut() {
+            Some(state) => f(state),
+            None => Err(HALError::NotRunning),
+        }
+    }
+}
+
+/// Notes what the `RendererSystem` has to catch up with during its update
+#[derive(Default)]
+struct RendererEvents {
+    assets_reloaded: Cell<bool>,
+    wireframe_changed: Cell<bool>,
+}
+
+impl Subscriber<ThermiteEventType, ThermiteEvent> for RendererEvents {
+    fn on_event(&self, event: &ThermiteEvent) -> BusRequest {
+        match event {
+            ThermiteEvent::Asset(AssetEvent::Reloaded { .. }) => self.assets_reloaded.set(true),
+            ThermiteEvent::Config(ConfigEvent::Changed(key)) if key == WIREFRAME_KEY => {
+                self.wireframe_changed.set(true)
+            }
+            _ => (),
+        }
+        BusRequest::NoActionNeeded
+    }
+}
+
+/// A window the renderer draws to
+struct RenderTarget {
+    window_id: WindowId,
+    /// The size of the window the swapchain was made for
+    size: PhysicalSize<u32>,
+    extent: Extent2D,
+    /// Whether the swapchain must be made again before drawing to the window (e.g. after a resize, or while minimized)
+    stale: bool,
+}
+
+impl RenderTarget {
+    fn new(window_id: WindowId) -> Self {
+        Self {
+            window_id,
+            size: PhysicalSize::new(0, 0),
+            extent: Extent2D {
+                width: 0,
+                height: 0,
+            },
+            stale: true,
+        }
+    }
+}
+
+/// Draws the scene to the application's windows, see the module's documentation
+#[derive(Default)]
+pub struct RendererSystem {
+    renderer: Renderer,
+    /// Starting failed, and isn't tried again
+    failed: bool,
+    /// Reloaded assets are waiting to be swapped in, until a swap leaves none (e.g. once a mesh still loading has loaded)
+    swap_pending: bool,
+    /// The last swap failed, which was already reported
+    swap_failed: bool,
+    targets: Vec<RenderTarget>,
+    events: Rc<RendererEvents>,
+}
+
+impl RendererSystem {
+    /// A renderer sharing its `HALState` with the given handle
+    pub fn new(renderer: Renderer) -> Self {
+        Self {
+            renderer,
+            ..Self::default()
+        }
+    }
+
+    /// The handle on the `HALState`
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    /// Starts the `HALState` on the first window opened, and adds a surface for every other one
+    fn add_window(
+        &mut self,
+        ctx: &mut AppContext,
+        windows: &WindowManager<ThermiteEvent>,
+        window_id: WindowId,
+    ) {
+        let window = match windows.get(window_id) {
+            Some(window) => window,
+            None => return,
+        };
+        let mut state = self.renderer.state.borrow_mut();
+        let added = match state.as_mut() {
+            Some(state) => state.resources.add_window(window_id, window),
+            None if self.failed => return,
+            None => match HALState::new(window_id, window, &ctx.config().borrow(), ctx.assets()) {
+                Ok(started) => {
+                    *state = Some(started);
+                    Ok(())
+                }
+                Err(err) => {
+                    error!("Couldn't start the renderer: {}", err);
+                    self.failed = true;
+                    return;
+                }
+            },
+        };
+        match added {
+            Ok(()) => self.targets.push(RenderTarget::new(window_id)),
+            Err(err) => warn!("Couldn't render to {:?}: {}", window_id, err),
+        }
+    }
+
+    /// Destroys the surface of a window which is closing
+    fn remove_window(&mut self, window_id: WindowId) {
+        let idx = match self
+            .targets
+            .iter()
+            .position(|target| target.window_id == window_id)
+        {
+            Some(idx) => idx,
+            None => return,
+        };
+        self.targets.remove(idx);
+        if let Some(state) = self.renderer.state.borrow_mut().as_mut() {
+            if let Err(err) = state.resources.remove_window(window_id) {
+                warn!("Couldn't destroy the surface of {:?}: {}", window_id, err);
+            }
+        }
+    }
+
+    /// Makes the swapchains of the windows which were resized (or restored) again
+    fn update_swapchains(&mut self, windows: &WindowManager<ThermiteEvent>) {
+        let mut state = self.renderer.state.borrow_mut();
+        let state = match state.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        for target in &mut self.targets {
+            let size = match windows.get(target.window_id) {
+                Some(window) => window.inner_size(),
+                None => continue,
+            };
+            if size == target.size && !target.stale {
+                continue;
+            }
+            target.size = size;
+            // Minimized windows have nothing to draw to until they're restored
+            target.stale = size.width == 0 || size.height == 0;
+            if target.stale {
+                continue;
+            }
+            let extent = Extent2D {
+                width: size.width,
+                height: size.height,
+            };
+            match state.resources.recreate_swapchain(target.window_id, extent) {
+                Ok(extent) => target.extent = extent,
+                Err(err) => {
+                    warn!(
+                        "Couldn't make the swapchain of {:?}: {}",
+                        target.window_id, err
+                    );
+                    target.stale = true;
+                }
+            }
+        }
+    }
+}
+
+/// Draws a frame to the target's window, and returns whether its swapchain must be made again
+unsafe fn draw_frame(
+    state: &mut HALState,
+    target: &RenderTarget,
+    teapots: &[PushConstants],
+) -> Result<bool, HALError> {
+    let resources = &mut *state.resources;
+    let image = match resources.acquire_image(target.window_id) {
+        Ok(image) => image,
+        // The swapchain no longer matches the window
+        Err(HALError::AcquireError(_)) => return Ok(true),
+        Err(err) => return Err(err),
+    };
+    resources.reset_command_pool()?;
+    let framebuffer = resources.create_framebuffer(&image, target.extent)?;
+    let viewport = resources.viewport(target.extent);
+    resources.record_cmds_for_submission(&framebuffer, &viewport, teapots);
+    let present_failed = resources.submit_cmds(target.window_id, image)?;
+    resources.destroy_framebuffer(framebuffer);
+    Ok(present_failed)
+}
+
+impl System for RendererSystem {
+    fn on_init(&mut self, ctx: &mut AppContext) {
+        ctx.event_bus()
+            .try_borrow_mut()
+            .expect("Couldn't borrow the event bus as mutable")
+            .subscribe(&self.events, ThermiteEventType::Asset);
+        ctx.event_bus()
+            .try_borrow_mut()
+            .expect("Couldn't borrow the event bus as mutable")
+            .subscribe(&self.events, ThermiteEventType::Config);
+    }
+
+    fn on_update(&mut self, ctx: &mut AppContext, _delta_sec: f32) {
+        // Already done when the wireframe console command changed it, but not when the configuration file did
+        if self.events.wireframe_changed.replace(false) {
+            let wireframe = ctx
+                .config()
+                .borrow()
+                .get_as::<bool>(WIREFRAME_KEY)
+                .unwrap_or(false);
+            match self.renderer.set_wireframe(wireframe) {
+                Ok(()) | Err(HALError::NotRunning) => (),
+                Err(err) => warn!("Couldn't rebuild the pipeline: {}", err),
+            }
+        }
+        if self.events.assets_reloaded.replace(false) {
+            self.swap_pending = true;
+            self.swap_failed = false;
+        }
+        if !self.swap_pending {
+            return;
+        }
+        let mut state = self.renderer.state.borrow_mut();
+        let state = match state.as_mut() {
+            Some(state) => state,
+            None => {
+                self.swap_pending = false;
+                return;
+            }
+        };
+        // Tried again every update until it succeeds, but only reported once per reload
+        match state.swap_reloaded_assets(ctx.assets()) {
+            Ok(_) => self.swap_failed = false,
+            Err(err) if !self.swap_failed => {
+                warn!("Couldn't swap in the reloaded assets: {}", err);
+                self.swap_failed = true;
+            }
+            Err(_) => (),
+        }
+        self.swap_pending = state.has_unswapped_assets();
+    }
+
+    fn on_render(&mut self, ctx: &mut AppContext) {
+        profile_scope!("RendererSystem::on_render");
+        let mut state = self.renderer.state.borrow_mut();
+        let state = match state.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        let angle = ctx.time().game_time().as_secs_f32();
+        let teapots = [PushConstants {
+            transform: make_transform([0.0, 0.0, 0.5], angle, 0.5),
+        }];
+        for target in self.targets.iter_mut().filter(|target| !target.stale) {
+            match unsafe { draw_frame(state, target, &teapots) } {
+                Ok(stale) => target.stale = stale,
+                Err(err) => {
+                    warn!("Couldn't draw to {:?}: {}", target.window_id, err);
+                    target.stale = true;
+                }
+            }
+        }
+    }
+
+    fn on_shutdown(&mut self, _ctx: &mut AppContext) {
+        self.targets.clear();
+        *self.renderer.state.borrow_mut() = None;
+    }
+}
+
+impl WindowSystem for RendererSystem {
+    fn on_windows(
+        &mut self,
+        _ctx: &mut AppContext,
+        windows: &mut WindowManager<ThermiteEvent>,
+        _target: &EventLoopWindowTarget<ThermiteEvent>,
+    ) {
+        self.update_swapchains(windows);
+    }
+
+    fn on_window_opened(
+        &mut self,
+        ctx: &mut AppContext,
+        windows: &WindowManager<ThermiteEvent>,
+        window_id: WindowId,
+    ) {
+        self.add_window(ctx, windows, window_id);
+    }
+
+    fn on_window_closed(&mut self, _ctx: &mut AppContext, window_id: WindowId) {
+        self.remove_window(window_id);
+    }
+}
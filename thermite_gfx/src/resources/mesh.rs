@@ -1,7 +1,20 @@
 use crate::primitives::vertex::Vertex;
 use bincode;
+use std::io::Cursor;
+use thermite_core::assets::{
+    cache::AssetMemory,
+    integrity::{AssetHeader, IntegrityError},
+    loader::{AssetLoader, LoadContext},
+};
+use thermite_core::jobs::{handle::JobHandle, scheduler::JobScheduler};
+use thermite_core::profile_scope;
 use thermite_core::tools::resources::{Resource, ResourceError};
 
+/// The kind of asset in the `AssetHeader` of mesh files
+pub const MESH_KIND: [u8; 4] = *b"MESH";
+/// The newest mesh format version. Version 0 is the legacy format without a header; both hold bincode-serialized vertices.
+pub const MESH_FORMAT_VERSION: u32 = 1;
+
 /// A 3D mesh
 pub struct Mesh {
     pub(crate) vertex_count: usize,
@@ -11,13 +24,73 @@ pub struct Mesh {
 impl Mesh {
     /// Loads a new 3D `Mesh` located at the given `Resource`, named `filename`
     pub fn new(res: &Resource, filename: &str) -> Result<Self, ResourceError> {
+        profile_scope!("Mesh::new");
         let binary_data = res.load_to_bytes(filename, false)?;
-        let vertex_data: Vec<Vertex> = bincode::deserialize(&binary_data)
-            .map_err(|_| ResourceError::DeserializationFailure(filename.to_string()))?;
+        Self::from_binary(&binary_data, filename)
+    }
+
+    /// Deserializes a `Mesh` from its binary file's contents, `filename` is only used for errors
+    fn from_binary(binary_data: &[u8], filename: &str) -> Result<Self, ResourceError> {
+        let (_version, offset) =
+            AssetHeader::check(binary_data, filename, MESH_KIND, MESH_FORMAT_VERSION)?;
+        let mut cursor = Cursor::new(&binary_data[offset..]);
+        let malformed = |cursor: &Cursor<&[u8]>, message: String| IntegrityError::Malformed {
+            path: filename.to_string(),
+            offset: offset as u64 + cursor.position(),
+            message,
+        };
+        let vertex_data: Vec<Vertex> = bincode::deserialize_from(&mut cursor)
+            .map_err(|error| malformed(&cursor, error.to_string()))?;
+        let trailing = cursor.get_ref().len() as u64 - cursor.position();
+        if trailing > 0 {
+            return Err(
+                malformed(&cursor, format!("{} unexpected trailing bytes", trailing)).into(),
+            );
+        }
         let vertex_count = vertex_data.len();
         Ok(Mesh {
             vertex_count: vertex_count,
             vertex_data: vertex_data,
         })
     }
+
+    /// Loads a new 3D `Mesh` like `new`, reading and deserializing it on one of the scheduler's worker threads
+    pub fn load_async(
+        jobs: &JobScheduler,
+        res: &Resource,
+        filename: &str,
+    ) -> JobHandle<Result<Self, ResourceError>> {
+        let res = res.clone();
+        let filename = filename.to_string();
+        jobs.spawn("Mesh::load_async", move || Mesh::new(&res, &filename))
+    }
+}
+
+/// Loads `Mesh`es from their binary files (bincode-serialized vertices, after an `AssetHeader` from version 1), for the `AssetServer`
+pub struct MeshLoader;
+
+impl AssetLoader for MeshLoader {
+    type Asset = Mesh;
+    type Settings = ();
+
+    fn extensions(&self) -> &[&str] {
+        &["bin", "mesh"]
+    }
+
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        _settings: &(),
+        ctx: &mut LoadContext,
+    ) -> Result<Mesh, ResourceError> {
+        profile_scope!("MeshLoader::load");
+        Mesh::from_binary(&bytes, &ctx.path().to_string())
+    }
+
+    fn memory(&self, mesh: &Mesh) -> AssetMemory {
+        AssetMemory::cpu(
+            std::mem::size_of::<Mesh>()
+                + mesh.vertex_data.capacity() * std::mem::size_of::<Vertex>(),
+        )
+    }
 }
@@ -2,6 +2,13 @@ use gfx_hal::{
     self,
     pso::{ShaderStageFlags, Specialization},
 };
+use log::warn;
+use thermite_core::assets::{
+    cache::AssetMemory,
+    loader::{AssetLoader, LoadContext},
+};
+use thermite_core::profile_scope;
+use thermite_core::tools::resources::ResourceError;
 
 #[repr(C)] // Layout this struct in memory the same as C (and shader code) would
 #[derive(Debug, Clone, Copy)]
@@ -32,6 +39,10 @@ pub enum ShaderError {
         filename: String,
         inner: std::io::Error,
     },
+    GlslCompileError {
+        filename: String,
+        message: String,
+    },
     VertexShaderRequired,
     ShaderModuleNotCompiled,
 }
@@ -60,6 +71,9 @@ impl std::fmt::Display for ShaderError {
             ShaderError::SpirvReadError { filename, inner } => {
                 write!(fmt, "{:?} ({}): {}", self, filename, inner)
             }
+            ShaderError::GlslCompileError { filename, message } => {
+                write!(fmt, "GlslCompileError ({}): {}", filename, message)
+            }
             ShaderError::VertexShaderRequired => write!(fmt, "{:?}", self),
             ShaderError::ShaderModuleNotCompiled => write!(fmt, "{:?}: Attempted an operation that requires a compiled shader module before it existed.", self)
         }
@@ -68,6 +82,120 @@ impl std::fmt::Display for ShaderError {
 
 impl std::error::Error for ShaderError {}
 
+/// Compiled SPIR-V code of a shader, loadable in the background by the `AssetServer` (see `ShaderLoader` and `Shader::from_code`)
+pub struct ShaderCode {
+    spirv: Vec<u32>,
+}
+
+impl ShaderCode {
+    pub fn spirv(&self) -> &[u32] {
+        &self.spirv
+    }
+
+    /// Compiles GLSL source code of the given kind to SPIR-V, with the given preprocessor macros defined.
+    /// `filename` is only used for errors.
+    pub fn compile_glsl(
+        source: &str,
+        kind: shaderc::ShaderKind,
+        filename: &str,
+        entry: &str,
+        definitions: &[(String, Option<String>)],
+    ) -> Result<Self, ShaderError> {
+        let compile_error = |message: String| ShaderError::GlslCompileError {
+            filename: filename.to_string(),
+            message,
+        };
+        let mut compiler = shaderc::Compiler::new()
+            .ok_or_else(|| compile_error(String::from("Couldn't create the GLSL compiler")))?;
+        let mut options = shaderc::CompileOptions::new().ok_or_else(|| {
+            compile_error(String::from("Couldn't create the GLSL compile options"))
+        })?;
+        for (name, value) in definitions {
+            options.add_macro_definition(name, value.as_deref());
+        }
+        let compiled = compiler
+            .compile_into_spirv(source, kind, filename, entry, Some(&options))
+            .map_err(|e| compile_error(e.to_string()))?;
+        if compiled.get_num_warnings() > 0 {
+            warn!("{}: {}", filename, compiled.get_warning_messages());
+        }
+        Ok(ShaderCode {
+            spirv: compiled.as_binary().to_vec(),
+        })
+    }
+}
+
+/// Settings of a `ShaderLoader` load, see `AssetServer::load_with_settings`
+#[derive(Debug, Clone, Default)]
+pub struct ShaderLoaderSettings {
+    /// Preprocessor macros defined when compiling GLSL sources, with their value if any
+    pub definitions: Vec<(String, Option<String>)>,
+}
+
+/// Loads `ShaderCode` for the `AssetServer`, from compiled SPIR-V (`.spv`) files or GLSL sources
+/// (`.vert`, `.frag`, `.geom`, `.tesc`, `.tese`, `.comp`) which it compiles
+pub struct ShaderLoader;
+
+impl AssetLoader for ShaderLoader {
+    type Asset = ShaderCode;
+    type Settings = ShaderLoaderSettings;
+
+    fn extensions(&self) -> &[&str] {
+        &["spv", "vert", "frag", "geom", "tesc", "tese", "comp"]
+    }
+
+    fn load(
+        &self,
+        bytes: Vec<u8>,
+        settings: &ShaderLoaderSettings,
+        ctx: &mut LoadContext,
+    ) -> Result<ShaderCode, ResourceError> {
+        profile_scope!("ShaderLoader::load");
+        let path = ctx.path();
+        let glsl_kind = match path.extension() {
+            Some("spv") => None,
+            Some("vert") => Some(shaderc::ShaderKind::Vertex),
+            Some("frag") => Some(shaderc::ShaderKind::Fragment),
+            Some("geom") => Some(shaderc::ShaderKind::Geometry),
+            Some("tesc") => Some(shaderc::ShaderKind::TessControl),
+            Some("tese") => Some(shaderc::ShaderKind::TessEvaluation),
+            Some("comp") => Some(shaderc::ShaderKind::Compute),
+            _ => {
+                return Err(ResourceError::DeserializationFailure(
+                    ShaderError::CannotDetermineShaderTypeForResource(path.to_string()).to_string(),
+                ))
+            }
+        };
+        match glsl_kind {
+            Some(kind) => {
+                let source = String::from_utf8(bytes)
+                    .map_err(|_| ResourceError::DeserializationFailure(path.to_string()))?;
+                ShaderCode::compile_glsl(
+                    &source,
+                    kind,
+                    &path.to_string(),
+                    "main",
+                    &settings.definitions,
+                )
+                .map_err(|e| ResourceError::DeserializationFailure(e.to_string()))
+            }
+            None => {
+                let spirv =
+                    gfx_hal::pso::read_spirv(std::io::Cursor::new(&bytes)).map_err(|e| {
+                        ResourceError::DeserializationFailure(format!("{}: {}", path, e))
+                    })?;
+                Ok(ShaderCode { spirv })
+            }
+        }
+    }
+
+    fn memory(&self, code: &ShaderCode) -> AssetMemory {
+        AssetMemory::cpu(
+            std::mem::size_of::<ShaderCode>() + code.spirv.capacity() * std::mem::size_of::<u32>(),
+        )
+    }
+}
+
 /// Structure containing all of the information needed to create and use a Shader in a rendering pipeline
 pub struct Shader<B: gfx_hal::Backend> {
     filename: String,
@@ -104,6 +232,25 @@ impl<B: gfx_hal::Backend> Shader<B> {
         })
     }
 
+    /// Create a new `Shader` like `new`, from code which has already been loaded (e.g. by the `AssetServer`).
+    /// `filename` only names the shader.
+    pub fn from_code(
+        filename: &str,
+        code: &ShaderCode,
+        stage: gfx_hal::pso::ShaderStageFlags,
+        entry: &str,
+        specialization: gfx_hal::pso::Specialization<'static>,
+    ) -> Shader<B> {
+        Shader {
+            filename: filename.to_string(),
+            stage,
+            entry: entry.to_string(),
+            spirv: code.spirv.clone(),
+            specialization,
+            module: None,
+        }
+    }
+
     /// Interally compile and store this `Shader`'s module
     pub(crate) unsafe fn compile_module(
         &mut self,
@@ -228,6 +375,39 @@ impl<'a, B: gfx_hal::Backend> ShaderSet<B> {
         }
     }
 
+    /// Creates a `ShaderSet` from code which has already been loaded (e.g. by the `AssetServer`), one per stage, named `set_name`
+    pub unsafe fn from_code(
+        set_name: &str,
+        stages: &[(ShaderStageFlags, &ShaderCode)],
+        entry: &str,
+        logical_device: &B::Device,
+    ) -> Result<Self, ShaderError> {
+        if !stages
+            .iter()
+            .any(|(stage, _)| *stage == ShaderStageFlags::VERTEX)
+        {
+            return Err(ShaderError::VertexShaderRequired);
+        }
+        let mut set = ShaderSet {
+            shaders: HashMap::new(),
+        };
+        for (stage, code) in stages {
+            let mut shader = Shader::from_code(
+                &format!("{} ({:?})", set_name, stage),
+                code,
+                *stage,
+                entry,
+                Specialization::default(),
+            );
+            if let Err(error) = shader.compile_module(logical_device) {
+                set.destroy(logical_device);
+                return Err(error);
+            }
+            set.shaders.insert(*stage, shader);
+        }
+        Ok(set)
+    }
+
     /// Returns the raw `GraphicsShaderSet` structure to be used in the rendering pipeline
     pub fn inner(&'a self) -> Result<gfx_hal::pso::GraphicsShaderSet<'a, B>, ShaderError> {
         Ok(gfx_hal::pso::GraphicsShaderSet {
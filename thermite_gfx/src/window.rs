@@ -1,9 +1,11 @@
+use crate::window_config::{WindowConfig, WindowConfigError};
+use std::collections::HashMap;
 use winit::{
     self,
     dpi::LogicalSize,
     error::OsError,
-    event_loop::EventLoop,
-    window::{Window as WinitWindow, WindowAttributes, WindowBuilder},
+    event_loop::{EventLoop, EventLoopWindowTarget},
+    window::{Window as WinitWindow, WindowAttributes, WindowBuilder, WindowId},
 };
 
 #[derive(Debug)]
@@ -70,3 +72,185 @@ impl<L: 'static> Default for Window<L> {
         .expect("Could not create Thermite Engine window!")
     }
 }
+
+/// A window opened or closed, see `WindowManager::take_changes`
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum WindowChange {
+    Opened(WindowId),
+    Closed(WindowId),
+}
+
+/// Owns several windows sharing a single `EventLoop`.
+///
+/// The first window created is the primary window. Windows are identified by their `WindowId`,
+/// which is also what window events on the bus are tagged with.
+#[derive(Debug)]
+pub struct WindowManager<L: 'static> {
+    windows: HashMap<WindowId, WinitWindow>,
+    primary: Option<WindowId>,
+    focused: Option<WindowId>,
+    changes: Vec<WindowChange>,
+    /// Closed windows, kept until `destroy_closed`
+    closed: Vec<WinitWindow>,
+    event_loop: Option<EventLoop<L>>,
+}
+
+impl<L: 'static> Default for WindowManager<L> {
+    fn default() -> Self {
+        Self {
+            windows: HashMap::new(),
+            primary: None,
+            focused: None,
+            changes: vec![],
+            closed: vec![],
+            event_loop: Option::from(EventLoop::<L>::with_user_event()),
+        }
+    }
+}
+
+impl<L: 'static> WindowManager<L> {
+    /// Constructs a `WindowManager` with its own `EventLoop`, and no windows
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a window on this manager's `EventLoop`, before it runs, and returns its ID.
+    ///
+    /// ### Panics
+    /// If the `EventLoop` was already taken, see `create_window_on` instead.
+    pub fn create_window(&mut self, attributes: WindowAttributes) -> Result<WindowId, OsError> {
+        let mut builder = WindowBuilder::new();
+        builder.window = attributes;
+        let handle = builder.build(self.event_loop.as_ref().expect(
+            "Cannot create windows on a running event loop, use create_window_on instead!",
+        ))?;
+        Ok(self.insert(handle))
+    }
+
+    /// Creates a window from the given `WindowConfig` on this manager's `EventLoop`, before it runs, and returns its ID.
+    ///
+    /// ### Panics
+    /// If the `EventLoop` was already taken, see `create_window_from_config_on` instead.
+    pub fn create_window_from_config(
+        &mut self,
+        config: &WindowConfig,
+    ) -> Result<WindowId, WindowConfigError> {
+        let handle = config.build(self.event_loop.as_ref().expect(
+            "Cannot create windows on a running event loop, use create_window_from_config_on instead!",
+        ))?;
+        Ok(self.insert(handle))
+    }
+
+    /// Creates a window from the given `WindowConfig` from within the running `EventLoop`, and returns its ID
+    pub fn create_window_from_config_on(
+        &mut self,
+        target: &EventLoopWindowTarget<L>,
+        config: &WindowConfig,
+    ) -> Result<WindowId, WindowConfigError> {
+        let handle = config.build(target)?;
+        Ok(self.insert(handle))
+    }
+
+    /// Creates a window from within the running `EventLoop`, using the target it gives to its event handler, and returns its ID
+    pub fn create_window_on(
+        &mut self,
+        target: &EventLoopWindowTarget<L>,
+        attributes: WindowAttributes,
+    ) -> Result<WindowId, OsError> {
+        let mut builder = WindowBuilder::new();
+        builder.window = attributes;
+        let handle = builder.build(target)?;
+        Ok(self.insert(handle))
+    }
+
+    /// Closes the window with the given ID, returning whether it existed. Closing the primary window leaves no primary window.
+    ///
+    /// The window is only destroyed by `destroy_closed`, so that whatever renders to it can let go of it first.
+    pub fn close(&mut self, id: WindowId) -> bool {
+        if self.primary == Some(id) {
+            self.primary = None;
+        }
+        if self.focused == Some(id) {
+            self.focused = None;
+        }
+        match self.windows.remove(&id) {
+            Some(handle) => {
+                self.closed.push(handle);
+                self.changes.push(WindowChange::Closed(id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The windows opened and closed since the last call, in order
+    pub fn take_changes(&mut self) -> Vec<WindowChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Destroys the windows closed so far, once nothing renders to them anymore (see `take_changes`)
+    pub fn destroy_closed(&mut self) {
+        self.closed.clear();
+    }
+
+    /// Returns a reference to the winit handle of the window with the given ID
+    pub fn get(&self, id: WindowId) -> Option<&WinitWindow> {
+        self.windows.get(&id)
+    }
+
+    /// The ID of the first window created, if it's still open
+    pub fn primary_id(&self) -> Option<WindowId> {
+        self.primary
+    }
+
+    /// Returns a reference to the winit handle of the primary window, if it's still open
+    pub fn primary(&self) -> Option<&WinitWindow> {
+        self.primary.and_then(|id| self.get(id))
+    }
+
+    /// The ID of the window which has input focus, if any of them has it
+    pub fn focused_id(&self) -> Option<WindowId> {
+        self.focused
+    }
+
+    /// Records a focus change reported by the event loop for the given window
+    pub fn set_focused(&mut self, id: WindowId, focused: bool) {
+        if focused {
+            self.focused = Some(id);
+        } else if self.focused == Some(id) {
+            self.focused = None;
+        }
+    }
+
+    /// Iterates over every open window, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&WindowId, &WinitWindow)> {
+        self.windows.iter()
+    }
+
+    /// The number of open windows
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Whether every window has been closed
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Moves the `EventLoop` shared by the windows out of this manager for usage.
+    ///
+    /// **NOTE:** Can only be done once!
+    pub fn event_loop(&mut self) -> EventLoop<L> {
+        self.event_loop
+            .take()
+            .expect("Cannot take more than one event loop from the window manager!")
+    }
+
+    fn insert(&mut self, handle: WinitWindow) -> WindowId {
+        let id = handle.id();
+        self.primary.get_or_insert(id);
+        self.windows.insert(id, handle);
+        self.changes.push(WindowChange::Opened(id));
+        id
+    }
+}
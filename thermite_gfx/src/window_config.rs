@@ -0,0 +1,374 @@
+/*
+    ABSTRACT: A serializable description of a window (mode, flags, size limits, icon, cursor), which can be saved to disk
+    along with the window's last position and size, so that they're restored on the next launch.
+*/
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+use thermite_core::tools::resources::{Resource, ResourceError};
+use winit::{
+    dpi::{LogicalSize, PhysicalPosition},
+    error::{ExternalError, OsError},
+    event_loop::EventLoopWindowTarget,
+    monitor::{MonitorHandle, VideoMode},
+    window::{BadIcon, CursorIcon, Fullscreen, Icon, Window as WinitWindow, WindowBuilder},
+};
+
+/// Errors relating to `WindowConfig`
+#[derive(Debug)]
+pub enum WindowConfigError {
+    Io(io::Error),
+    Serialization(ron::Error),
+    Resource(ResourceError),
+    IconDecoding(png::DecodingError),
+    BadIcon(BadIcon),
+    Os(OsError),
+    Cursor(ExternalError),
+}
+
+impl From<io::Error> for WindowConfigError {
+    fn from(error: io::Error) -> Self {
+        WindowConfigError::Io(error)
+    }
+}
+
+impl From<ron::Error> for WindowConfigError {
+    fn from(error: ron::Error) -> Self {
+        WindowConfigError::Serialization(error)
+    }
+}
+
+impl From<ResourceError> for WindowConfigError {
+    fn from(error: ResourceError) -> Self {
+        WindowConfigError::Resource(error)
+    }
+}
+
+impl From<png::DecodingError> for WindowConfigError {
+    fn from(error: png::DecodingError) -> Self {
+        WindowConfigError::IconDecoding(error)
+    }
+}
+
+impl From<BadIcon> for WindowConfigError {
+    fn from(error: BadIcon) -> Self {
+        WindowConfigError::BadIcon(error)
+    }
+}
+
+impl From<OsError> for WindowConfigError {
+    fn from(error: OsError) -> Self {
+        WindowConfigError::Os(error)
+    }
+}
+
+impl From<ExternalError> for WindowConfigError {
+    fn from(error: ExternalError) -> Self {
+        WindowConfigError::Cursor(error)
+    }
+}
+
+impl std::fmt::Display for WindowConfigError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowConfigError::Io(err) => write!(fmt, "{:?}: {}", self, err),
+            WindowConfigError::Serialization(err) => write!(fmt, "{:?}: {}", self, err),
+            WindowConfigError::Resource(err) => write!(fmt, "{:?}: {}", self, err),
+            WindowConfigError::IconDecoding(err) => write!(fmt, "{:?}: {}", self, err),
+            WindowConfigError::BadIcon(err) => write!(fmt, "{:?}: {}", self, err),
+            WindowConfigError::Os(err) => write!(fmt, "{:?}: {}", self, err),
+            WindowConfigError::Cursor(err) => write!(fmt, "{:?}: {}", self, err),
+        }
+    }
+}
+
+impl std::error::Error for WindowConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WindowConfigError::Io(err) => Some(err),
+            WindowConfigError::Serialization(err) => Some(err),
+            WindowConfigError::Resource(err) => Some(err),
+            WindowConfigError::IconDecoding(err) => Some(err),
+            WindowConfigError::BadIcon(err) => Some(err),
+            WindowConfigError::Os(err) => Some(err),
+            WindowConfigError::Cursor(err) => Some(err),
+        }
+    }
+}
+
+/// Which monitor a fullscreen window goes on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonitorSelection {
+    Primary,
+    /// The monitor at this index in the platform's list of monitors
+    Index(usize),
+    /// The monitor with this name
+    Name(String),
+}
+
+/// Which video mode an exclusive fullscreen window uses. Unset fields pick the largest/highest available.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct VideoModeSelection {
+    pub size: Option<[u32; 2]>,
+    pub bit_depth: Option<u16>,
+    pub refresh_rate: Option<u16>,
+}
+
+impl VideoModeSelection {
+    fn matches(&self, mode: &VideoMode) -> bool {
+        let size = mode.size();
+        self.size.is_none_or(|s| s == [size.width, size.height])
+            && self.bit_depth.is_none_or(|bd| bd == mode.bit_depth())
+            && self.refresh_rate.is_none_or(|rr| rr == mode.refresh_rate())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    /// A window covering the whole monitor, without changing its video mode
+    BorderlessFullscreen(MonitorSelection),
+    /// Takes over the monitor, changing its video mode.
+    /// Falls back to `BorderlessFullscreen` on the same monitor if it has no matching video mode (e.g. after a monitor change).
+    ExclusiveFullscreen {
+        monitor: MonitorSelection,
+        video_mode: VideoModeSelection,
+    },
+}
+
+/// The mouse cursor while it's over the window.
+///
+/// Only the system's cursor icons are supported, not custom cursor images: winit 0.22 has no way to set one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CursorConfig {
+    pub icon: CursorIcon,
+    pub visible: bool,
+    /// Confines the cursor to the window
+    pub grabbed: bool,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            icon: CursorIcon::Default,
+            visible: true,
+            grabbed: false,
+        }
+    }
+}
+
+/// Everything about how a window is created, serializable to disk (as RON) with `save` and `load`.
+///
+/// Sizes are in logical pixels, positions are in physical pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: [u32; 2],
+    /// Where the window's top-left corner goes, or `None` to let the platform decide
+    pub position: Option<[i32; 2]>,
+    pub min_size: Option<[u32; 2]>,
+    pub max_size: Option<[u32; 2]>,
+    pub mode: WindowMode,
+    pub resizable: bool,
+    pub decorations: bool,
+    pub always_on_top: bool,
+    pub maximized: bool,
+    /// Path of a PNG icon, resolved through the default VFS mounts, or absolute
+    pub icon: Option<String>,
+    pub cursor: CursorConfig,
+    /// Whether the window's last position and size should be saved, to be restored on the next launch
+    pub remember_placement: bool,
+}
+
+impl Default for WindowConfig {
+    /// An 800x600 resizable window with the `Thermite Engine` as the title, and the icon from `assets/icon.png`
+    fn default() -> Self {
+        Self {
+            title: format!("Thermite Engine v{}", env!("CARGO_PKG_VERSION")),
+            size: [800, 600],
+            position: None,
+            min_size: None,
+            max_size: None,
+            mode: WindowMode::Windowed,
+            resizable: true,
+            decorations: true,
+            always_on_top: false,
+            maximized: false,
+            icon: Some(String::from("assets/icon.png")),
+            cursor: CursorConfig::default(),
+            remember_placement: true,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// The default configuration, with the given `title` and `size`
+    pub fn new<T: Into<String>>(title: T, size: [u32; 2]) -> Self {
+        Self {
+            title: title.into(),
+            size,
+            ..Self::default()
+        }
+    }
+
+    /// Loads a configuration from the given RON file
+    pub fn load(path: &Path) -> Result<Self, WindowConfigError> {
+        Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Loads a configuration from the given RON file if it exists, or returns `default` if it doesn't
+    pub fn load_or(path: &Path, default: Self) -> Result<Self, WindowConfigError> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(default)
+        }
+    }
+
+    /// Saves this configuration to the given file, as RON
+    pub fn save(&self, path: &Path) -> Result<(), WindowConfigError> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        Ok(fs::write(path, ron)?)
+    }
+
+    /// Records the window's current position and size into this configuration, if `remember_placement` is set.
+    ///
+    /// Fullscreen windows keep their previous windowed placement.
+    pub fn remember(&mut self, window: &WinitWindow) {
+        if !self.remember_placement || window.fullscreen().is_some() {
+            return;
+        }
+        let size: LogicalSize<u32> = window.inner_size().to_logical(window.scale_factor());
+        self.size = [size.width, size.height];
+        if let Ok(position) = window.outer_position() {
+            self.position = Some([position.x, position.y]);
+        }
+    }
+
+    /// Creates a window from this configuration.
+    ///
+    /// A missing or invalid icon, or an exclusive fullscreen video mode the monitor doesn't offer anymore,
+    /// doesn't prevent the window's creation, it's only logged.
+    pub fn build<L: 'static>(
+        &self,
+        target: &EventLoopWindowTarget<L>,
+    ) -> Result<WinitWindow, WindowConfigError> {
+        let mut builder = WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_inner_size(LogicalSize::<u32>::from(self.size))
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_always_on_top(self.always_on_top)
+            .with_maximized(self.maximized);
+        if let Some(min_size) = self.min_size {
+            builder = builder.with_min_inner_size(LogicalSize::<u32>::from(min_size));
+        }
+        if let Some(max_size) = self.max_size {
+            builder = builder.with_max_inner_size(LogicalSize::<u32>::from(max_size));
+        }
+        if let Some(icon_path) = &self.icon {
+            match load_icon(icon_path) {
+                Ok(icon) => builder = builder.with_window_icon(Some(icon)),
+                Err(err) => warn!("Couldn't load window icon {}: {}", icon_path, err),
+            }
+        }
+        let window = builder.build(target)?;
+        // Monitors can only be enumerated through a window (or the event loop before it runs), so fullscreen is set afterwards
+        match self.fullscreen(&window) {
+            Some(fullscreen) => window.set_fullscreen(Some(fullscreen)),
+            None => {
+                if let Some([x, y]) = self.position {
+                    window.set_outer_position(PhysicalPosition::new(x, y));
+                }
+            }
+        }
+        window.set_cursor_icon(self.cursor.icon);
+        window.set_cursor_visible(self.cursor.visible);
+        if self.cursor.grabbed {
+            window.set_cursor_grab(true)?;
+        }
+        Ok(window)
+    }
+
+    fn fullscreen(&self, window: &WinitWindow) -> Option<Fullscreen> {
+        match &self.mode {
+            WindowMode::Windowed => None,
+            WindowMode::BorderlessFullscreen(monitor) => {
+                Some(Fullscreen::Borderless(select_monitor(window, monitor)))
+            }
+            WindowMode::ExclusiveFullscreen {
+                monitor,
+                video_mode,
+            } => {
+                let monitor = select_monitor(window, monitor);
+                let mode = monitor
+                    .video_modes()
+                    .filter(|mode| video_mode.matches(mode))
+                    // Largest, then deepest, then fastest
+                    .max_by_key(|mode| {
+                        let size = mode.size();
+                        (
+                            size.width * size.height,
+                            mode.bit_depth(),
+                            mode.refresh_rate(),
+                        )
+                    });
+                match mode {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                    None => {
+                        warn!(
+                            "No video mode of monitor {:?} matches {:?}, using borderless fullscreen instead",
+                            monitor.name(),
+                            video_mode
+                        );
+                        Some(Fullscreen::Borderless(monitor))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finds the selected monitor, falling back to the primary monitor if it isn't connected
+fn select_monitor(window: &WinitWindow, selection: &MonitorSelection) -> MonitorHandle {
+    let selected = match selection {
+        MonitorSelection::Primary => None,
+        MonitorSelection::Index(idx) => window.available_monitors().nth(*idx),
+        MonitorSelection::Name(name) => window
+            .available_monitors()
+            .find(|monitor| monitor.name().as_ref() == Some(name)),
+    };
+    selected.unwrap_or_else(|| window.primary_monitor())
+}
+
+/// Loads a PNG file into a window `Icon`, from an absolute path, or through the default VFS mounts (see `Vfs::with_default_mounts`)
+pub fn load_icon(path: &str) -> Result<Icon, WindowConfigError> {
+    let bytes = if Path::new(path).is_absolute() {
+        fs::read(path)?
+    } else {
+        Resource::new(Path::new(""))?.load_to_bytes(path, false)?
+    };
+    let mut decoder = png::Decoder::new(bytes.as_slice());
+    // Palettes and low bit depths are expanded, and 16 bit channels stripped, so every pixel is 8 bits per channel
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info()?;
+    let mut buffer = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buffer)?;
+    let rgba = match info.color_type {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer
+            .chunks(3)
+            .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks(2)
+            .flat_map(|ga| vec![ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale | png::ColorType::Indexed => {
+            buffer.iter().flat_map(|g| vec![*g, *g, *g, 255]).collect()
+        }
+    };
+    Ok(Icon::from_rgba(rgba, info.width, info.height)?)
+}